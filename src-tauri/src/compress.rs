@@ -0,0 +1,292 @@
+use crate::database::{get_db_connection, DbResponse};
+use crate::error::AppResult;
+use crate::pool::run_blocking;
+use rusqlite::types::{Value, ValueRef};
+use rusqlite::{params, Connection, Row};
+use serde::Serialize;
+use std::io;
+use tauri::ipc::Channel;
+use tauri::{command, AppHandle};
+
+// 压缩后的正文以 BLOB 存储，开头为格式标记，其后是 zstd 数据；未压缩的正文（包括旧数据）仍是 TEXT
+const ZSTD_MARKER: &[u8] = b"EEZ1";
+// 过短的正文压缩收益很小，保持 TEXT 存储
+const MIN_COMPRESS_BYTES: usize = 256;
+const COMPRESSION_LEVEL: i32 = 3;
+// 重新压缩时每个事务处理的行数
+const RECOMPRESS_BATCH: usize = 200;
+
+// 压缩正文，压缩后没有变小时原样返回 TEXT
+pub fn pack_content(text: &str) -> io::Result<Value> {
+    if text.len() < MIN_COMPRESS_BYTES {
+        return Ok(Value::Text(text.to_string()));
+    }
+    let mut packed = ZSTD_MARKER.to_vec();
+    zstd::stream::copy_encode(text.as_bytes(), &mut packed, COMPRESSION_LEVEL)?;
+    if packed.len() >= text.len() {
+        return Ok(Value::Text(text.to_string()));
+    }
+    Ok(Value::Blob(packed))
+}
+
+// 还原正文：TEXT 原样返回，带格式标记的 BLOB 解压，NULL 视为空字符串
+pub fn unpack_content(value: ValueRef<'_>) -> io::Result<String> {
+    match value {
+        ValueRef::Null => Ok(String::new()),
+        ValueRef::Text(bytes) => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        ValueRef::Blob(bytes) => match bytes.strip_prefix(ZSTD_MARKER) {
+            Some(frame) => String::from_utf8(zstd::stream::decode_all(frame)?)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err)),
+            None => Ok(String::from_utf8_lossy(bytes).into_owned()),
+        },
+        ValueRef::Integer(value) => Ok(value.to_string()),
+        ValueRef::Real(value) => Ok(value.to_string()),
+    }
+}
+
+// 读取查询结果中的正文列。数据库中不再注册解压函数，所有读取正文的查询都在这里解压
+pub fn read_content(row: &Row<'_>, idx: usize) -> Result<String, rusqlite::Error> {
+    let value = row.get_ref(idx)?;
+    unpack_content(value).map_err(|err| {
+        rusqlite::Error::FromSqlConversionFailure(idx, value.data_type(), Box::new(err))
+    })
+}
+
+// 准备写入的正文：存储值与原文长度。长度列由写入方一并写入，列表中的字数统计不必解压正文
+pub struct StoredContent {
+    pub value: Value,
+    pub char_count: i64,
+    pub byte_count: i64,
+}
+
+impl StoredContent {
+    pub fn new(text: &str) -> Result<Self, rusqlite::Error> {
+        Ok(Self {
+            value: pack_content(text)
+                .map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?,
+            char_count: text.chars().count() as i64,
+            byte_count: text.len() as i64,
+        })
+    }
+}
+
+// 重新压缩进度，通过 Channel 发送给前端
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressProgress {
+    pub current: usize,
+    pub total: usize,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CompressResult {
+    // 本次压缩的章节数与历史版本数
+    pub chapters: usize,
+    pub revisions: usize,
+    // 章节与历史版本正文压缩前后占用的字节数
+    pub size_before: i64,
+    pub size_after: i64,
+    pub saved: i64,
+}
+
+fn stored_size(db: &Connection) -> Result<i64, rusqlite::Error> {
+    db.query_row(
+        "SELECT (SELECT COALESCE(SUM(length(CAST(content AS BLOB))), 0) FROM ee_chapter) \
+              + (SELECT COALESCE(SUM(length(CAST(content AS BLOB))), 0) FROM ee_chapter_revision)",
+        [],
+        |row| row.get(0),
+    )
+}
+
+// 仍以 TEXT 存储、长度达到压缩下限的行
+fn pending_ids(db: &Connection, table: &str) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = db.prepare(&format!(
+        "SELECT id FROM {} WHERE typeof(content) = 'text' \
+         AND length(CAST(content AS BLOB)) >= ? ORDER BY id",
+        table
+    ))?;
+    let rows = stmt.query_map(params![MIN_COMPRESS_BYTES as i64], |row| row.get(0))?;
+    rows.collect()
+}
+
+fn compressed_count(db: &Connection, table: &str) -> Result<usize, rusqlite::Error> {
+    db.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM {} WHERE typeof(content) = 'blob'",
+            table
+        ),
+        [],
+        |row| row.get(0),
+    )
+}
+
+// 压缩所有仍以 TEXT 存储的正文，分批提交，中途失败时已提交的批次保持压缩状态
+// 压缩后没有变小的正文仍保持 TEXT，不计入结果。存储值改变后章节统计缓存会在下次查看时重新计算
+fn recompress_library_with(
+    db: &mut Connection,
+    on_progress: &Channel<CompressProgress>,
+) -> AppResult<CompressResult> {
+    let size_before = stored_size(db)?;
+    let chapters_before = compressed_count(db, "ee_chapter")?;
+    let revisions_before = compressed_count(db, "ee_chapter_revision")?;
+    let chapter_ids = pending_ids(db, "ee_chapter")?;
+    let revision_ids = pending_ids(db, "ee_chapter_revision")?;
+    let total = chapter_ids.len() + revision_ids.len();
+
+    let pending = chapter_ids
+        .iter()
+        .map(|id| ("ee_chapter", *id))
+        .chain(revision_ids.iter().map(|id| ("ee_chapter_revision", *id)))
+        .collect::<Vec<_>>();
+    let mut current = 0;
+    for batch in pending.chunks(RECOMPRESS_BATCH) {
+        let tx = db.transaction()?;
+        for (table, id) in batch {
+            // 正文不变，长度与全文索引无需更新
+            let text: String = tx.query_row(
+                &format!("SELECT content FROM {} WHERE id = ?", table),
+                params![id],
+                |row| row.get(0),
+            )?;
+            let packed = pack_content(&text)?;
+            if matches!(packed, Value::Blob(_)) {
+                tx.execute(
+                    &format!("UPDATE {} SET content = ? WHERE id = ?", table),
+                    params![packed, id],
+                )?;
+            }
+        }
+        tx.commit()?;
+        current += batch.len();
+        let _ = on_progress.send(CompressProgress { current, total });
+    }
+
+    let size_after = stored_size(db)?;
+    Ok(CompressResult {
+        chapters: compressed_count(db, "ee_chapter")? - chapters_before,
+        revisions: compressed_count(db, "ee_chapter_revision")? - revisions_before,
+        size_before,
+        size_after,
+        saved: size_before - size_after,
+    })
+}
+
+// 压缩整个书库中尚未压缩的正文（升级前保存的旧数据），返回节省的空间
+// 数据库文件需再执行 vacuum_database 才会变小
+#[command]
+pub async fn recompress_library(
+    on_progress: Channel<CompressProgress>,
+    app_handle: AppHandle,
+) -> DbResponse<CompressResult> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+        recompress_library_with(&mut db, &on_progress)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::search::search_chapters_with;
+    use crate::testutil::{content, open_book};
+
+    fn long_text() -> String {
+        "<p>压缩测试正文 compress</p>\n".repeat(50)
+    }
+
+    fn unpack(value: &Value) -> String {
+        unpack_content(ValueRef::from(value)).unwrap()
+    }
+
+    #[test]
+    fn pack_and_unpack_round_trip() {
+        let text = long_text();
+        let packed = pack_content(&text).unwrap();
+        match &packed {
+            Value::Blob(bytes) => {
+                assert!(bytes.starts_with(ZSTD_MARKER));
+                assert!(bytes.len() < text.len());
+            }
+            other => panic!("应压缩为 BLOB: {:?}", other),
+        }
+        assert_eq!(unpack(&packed), text);
+    }
+
+    #[test]
+    fn short_or_incompressible_text_stays_text() {
+        let short = "短正文";
+        assert_eq!(pack_content(short).unwrap(), Value::Text(short.to_string()));
+
+        // 刚达到压缩下限、没有重复内容的文本压缩后不会变小
+        let mut state: u32 = 1;
+        let noise: String = (0..MIN_COMPRESS_BYTES)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                char::from(b'!' + (state >> 16) as u8 % 94)
+            })
+            .collect();
+        assert_eq!(pack_content(&noise).unwrap(), Value::Text(noise.clone()));
+    }
+
+    #[test]
+    fn legacy_text_and_unmarked_blob_still_read() {
+        let db = open_book(&[("第一章", "甲"), ("第二章", "乙")]);
+        db.execute("UPDATE ee_chapter SET content = '旧正文' WHERE id = 1", [])
+            .unwrap();
+        db.execute(
+            "UPDATE ee_chapter SET content = CAST('旧 BLOB' AS BLOB) WHERE id = 2",
+            [],
+        )
+        .unwrap();
+        assert_eq!(content(&db, 1), "旧正文");
+        assert_eq!(content(&db, 2), "旧 BLOB");
+    }
+
+    #[test]
+    fn recompress_reports_saved_bytes_and_keeps_counts_and_index() {
+        let text = long_text();
+        let mut db = open_book(&[("第一章", &text), ("第二章", "短")]);
+        // 模拟升级前以 TEXT 保存的正文
+        db.execute(
+            "UPDATE ee_chapter SET content = ? WHERE id = 1",
+            params![text],
+        )
+        .unwrap();
+        let count_before: i64 = db
+            .query_row("SELECT charCount FROM ee_chapter WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+
+        let result = recompress_library_with(&mut db, &Channel::new(|_| Ok(()))).unwrap();
+        assert_eq!(result.chapters, 1);
+        assert!(result.saved > 0);
+        assert_eq!(result.saved, result.size_before - result.size_after);
+
+        let types: Vec<String> = db
+            .prepare("SELECT typeof(content) FROM ee_chapter ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(types, vec!["blob", "text"]);
+        assert_eq!(content(&db, 1), text);
+        let count_after: i64 = db
+            .query_row("SELECT charCount FROM ee_chapter WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(count_after, count_before);
+
+        let hits = search_chapters_with(&db, "压缩测试", None, 10, 0).unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].chapter_id, 1);
+        // 再次执行时没有需要压缩的正文
+        let again = recompress_library_with(&mut db, &Channel::new(|_| Ok(()))).unwrap();
+        assert_eq!((again.chapters, again.saved), (0, 0));
+    }
+}
@@ -1,18 +1,39 @@
-use crate::setup::AppState;
-use rusqlite::{params, Connection};
+use crate::compress::{read_content, StoredContent};
+use crate::error::{AppError, AppResult};
+use crate::fileutil::copy_dir_all;
+use crate::metadata::{
+    copy_book_metadata, delete_book_metadata, display_author, generate_book_uuid,
+    load_book_metadata, resolve_book_uuid, set_book_uuid_with, sync_primary_author,
+    write_book_metadata, BookMetadata, Contributor,
+};
+use crate::migration::{run_migrations, MigrationError};
+use crate::pool::{self, run_blocking, DbPool};
+use crate::revision::{
+    snapshot_chapter, snapshot_deleted_chapter, snapshot_for_edit, snapshot_toc, RevisionContext,
+};
+use crate::search::{index_chapter, reindex_chapter};
+use crate::setup::{AppState, MigrationStatus};
+use crate::stats::book_word_count;
+use crate::tag::{copy_book_tags, delete_book_tags};
+use regex::Regex;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Transaction};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
 use std::fs;
-use std::sync::MutexGuard;
+use std::path::{Path, PathBuf};
+use std::sync::{MutexGuard, Once};
 use std::time::SystemTime;
 use tauri::{command, AppHandle, Manager, State};
 
-// 定义通用的数据库响应结构体
-#[derive(Debug, Serialize, Deserialize)]
+// 定义通用的数据库响应结构体，命令失败时 error 为结构化错误（code、messageKey、detail）
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DbResponse<T> {
     success: bool,
     data: Option<T>,
-    error: Option<String>,
+    error: Option<AppError>,
 }
 
 // 成功响应的辅助构造函数
@@ -26,41 +47,53 @@ impl<T: Serialize> DbResponse<T> {
     }
 
     // 错误响应的辅助构造函数
-    pub fn error(error: String) -> Self {
+    pub fn error(error: impl Into<AppError>) -> Self {
         Self {
             success: false,
             data: None,
-            error: Some(error),
+            error: Some(error.into()),
         }
     }
 }
 
-const DB_FILENAME: &str = "books.db";
-
-pub fn get_db_connection<'a>(
-    state: &'a State<'_, AppState>,
-) -> Result<MutexGuard<'a, Connection>, String> {
-    match state.db.lock() {
-        Ok(conn) => Ok(conn),
-        Err(err) => Err(err.to_string()),
+// 命令统一把执行结果包装为响应，不再通过 Err 返回错误
+impl<T: Serialize> From<AppResult<T>> for DbResponse<T> {
+    fn from(result: AppResult<T>) -> Self {
+        match result {
+            Ok(data) => DbResponse::success(data),
+            Err(err) => DbResponse::error(err),
+        }
     }
 }
 
+pub const DB_FILENAME: &str = "books.db";
+pub const BACKUP_DIRNAME: &str = "backups";
+
+pub fn get_db_connection(state: &AppState) -> AppResult<MutexGuard<'_, Connection>> {
+    state.db.writer()
+}
+
+// 获取只读连接，读操作不会被正在执行的写事务阻塞
+pub fn get_read_connection(state: &AppState) -> AppResult<MutexGuard<'_, Connection>> {
+    state.db.reader()
+}
+
 // 添加一个函数来安全关闭数据库连接
 #[command]
-pub fn close_database(state: State<'_, AppState>) -> Result<DbResponse<()>, String> {
-    // 尝试关闭数据库连接
-    if let Ok(mut db_guard) = state.db.lock() {
-        // 断开所有数据库连接
-        *db_guard = Connection::open_in_memory().map_err(|e| e.to_string())?;
-        return Ok(DbResponse::success(()));
+pub fn close_database(app_handle: AppHandle) -> DbResponse<()> {
+    // 断开所有数据库连接（包括只读连接）；加密库尚未解锁时没有打开的连接
+    match app_handle.try_state::<AppState>() {
+        Some(state) => state
+            .db
+            .close()
+            .map_err(|err| err.context("Failed to close database connection"))
+            .into(),
+        None => DbResponse::success(()),
     }
-    Ok(DbResponse::error(
-        "Failed to close database connection".to_string(),
-    ))
 }
 
-pub fn init_db(app_handle: &AppHandle) -> Result<Connection, rusqlite::Error> {
+// key 为加密库的密码，未加密时为 None
+pub fn init_db(app_handle: &AppHandle, key: Option<&str>) -> Result<DbPool, Box<dyn Error>> {
     // 获取应用数据目录并确保它存在
     let app_dir = app_handle
         .path()
@@ -69,48 +102,34 @@ pub fn init_db(app_handle: &AppHandle) -> Result<Connection, rusqlite::Error> {
     fs::create_dir_all(&app_dir).expect("Failed to create app data directory");
 
     let db_path = app_dir.join(DB_FILENAME);
-    let mut db = Connection::open(db_path)?;
+    let mut db = pool::open_writer(&db_path, key)?;
+
+    // 新建的库开启增量整理，删除书籍后可通过 vacuum_database 释放空间；已有的库需完整整理一次才会切换
+    db.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
 
     // 设置WAL模式以提高性能
     db.pragma_update(None, "journal_mode", "WAL")?;
 
-    // 直接创建表（如果不存在）
-    create_tables(&mut db)?;
-
-    Ok(db)
-}
-
-fn create_tables(db: &mut Connection) -> Result<(), rusqlite::Error> {
-    db.execute_batch(
-        "
-        CREATE TABLE IF NOT EXISTS ee_book (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            title TEXT,
-            author TEXT,
-            description TEXT,
-            toc TEXT,
-            isDel INTEGER,
-            createTime TEXT,
-            updateTime TEXT
-        );
-        
-        CREATE TABLE IF NOT EXISTS ee_chapter (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            bookId INTEGER,
-            label TEXT,
-            href TEXT,
-            content TEXT,
-            createTime TEXT,
-            updateTime TEXT
-        );
-    ",
-    )?;
+    // 按 user_version 执行未应用的迁移，升级前备份到 backups 目录
+    run_migrations(&mut db, &app_dir.join(BACKUP_DIRNAME))?;
 
-    Ok(())
+    // 迁移完成后再打开只读连接
+    Ok(DbPool::open(&db_path, db, key)?)
+}
+
+// 获取启动时数据库迁移的结果，失败时返回错误详情（包含备份路径）
+#[command]
+pub fn get_migration_status(
+    state: State<'_, MigrationStatus>,
+) -> DbResponse<Option<MigrationError>> {
+    match state.0.lock() {
+        Ok(err) => DbResponse::success(err.clone()),
+        Err(err) => DbResponse::error(err),
+    }
 }
 
 // 辅助函数：获取当前时间的字符串表示
-fn get_current_time_string() -> String {
+pub fn get_current_time_string() -> String {
     // 将系统时间转换为RFC3339格式的字符串
     let now = SystemTime::now();
     now.duration_since(SystemTime::UNIX_EPOCH)
@@ -118,16 +137,40 @@ fn get_current_time_string() -> String {
         .unwrap_or_else(|_| "0".to_string())
 }
 
+// 读取 ee_setting 中的配置项
+pub fn get_setting(db: &Connection, key: &str) -> Result<Option<String>, rusqlite::Error> {
+    db.query_row(
+        "SELECT value FROM ee_setting WHERE key = ?",
+        params![key],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+// 写入 ee_setting 中的配置项
+pub fn set_setting(db: &Connection, key: &str, value: &str) -> Result<(), rusqlite::Error> {
+    db.execute(
+        "INSERT INTO ee_setting (key, value) VALUES (?, ?) \
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
 // 定义 Book 结构体用于数据传输
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Book {
     pub id: i64,
+    // 书籍唯一标识，导出 EPUB 时作为包标识
+    pub uuid: String,
     pub title: String,
     pub author: String,
     pub description: String,
     pub toc: String,
     pub create_time: String,
     pub update_time: String,
+    // 扩展元数据：作者列表、语言、出版社、系列等
+    pub metadata: BookMetadata,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -140,315 +183,2530 @@ pub struct Chapter {
     pub content: String,
 }
 
-// 添加书籍
+// 添加书籍，metadata 为导入时读取到的扩展元数据，uuid 为导入的书籍标识（为空或已被占用时自动生成）
 #[command]
 pub async fn add_book(
     title: String,
     author: String,
     description: String,
     toc: String,
-    state: State<'_, AppState>,
-) -> Result<DbResponse<Book>, String> {
-    // 从应用状态中获取数据库连接
-    let db = get_db_connection(&state)?;
+    metadata: Option<BookMetadata>,
+    uuid: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<Book> {
+    // 导入大书时写入耗时较长，放到阻塞线程中执行
+    run_blocking(app_handle, move |state| {
+        // 从应用状态中获取数据库连接
+        let mut db = get_db_connection(state)?;
 
-    // 获取当前时间作为创建和更新时间
-    let current_time = get_current_time_string();
+        // 获取当前时间作为创建和更新时间
+        let current_time = get_current_time_string();
 
-    // 执行插入操作
-    match db.execute(
-        "INSERT INTO ee_book (title, author, description, toc, isDel, createTime, updateTime) \
-         VALUES (?, ?, ?, ?, 0, ?, ?)",
-        params![title, author, description, toc, current_time, current_time],
-    ) {
-        Ok(_) => {
-            // 获取最后插入的 ID
-            let last_id = db.last_insert_rowid();
-
-            // 构建成功响应
-            let book = Book {
-                id: last_id,
-                title: title.clone(),
-                author: author.clone(),
-                description: description.clone(),
-                toc: toc.clone(),
-                create_time: current_time.clone(),
-                update_time: current_time.clone(),
-            };
+        // 没有作者列表时以 author 作为第一作者；有作者列表时 author 由列表生成
+        let mut metadata = metadata.unwrap_or_default();
+        if metadata.authors.is_empty() && !author.trim().is_empty() {
+            metadata.authors.push(Contributor {
+                name: author.trim().to_string(),
+                role: crate::metadata::ROLE_AUTHOR.to_string(),
+            });
+        }
+        let author = display_author(&metadata.authors).unwrap_or(author);
 
-            Ok(DbResponse::success(book))
+        // 执行插入操作，书籍、扩展元数据与目录节点在同一事务中写入
+        let tx = db.transaction()?;
+        let uuid = resolve_book_uuid(&tx, uuid.as_deref())?;
+        tx.execute(
+            "INSERT INTO ee_book (uuid, title, author, description, toc, isDel, createTime, updateTime) \
+             VALUES (?, ?, ?, ?, ?, 0, ?, ?)",
+            params![uuid, title, author, description, toc, current_time, current_time],
+        )?;
+        // 获取最后插入的 ID
+        let last_id = tx.last_insert_rowid();
+        write_book_metadata(&tx, last_id, &metadata)?;
+        replace_toc_from_json(&tx, last_id, &toc)?;
+        tx.commit()?;
+
+        Ok(Book {
+            id: last_id,
+            uuid,
+            title,
+            author,
+            description,
+            toc,
+            create_time: current_time.clone(),
+            update_time: current_time,
+            metadata,
+        })
+    })
+    .await
+    .into()
+}
+
+// 写入一个章节的标题与内容（content 为空时只更新标题），返回受影响的行数
+pub fn write_chapter(
+    db: &Connection,
+    id: i64,
+    label: &str,
+    content: Option<&str>,
+    current_time: &str,
+) -> Result<usize, rusqlite::Error> {
+    match content {
+        Some(content) => {
+            let stored = StoredContent::new(content)?;
+            let updated = db.execute(
+                "UPDATE ee_chapter SET label = ?, content = ?, charCount = ?, byteCount = ?, \
+                 updateTime = ? WHERE id = ?",
+                params![
+                    label,
+                    stored.value,
+                    stored.char_count,
+                    stored.byte_count,
+                    current_time,
+                    id
+                ],
+            )?;
+            if updated > 0 {
+                index_chapter(db, id, label, content)?;
+            }
+            Ok(updated)
         }
-        Err(err) => {
-            // 构建错误响应
-            Ok(DbResponse::error(err.to_string()))
+        None => {
+            let updated = db.execute(
+                "UPDATE ee_chapter SET label = ?, updateTime = ? WHERE id = ?",
+                params![label, current_time, id],
+            )?;
+            reindex_chapter(db, id)?;
+            Ok(updated)
         }
     }
 }
 
+// 插入一个章节，正文压缩后写入，返回新章节 id
+pub fn insert_chapter(
+    db: &Connection,
+    book_id: i64,
+    label: &str,
+    href: &str,
+    content: &str,
+    current_time: Option<&str>,
+) -> Result<i64, rusqlite::Error> {
+    let stored = StoredContent::new(content)?;
+    db.prepare_cached(
+        "INSERT INTO ee_chapter \
+         (bookId, label, href, content, charCount, byteCount, createTime, updateTime) \
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+    )?
+    .execute(params![
+        book_id,
+        label,
+        href,
+        stored.value,
+        stored.char_count,
+        stored.byte_count,
+        current_time,
+        current_time
+    ])?;
+    let id = db.last_insert_rowid();
+    index_chapter(db, id, label, content)?;
+    Ok(id)
+}
+
 // 更新章节内容（允许 content 为空）
 #[command]
-pub fn update_chapter(
+pub async fn update_chapter(
     id: i64,
     label: String,
     content: Option<String>,
-    state: State<'_, AppState>,
-) -> Result<DbResponse<()>, String> {
-    let db = get_db_connection(&state)?;
+    app_handle: AppHandle,
+) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+        let current_time = get_current_time_string();
+
+        // 历史版本与新内容在同一事务中写入，写入失败时不会留下多余的版本
+        let tx = db.transaction()?;
+        // 内容有变化时保存历史版本（编辑器频繁保存，按时间窗口合并）
+        if chapter_changed(&tx, id, &label, content.as_deref())? {
+            snapshot_for_edit(&tx, id)?;
+        }
+        write_chapter(&tx, id, &label, content.as_deref(), &current_time)?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+    .into()
+}
+
+// 判断章节的标题或内容是否与数据库中不同，章节不存在时返回 false
+fn chapter_changed(
+    db: &Connection,
+    id: i64,
+    label: &str,
+    content: Option<&str>,
+) -> Result<bool, rusqlite::Error> {
+    let current: Option<(Option<String>, Option<String>)> = db
+        .query_row(
+            "SELECT label, content FROM ee_chapter WHERE id = ?",
+            params![id],
+            |row| Ok((row.get(0)?, Some(read_content(row, 1)?))),
+        )
+        .optional()?;
+    Ok(match current {
+        Some((old_label, old_content)) => {
+            old_label.as_deref() != Some(label)
+                || (content.is_some() && old_content.as_deref() != content)
+        }
+        None => false,
+    })
+}
+
+// 批量更新时的单个章节
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterUpdate {
+    pub id: i64,
+    pub label: String,
+    pub content: Option<String>,
+}
+
+// 批量更新中每个章节的结果，changed 为 false 表示内容与原来相同未写入
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterUpdateResult {
+    pub id: i64,
+    pub changed: bool,
+}
+
+// 在事务中批量写入章节，任一章节不存在或写入失败时返回错误（由调用方回滚）
+// 有变化的章节在写入前保存历史版本，同一批次共用 ctx 中的操作 id
+pub fn update_chapters_tx(
+    tx: &Transaction,
+    chapters: &[ChapterUpdate],
+    ctx: &RevisionContext,
+) -> AppResult<Vec<ChapterUpdateResult>> {
     let current_time = get_current_time_string();
+    let mut results = Vec::with_capacity(chapters.len());
 
-    let sql = if content.is_none() {
-        "UPDATE ee_chapter SET label = ?, updateTime = ? WHERE id = ?"
-    } else {
-        "UPDATE ee_chapter SET label = ?, content = ?, updateTime = ? WHERE id = ?"
-    };
+    for chapter in chapters {
+        let exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM ee_chapter WHERE id = ?)",
+            params![chapter.id],
+            |row| row.get(0),
+        )?;
+        if !exists {
+            return Err(AppError::NotFound(format!("章节 {} 不存在", chapter.id)));
+        }
 
-    let params = if content.is_none() {
-        params![label, current_time, id]
-    } else {
-        params![label, content.unwrap(), current_time, id]
-    };
+        let changed = chapter_changed(tx, chapter.id, &chapter.label, chapter.content.as_deref())?;
+        if changed {
+            snapshot_chapter(tx, chapter.id, ctx).map_err(|err| {
+                AppError::from(err).context(&format!("保存章节 {} 历史版本失败", chapter.id))
+            })?;
+            write_chapter(
+                tx,
+                chapter.id,
+                &chapter.label,
+                chapter.content.as_deref(),
+                &current_time,
+            )
+            .map_err(|err| AppError::from(err).context(&format!("更新章节 {} 失败", chapter.id)))?;
+        }
+        results.push(ChapterUpdateResult {
+            id: chapter.id,
+            changed,
+        });
+    }
+
+    Ok(results)
+}
+
+// 批量更新章节，所有章节在同一事务中写入，失败时全部回滚
+// 传入 operation_id 后可通过 undo_operation 整体撤销
+#[command]
+pub async fn update_chapters(
+    chapters: Vec<ChapterUpdate>,
+    operation_id: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<ChapterUpdateResult>> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+        let tx = db.transaction()?;
+
+        let ctx = RevisionContext::new("batch", operation_id);
+        let results = update_chapters_tx(&tx, &chapters, &ctx)?;
+        tx.commit()?;
+        Ok(results)
+    })
+    .await
+    .into()
+}
+
+// 书籍列表排序方式
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BookSort {
+    #[default]
+    Id,
+    Title,
+    Author,
+    CreateTimeDesc,
+    UpdateTimeDesc,
+}
+
+// 新写入的时间统一为秒级时间戳，旧版本用 datetime('now', 'localtime') 写入的日期字符串仍可能存在，
+// 查询时统一转换为秒级时间戳
+fn timestamp_expr(column: &str) -> String {
+    format!(
+        "CASE WHEN {c} GLOB '[0-9]*' AND {c} NOT LIKE '%-%' THEN CAST({c} AS INTEGER) \
+         ELSE CAST(strftime('%s', {c}, 'utc') AS INTEGER) END",
+        c = column
+    )
+}
 
-    match db.execute(sql, params) {
-        Ok(_) => Ok(DbResponse::success(())),
-        Err(err) => Ok(DbResponse::error(err.to_string())),
+impl BookSort {
+    pub fn to_sql(self) -> String {
+        match self {
+            BookSort::Id => "id".to_string(),
+            BookSort::Title => "title COLLATE NOCASE, id".to_string(),
+            BookSort::Author => "author COLLATE NOCASE, title COLLATE NOCASE, id".to_string(),
+            BookSort::CreateTimeDesc => format!("{} DESC, id DESC", timestamp_expr("createTime")),
+            BookSort::UpdateTimeDesc => format!("{} DESC, id DESC", timestamp_expr("updateTime")),
+        }
     }
 }
 
-// 获取所有书籍
+// 按条件查询未删除的书籍，condition 为 WHERE 中的附加条件
+pub fn load_books_where(
+    db: &Connection,
+    condition: &str,
+    values: Vec<Value>,
+    sort: BookSort,
+) -> Result<Vec<Book>, rusqlite::Error> {
+    let sql = format!(
+        "SELECT id FROM ee_book WHERE isDel = 0 AND ({}) ORDER BY {}",
+        condition,
+        sort.to_sql()
+    );
+    let mut stmt = db.prepare(&sql)?;
+    let ids = stmt
+        .query_map(params_from_iter(values), |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    ids.into_iter().map(|id| load_book(db, id)).collect()
+}
+
+// 获取所有书籍，sort 为空时按 id 排序
 #[command]
-pub fn get_all_books(state: State<'_, AppState>) -> Result<DbResponse<Vec<Book>>, String> {
-    let db = get_db_connection(&state)?;
-    // 准备查询语句，只获取未删除的书籍(isDel=0)
-    let mut stmt = match db.prepare("SELECT * FROM ee_book WHERE isDel = 0") {
-        Ok(statement) => statement,
-        Err(err) => return Err(err.to_string()),
-    };
+pub async fn get_all_books(sort: Option<BookSort>, app_handle: AppHandle) -> DbResponse<Vec<Book>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
 
-    // 执行查询并映射结果到Book结构体向量
-    let books = match stmt.query_map(params![], |row| {
-        Ok(Book {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            author: row.get(2)?,
-            description: row.get(3)?,
-            toc: row.get(4)?,
-            create_time: row.get(6)?,
-            update_time: row.get(7)?,
-        })
-    }) {
-        Ok(rows) => {
-            // 收集查询结果，处理可能的映射错误
-            let mut results = Vec::new();
-            for book_result in rows {
-                match book_result {
-                    Ok(book) => results.push(book),
-                    Err(err) => return Err(err.to_string()),
-                }
-            }
-            results
+        // 只获取未删除的书籍(isDel=0)，目录以 ee_toc_node 为准
+        Ok(load_books_where(
+            &db,
+            "1",
+            Vec::new(),
+            sort.unwrap_or_default(),
+        )?)
+    })
+    .await
+    .into()
+}
+
+// 书籍列表查询条件，title/author 为子串匹配，keyword 同时匹配书名与作者
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BookListQuery {
+    pub keyword: Option<String>,
+    pub title_contains: Option<String>,
+    pub author_contains: Option<String>,
+    // 只返回拥有其中任一标签的书籍
+    pub tag_ids: Option<Vec<i64>>,
+    pub sort: BookSort,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl BookListQuery {
+    // 生成 WHERE 条件以及对应的参数（不含排序与分页）
+    fn to_where_sql(&self) -> (String, Vec<Value>) {
+        let mut conditions = vec!["isDel = 0".to_string()];
+        let mut values = Vec::new();
+
+        if let Some(keyword) = self.keyword.as_deref().filter(|k| !k.is_empty()) {
+            conditions.push("(instr(title, ?) > 0 OR instr(author, ?) > 0)".to_string());
+            values.push(Value::Text(keyword.to_string()));
+            values.push(Value::Text(keyword.to_string()));
         }
-        Err(err) => return Err(err.to_string()),
-    };
+        if let Some(title) = self.title_contains.as_deref().filter(|t| !t.is_empty()) {
+            conditions.push("instr(title, ?) > 0".to_string());
+            values.push(Value::Text(title.to_string()));
+        }
+        if let Some(author) = self.author_contains.as_deref().filter(|a| !a.is_empty()) {
+            conditions.push("instr(author, ?) > 0".to_string());
+            values.push(Value::Text(author.to_string()));
+        }
+        if let Some(tag_ids) = self.tag_ids.as_ref().filter(|ids| !ids.is_empty()) {
+            let marks = vec!["?"; tag_ids.len()].join(", ");
+            conditions.push(format!(
+                "id IN (SELECT bookId FROM ee_book_tag WHERE tagId IN ({}))",
+                marks
+            ));
+            values.extend(tag_ids.iter().map(|id| Value::Integer(*id)));
+        }
+
+        (format!(" WHERE {}", conditions.join(" AND ")), values)
+    }
+}
+
+// 书籍列表中的一项，不包含目录与扩展元数据
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookSummary {
+    pub id: i64,
+    pub title: String,
+    pub author: String,
+    pub chapter_count: i64,
+    // 字数：中日韩文字按字、其他文字按单词统计，不含 HTML 标签，与 book_stats 的 words 一致
+    pub word_count: i64,
+    pub create_time: String,
+    pub update_time: String,
+    // 封面图片的绝对路径，没有封面时为空
+    pub cover_path: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookPage {
+    pub total: i64,
+    pub items: Vec<BookSummary>,
+}
+
+pub fn list_books_with(
+    db: &Connection,
+    app_dir: &Path,
+    query: &BookListQuery,
+) -> AppResult<BookPage> {
+    let (where_sql, mut values) = query.to_where_sql();
+    let total: i64 = db.query_row(
+        &format!("SELECT COUNT(*) FROM ee_book{}", where_sql),
+        params_from_iter(values.iter()),
+        |row| row.get(0),
+    )?;
+
+    // 时间统一返回秒级时间戳字符串，与 add_book 写入的格式一致
+    let mut sql = format!(
+        "SELECT id, title, author, CAST({} AS TEXT), CAST({} AS TEXT), \
+                (SELECT COUNT(*) FROM ee_chapter WHERE bookId = ee_book.id) \
+         FROM ee_book{} ORDER BY {}",
+        timestamp_expr("createTime"),
+        timestamp_expr("updateTime"),
+        where_sql,
+        query.sort.to_sql()
+    );
+    if query.limit.is_some() || query.offset.is_some() {
+        sql.push_str(" LIMIT ? OFFSET ?");
+        values.push(Value::Integer(query.limit.unwrap_or(-1)));
+        values.push(Value::Integer(query.offset.unwrap_or(0).max(0)));
+    }
+
+    let mut stmt = db.prepare(&sql)?;
+    let mut items = stmt
+        .query_map(params_from_iter(values), |row| {
+            let id: i64 = row.get(0)?;
+            let cover = book_cover_path(app_dir, id);
+            Ok(BookSummary {
+                id,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                author: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                create_time: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                update_time: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                chapter_count: row.get(5)?,
+                word_count: 0,
+                cover_path: cover.exists().then(|| cover.to_string_lossy().to_string()),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    // 字数需要去掉标签统计，优先取章节统计缓存
+    for item in items.iter_mut() {
+        item.word_count = book_word_count(db, item.id)?;
+    }
 
-    // 返回成功响应，包含书籍列表
-    Ok(DbResponse::success(books))
+    Ok(BookPage { total, items })
 }
 
+// 分页获取书籍列表（只包含摘要信息，打开书籍时再通过 get_book 获取目录）
 #[command]
-pub fn add_chapter(
+pub async fn list_books(
+    query: Option<BookListQuery>,
+    app_handle: AppHandle,
+) -> DbResponse<BookPage> {
+    run_blocking(app_handle.clone(), move |state| {
+        let app_dir = get_app_dir(&app_handle)?;
+        let db = get_read_connection(state)?;
+
+        list_books_with(&db, &app_dir, &query.unwrap_or_default())
+    })
+    .await
+    .into()
+}
+
+// 获取一本书的完整信息，包括目录与扩展元数据
+#[command]
+pub async fn get_book(id: i64, app_handle: AppHandle) -> DbResponse<Book> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        load_book(&db, id)
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("书籍 {} 不存在", id)))
+    })
+    .await
+    .into()
+}
+
+#[command]
+pub async fn add_chapter(
     book_id: i64,
     label: String,
     href: String,
     content: String,
-    state: State<'_, AppState>,
-) -> Result<DbResponse<i64>, String> {
-    let db = get_db_connection(&state)?;
-
-    // 执行插入操作
-    match db.execute(
-        "INSERT INTO ee_chapter (bookId, label, href, content) \
-         VALUES (?, ?, ?, ?)",
-        params![book_id, label, href, content],
-    ) {
-        Ok(_) => {
-            // 获取最后插入的 ID
-            let last_id = db.last_insert_rowid();
-
-            Ok(DbResponse::success(last_id))
-        }
-        Err(err) => {
-            // 构建错误响应
-            Ok(DbResponse::error(err.to_string()))
-        }
-    }
+    app_handle: AppHandle,
+) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+
+        // 执行插入操作，返回新章节的 ID
+        Ok(insert_chapter(&db, book_id, &label, &href, &content, None)?)
+    })
+    .await
+    .into()
 }
 
 #[command]
-pub fn get_chapter(
-    id: String,
-    state: State<'_, AppState>,
-) -> Result<DbResponse<Vec<Chapter>>, String> {
-    let db = get_db_connection(&state)?;
+pub async fn get_chapter(id: String, app_handle: AppHandle) -> DbResponse<Vec<Chapter>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
 
-    // 执行查询操作
-    let mut stmt = match db.prepare("SELECT * FROM ee_chapter WHERE id = ?") {
-        Ok(statement) => statement,
-        Err(err) => return Err(err.to_string()),
-    };
+        // 执行查询操作
+        let mut stmt =
+            db.prepare("SELECT id, bookId, label, href, content FROM ee_chapter WHERE id = ?")?;
+
+        // 执行查询并映射结果到Chapter结构体向量
+        let rows = stmt.query_map(params![id], row_to_chapter)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    })
+    .await
+    .into()
+}
 
-    // 执行查询并映射结果到Chapter结构体向量
-    let chapters = match stmt.query_map(params![id], |row| {
-        Ok(Chapter {
+// 章节索引项，不包含正文
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterIndex {
+    pub id: i64,
+    pub label: String,
+    pub href: String,
+    // 正文的 UTF-8 字节数
+    pub content_length: i64,
+    // 正文的字符数
+    pub char_count: i64,
+    pub update_time: Option<String>,
+}
+
+pub fn list_chapters_with(
+    db: &Connection,
+    book_id: i64,
+) -> Result<Vec<ChapterIndex>, rusqlite::Error> {
+    // 字节数与字符数由触发器在写入时记录，不需要读取和解压正文
+    let mut stmt = db.prepare(
+        "SELECT id, label, href, COALESCE(byteCount, 0), COALESCE(charCount, 0), updateTime \
+         FROM ee_chapter WHERE bookId = ? ORDER BY id",
+    )?;
+    let rows = stmt.query_map(params![book_id], |row| {
+        Ok(ChapterIndex {
             id: row.get(0)?,
-            book_id: row.get(1)?,
-            label: row.get(2)?,
-            href: row.get(3)?,
-            content: row.get(4)?,
+            label: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            href: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            content_length: row.get(3)?,
+            char_count: row.get(4)?,
+            update_time: row.get(5)?,
         })
-    }) {
-        Ok(rows) => {
-            // 收集查询结果，处理可能的映射错误
-            let mut results = Vec::new();
-            for chapter_result in rows {
-                match chapter_result {
-                    Ok(chapter) => results.push(chapter),
-                    Err(err) => return Err(err.to_string()),
-                }
-            }
-            results
-        }
-        Err(err) => return Err(err.to_string()),
-    };
+    })?;
+    rows.collect()
+}
 
-    // 返回成功响应，包含章节列表
-    Ok(DbResponse::success(chapters))
+// 获取一本书的章节列表（不含正文），正文通过 get_chapter_content 按需读取
+#[command]
+pub async fn list_chapters(book_id: i64, app_handle: AppHandle) -> DbResponse<Vec<ChapterIndex>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        Ok(list_chapters_with(&db, book_id)?)
+    })
+    .await
+    .into()
 }
 
+// 只读取一个章节的正文
 #[command]
-pub fn update_toc(
-    id: i64,
-    toc: String,
-    state: State<'_, AppState>,
-) -> Result<DbResponse<i64>, String> {
-    let db = get_db_connection(&state)?;
+pub async fn get_chapter_content(id: i64, app_handle: AppHandle) -> DbResponse<String> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        let content = db
+            .query_row(
+                "SELECT content FROM ee_chapter WHERE id = ?",
+                params![id],
+                |row| read_content(row, 0),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("章节 {} 不存在", id)))?;
+        Ok(content)
+    })
+    .await
+    .into()
+}
+
+// 整体替换目录（旧接口）：在同一事务中重建 ee_toc_node，并用新的节点重新生成 toc 列以便回退
+#[command]
+pub async fn update_toc(id: i64, toc: String, app_handle: AppHandle) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        // 执行更新操作
+        let tx = db.transaction()?;
+        replace_toc_from_json(&tx, id, &toc)?;
+        sync_toc_column(&tx, id)?;
+        tx.commit()?;
+        // 返回成功响应，包含更新的行数
+        Ok(1)
+    })
+    .await
+    .into()
+}
+
+// 目录节点，subitems 为按 sortIndex 排序的子节点
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TocNode {
+    pub id: i64,
+    pub parent_id: Option<i64>,
+    pub sort_index: i64,
+    pub chapter_id: Option<i64>,
+    pub label: String,
+    pub anchor: Option<String>,
+    pub subitems: Vec<TocNode>,
+}
 
-    // 执行更新操作
-    match db.execute("UPDATE ee_book SET toc = ? WHERE id = ?", params![toc, id]) {
-        Ok(_) => {
-            // 返回成功响应，包含更新的行数
-            Ok(DbResponse::success(1))
+// 读取一本书的全部目录节点并组装成树
+pub fn load_toc_tree(db: &Connection, book_id: i64) -> Result<Vec<TocNode>, rusqlite::Error> {
+    let mut stmt = db.prepare(
+        "SELECT id, parentId, sortIndex, chapterId, label, anchor FROM ee_toc_node \
+         WHERE bookId = ? ORDER BY sortIndex, id",
+    )?;
+    let nodes = stmt
+        .query_map(params![book_id], |row| {
+            Ok(TocNode {
+                id: row.get(0)?,
+                parent_id: row.get(1)?,
+                sort_index: row.get(2)?,
+                chapter_id: row.get(3)?,
+                label: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                anchor: row.get(5)?,
+                subitems: Vec::new(),
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // 按父节点分组后递归组装
+    let mut children: HashMap<Option<i64>, Vec<TocNode>> = HashMap::new();
+    for node in nodes {
+        children.entry(node.parent_id).or_default().push(node);
+    }
+    fn attach(
+        parent: Option<i64>,
+        children: &mut HashMap<Option<i64>, Vec<TocNode>>,
+    ) -> Vec<TocNode> {
+        let mut items = children.remove(&parent).unwrap_or_default();
+        for item in items.iter_mut() {
+            item.subitems = attach(Some(item.id), children);
         }
-        Err(err) => {
-            // 构建错误响应
-            Ok(DbResponse::error(err.to_string()))
+        items
+    }
+    Ok(attach(None, &mut children))
+}
+
+// 目录中章节的阅读顺序，同一章节出现多次时取第一次
+pub fn toc_chapter_order(nodes: &[TocNode], order: &mut Vec<i64>, seen: &mut HashSet<i64>) {
+    for node in nodes {
+        if let Some(chapter_id) = node.chapter_id {
+            if seen.insert(chapter_id) {
+                order.push(chapter_id);
+            }
         }
+        toc_chapter_order(&node.subitems, order, seen);
     }
 }
 
-#[command]
-pub fn get_chapter_where(
-    where_str: String, // 已修改为 String 类型
-    state: State<'_, AppState>,
-) -> Result<DbResponse<Vec<Chapter>>, String> {
-    let db = get_db_connection(&state)?;
+// 书籍全部章节的阅读顺序：先按目录顺序，不在目录中的章节按 id 排在最后
+pub fn reading_order(db: &Connection, book_id: i64) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = db.prepare("SELECT id FROM ee_chapter WHERE bookId = ? ORDER BY id")?;
+    let chapter_ids = stmt
+        .query_map(params![book_id], |row| row.get::<_, i64>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    let existing: HashSet<i64> = chapter_ids.iter().copied().collect();
+
+    let mut order = Vec::new();
+    toc_chapter_order(
+        &load_toc_tree(db, book_id)?,
+        &mut order,
+        &mut HashSet::new(),
+    );
+    order.retain(|id| existing.contains(id));
+    let listed: HashSet<i64> = order.iter().copied().collect();
+    order.extend(chapter_ids.into_iter().filter(|id| !listed.contains(id)));
+    Ok(order)
+}
 
-    // 构建 SQL 查询语句
-    let sql = format!("SELECT * FROM ee_chapter WHERE {}", where_str);
+// 将目录树转换为前端使用的 JSON（id / label / href / subitems，id 为目录节点 id，href 为章节 id）
+pub fn toc_to_json(db: &Connection, book_id: i64) -> Result<String, rusqlite::Error> {
+    fn to_value(nodes: &[TocNode]) -> serde_json::Value {
+        serde_json::Value::Array(
+            nodes
+                .iter()
+                .map(|node| {
+                    let href = match (&node.chapter_id, &node.anchor) {
+                        (Some(id), Some(anchor)) => serde_json::json!(format!("{}#{}", id, anchor)),
+                        (Some(id), None) => serde_json::json!(id),
+                        (None, _) => serde_json::Value::Null,
+                    };
+                    let subitems = if node.subitems.is_empty() {
+                        serde_json::Value::Null
+                    } else {
+                        to_value(&node.subitems)
+                    };
+                    serde_json::json!({
+                        "id": node.id,
+                        "label": node.label,
+                        "href": href,
+                        "subitems": subitems
+                    })
+                })
+                .collect(),
+        )
+    }
+    Ok(to_value(&load_toc_tree(db, book_id)?).to_string())
+}
 
-    // 打印 SQL 语句到终端
-    println!("[SQL] 执行查询: {}", sql);
+// 解析目录 JSON 中的 href：数字或 "章节id#锚点"
+fn parse_toc_href(href: &serde_json::Value) -> (Option<i64>, Option<String>) {
+    match href {
+        serde_json::Value::Number(num) => (num.as_i64(), None),
+        serde_json::Value::String(text) => {
+            let (id, anchor) = match text.split_once('#') {
+                Some((id, anchor)) => (id, Some(anchor.to_string())),
+                None => (text.as_str(), None),
+            };
+            (id.trim().parse().ok(), anchor.filter(|a| !a.is_empty()))
+        }
+        _ => (None, None),
+    }
+}
 
-    // 如果需要更详细的日志，也可以打印传入的 where 条件
-    println!("[SQL] WHERE 条件: {}", where_str);
+// 用目录 JSON 重建一本书的 ee_toc_node，JSON 为空时清空目录
+pub fn replace_toc_from_json(
+    tx: &Transaction,
+    book_id: i64,
+    toc: &str,
+) -> Result<(), rusqlite::Error> {
+    tx.execute("DELETE FROM ee_toc_node WHERE bookId = ?", params![book_id])?;
+    if toc.trim().is_empty() {
+        return Ok(());
+    }
+    let items: serde_json::Value = serde_json::from_str(toc)
+        .map_err(|err| rusqlite::Error::ToSqlConversionFailure(Box::new(err)))?;
 
-    // 执行查询操作
-    let mut stmt = match db.prepare(&sql) {
-        Ok(statement) => statement,
-        Err(err) => {
-            // 打印错误信息
-            println!("[SQL ERROR] 准备语句失败: {}", err);
-            return Err(err.to_string());
+    fn insert_items(
+        tx: &Transaction,
+        book_id: i64,
+        parent_id: Option<i64>,
+        items: &serde_json::Value,
+    ) -> Result<(), rusqlite::Error> {
+        let items = match items.as_array() {
+            Some(items) => items,
+            None => return Ok(()),
+        };
+        for (index, item) in items.iter().enumerate() {
+            let (chapter_id, anchor) = parse_toc_href(&item["href"]);
+            let label = item["label"].as_str().unwrap_or_default();
+            tx.execute(
+                "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label, anchor) \
+                 VALUES (?, ?, ?, ?, ?, ?)",
+                params![book_id, parent_id, index as i64, chapter_id, label, anchor],
+            )?;
+            let id = tx.last_insert_rowid();
+            insert_items(tx, book_id, Some(id), &item["subitems"])?;
         }
-    };
+        Ok(())
+    }
+    insert_items(tx, book_id, None, &items)
+}
 
-    // 执行查询并映射结果到Chapter结构体
-    let chapters = match stmt.query_map([], |row| {
-        Ok(Chapter {
-            id: row.get(0)?,
-            book_id: row.get(1)?,
-            label: row.get(2)?,
-            href: row.get(3)?,
-            content: row.get(4)?,
-        })
-    }) {
-        Ok(rows) => {
-            // 收集查询结果，处理可能的映射错误
-            let mut results = Vec::new();
-            for chapter_result in rows {
-                match chapter_result {
-                    Ok(chapter) => results.push(chapter),
-                    Err(err) => {
-                        println!("[SQL ERROR] 映射结果失败: {}", err);
-                        return Err(err.to_string());
-                    }
-                }
-            }
-            // 打印查询结果数量
-            println!("[SQL] 查询完成，返回 {} 条记录", results.len());
-            results
+// 目录节点在树中的位置
+struct TocPosition {
+    book_id: i64,
+    parent_id: Option<i64>,
+}
+
+fn get_toc_position(tx: &Transaction, id: i64) -> Result<TocPosition, rusqlite::Error> {
+    tx.query_row(
+        "SELECT bookId, parentId FROM ee_toc_node WHERE id = ?",
+        params![id],
+        |row| {
+            Ok(TocPosition {
+                book_id: row.get(0)?,
+                parent_id: row.get(1)?,
+            })
+        },
+    )
+}
+
+// 同一父节点下的子节点 id，按顺序排列
+pub fn toc_sibling_ids(
+    tx: &Transaction,
+    book_id: i64,
+    parent_id: Option<i64>,
+) -> Result<Vec<i64>, rusqlite::Error> {
+    let mut stmt = tx.prepare(
+        "SELECT id FROM ee_toc_node WHERE bookId = ? AND parentId IS ? ORDER BY sortIndex, id",
+    )?;
+    let ids = stmt
+        .query_map(params![book_id, parent_id], |row| row.get(0))?
+        .collect();
+    ids
+}
+
+// 按给定顺序重写父节点与 sortIndex
+pub fn write_toc_order(
+    tx: &Transaction,
+    parent_id: Option<i64>,
+    ids: &[i64],
+) -> Result<(), rusqlite::Error> {
+    let mut stmt = tx.prepare("UPDATE ee_toc_node SET parentId = ?, sortIndex = ? WHERE id = ?")?;
+    for (index, id) in ids.iter().enumerate() {
+        stmt.execute(params![parent_id, index as i64, id])?;
+    }
+    Ok(())
+}
+
+// 将节点移动到 new_parent_id 下的 index 位置（index 超出范围时追加到末尾）
+fn move_toc_node_tx(
+    tx: &Transaction,
+    id: i64,
+    new_parent_id: Option<i64>,
+    index: usize,
+) -> AppResult<()> {
+    let pos = get_toc_position(tx, id)?;
+
+    // 目标父节点必须属于同一本书，且不能是自身或自身的子孙
+    let mut ancestor = new_parent_id;
+    while let Some(ancestor_id) = ancestor {
+        if ancestor_id == id {
+            return Err(AppError::InvalidInput(
+                "不能将目录移动到自身或其子目录下".to_string(),
+            ));
         }
-        Err(err) => {
-            println!("[SQL ERROR] 执行查询失败: {}", err);
-            return Err(err.to_string());
+        let parent = get_toc_position(tx, ancestor_id)?;
+        if parent.book_id != pos.book_id {
+            return Err(AppError::InvalidInput("目标目录不属于同一本书".to_string()));
         }
+        ancestor = parent.parent_id;
+    }
+
+    let mut old_siblings = toc_sibling_ids(tx, pos.book_id, pos.parent_id)?;
+    old_siblings.retain(|sibling| *sibling != id);
+    write_toc_order(tx, pos.parent_id, &old_siblings)?;
+
+    let mut new_siblings = if new_parent_id == pos.parent_id {
+        old_siblings
+    } else {
+        toc_sibling_ids(tx, pos.book_id, new_parent_id)?
     };
+    new_siblings.insert(index.min(new_siblings.len()), id);
+    Ok(write_toc_order(tx, new_parent_id, &new_siblings)?)
+}
 
-    // 返回成功响应，包含章节列表
-    Ok(DbResponse::success(chapters))
+// 在事务中执行目录操作，op 返回被修改的书籍 id 与结果，提交前同步该书的 toc 列
+async fn with_toc_transaction<T>(
+    app_handle: AppHandle,
+    op: impl FnOnce(&Transaction) -> AppResult<(i64, T)> + Send + 'static,
+) -> DbResponse<T>
+where
+    T: Serialize + Send + 'static,
+{
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+        let tx = db.transaction()?;
+        let (book_id, value) = op(&tx)?;
+        sync_toc_column(&tx, book_id)?;
+        tx.commit()?;
+        Ok(value)
+    })
+    .await
+    .into()
 }
 
+// 获取目录树
 #[command]
-pub fn delete_book(id: i64, state: State<'_, AppState>) -> Result<DbResponse<i64>, String> {
-    let db = get_db_connection(&state)?;
+pub async fn get_toc_tree(book_id: i64, app_handle: AppHandle) -> DbResponse<Vec<TocNode>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        Ok(load_toc_tree(&db, book_id)?)
+    })
+    .await
+    .into()
+}
 
-    // 执行删除操作（逻辑删除，将 isDel 设置为 1）
-    match db.execute(
-        "UPDATE ee_book SET isDel = 1, updateTime = datetime('now', 'localtime') WHERE id = ?",
-        params![id],
-    ) {
-        Ok(_) => {
-            // 返回成功响应，包含更新的行数
-            Ok(DbResponse::success(1))
+// 插入目录节点，index 为空时追加到末尾，返回新节点 id
+#[command]
+pub async fn insert_toc_node(
+    book_id: i64,
+    parent_id: Option<i64>,
+    index: Option<usize>,
+    chapter_id: Option<i64>,
+    label: String,
+    anchor: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<i64> {
+    with_toc_transaction(app_handle, move |tx| {
+        if let Some(parent_id) = parent_id {
+            let parent = get_toc_position(tx, parent_id)?;
+            if parent.book_id != book_id {
+                return Err(AppError::InvalidInput("父目录不属于该书籍".to_string()));
+            }
         }
-        Err(err) => {
-            // 构建错误响应
-            Ok(DbResponse::error(err.to_string()))
+        let mut siblings = toc_sibling_ids(tx, book_id, parent_id)?;
+        tx.execute(
+            "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label, anchor) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                book_id,
+                parent_id,
+                siblings.len() as i64,
+                chapter_id,
+                label,
+                anchor
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        siblings.insert(index.unwrap_or(siblings.len()).min(siblings.len()), id);
+        write_toc_order(tx, parent_id, &siblings)?;
+        Ok((book_id, id))
+    })
+    .await
+}
+
+// 移动目录节点（拖拽）
+#[command]
+pub async fn move_toc_node(
+    id: i64,
+    parent_id: Option<i64>,
+    index: usize,
+    app_handle: AppHandle,
+) -> DbResponse<()> {
+    with_toc_transaction(app_handle, move |tx| {
+        let pos = get_toc_position(tx, id)?;
+        move_toc_node_tx(tx, id, parent_id, index)?;
+        Ok((pos.book_id, ()))
+    })
+    .await
+}
+
+// 增加缩进：成为前一个兄弟节点的最后一个子节点
+#[command]
+pub async fn indent_toc_node(id: i64, app_handle: AppHandle) -> DbResponse<()> {
+    with_toc_transaction(app_handle, move |tx| {
+        let pos = get_toc_position(tx, id)?;
+        let siblings = toc_sibling_ids(tx, pos.book_id, pos.parent_id)?;
+        let index = siblings.iter().position(|s| *s == id).unwrap_or(0);
+        if index == 0 {
+            return Err(AppError::InvalidInput(
+                "第一个目录项无法增加缩进".to_string(),
+            ));
         }
+        move_toc_node_tx(tx, id, Some(siblings[index - 1]), usize::MAX)?;
+        Ok((pos.book_id, ()))
+    })
+    .await
+}
+
+// 减少缩进：移动到父节点之后
+#[command]
+pub async fn outdent_toc_node(id: i64, app_handle: AppHandle) -> DbResponse<()> {
+    with_toc_transaction(app_handle, move |tx| {
+        let pos = get_toc_position(tx, id)?;
+        let parent_id = match pos.parent_id {
+            Some(parent_id) => parent_id,
+            None => return Err(AppError::InvalidInput("顶级目录项无法减少缩进".to_string())),
+        };
+        let parent = get_toc_position(tx, parent_id)?;
+        let parent_siblings = toc_sibling_ids(tx, parent.book_id, parent.parent_id)?;
+        let index = parent_siblings
+            .iter()
+            .position(|s| *s == parent_id)
+            .unwrap_or(parent_siblings.len());
+        move_toc_node_tx(tx, id, parent.parent_id, index + 1)?;
+        Ok((pos.book_id, ()))
+    })
+    .await
+}
+
+// 删除目录节点及其所有子节点，delete_chapters 为 true 时同时删除对应章节，返回书籍 id
+fn delete_toc_node_tx(
+    tx: &Transaction,
+    id: i64,
+    delete_chapters: bool,
+    ctx: &RevisionContext,
+) -> AppResult<i64> {
+    let pos = get_toc_position(tx, id)?;
+    let subtree = "WITH RECURSIVE subtree(id) AS ( \
+             SELECT ?1 UNION ALL \
+             SELECT n.id FROM ee_toc_node n JOIN subtree s ON n.parentId = s.id)";
+    let chapter_ids: Vec<i64> = if delete_chapters {
+        let mut stmt = tx.prepare(&format!(
+            "{} SELECT DISTINCT chapterId FROM ee_toc_node \
+             WHERE id IN subtree AND chapterId IS NOT NULL",
+            subtree
+        ))?;
+        let rows = stmt.query_map(params![id], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    } else {
+        Vec::new()
+    };
+
+    // 删除章节前保存目录与章节内容，可通过 undo_operation 撤销
+    if !chapter_ids.is_empty() {
+        snapshot_toc(tx, pos.book_id, ctx)?;
+    }
+    for chapter_id in &chapter_ids {
+        snapshot_deleted_chapter(tx, *chapter_id, ctx)?;
+        tx.execute("DELETE FROM ee_chapter WHERE id = ?", params![chapter_id])?;
+    }
+    tx.execute(
+        &format!("{} DELETE FROM ee_toc_node WHERE id IN subtree", subtree),
+        params![id],
+    )?;
+
+    // 目录中其他位置指向已删除章节的节点（如锚点）：没有子节点的直接删除，有子节点的只保留标题
+    for chapter_id in &chapter_ids {
+        tx.execute(
+            "DELETE FROM ee_toc_node WHERE chapterId = ?1 \
+             AND NOT EXISTS (SELECT 1 FROM ee_toc_node c WHERE c.parentId = ee_toc_node.id)",
+            params![chapter_id],
+        )?;
+        tx.execute(
+            "UPDATE ee_toc_node SET chapterId = NULL, anchor = NULL WHERE chapterId = ?",
+            params![chapter_id],
+        )?;
     }
+
+    let siblings = toc_sibling_ids(tx, pos.book_id, pos.parent_id)?;
+    write_toc_order(tx, pos.parent_id, &siblings)?;
+    Ok(pos.book_id)
 }
 
+// 删除目录节点及其所有子节点，delete_chapters 为 true 时同时删除对应章节。
+// 删除了章节时返回操作 id，可通过 undo_operation 撤销
 #[command]
-pub fn update_book(
+pub async fn delete_toc_node(
     id: i64,
-    title: String,
-    author: String,
-    description: String,
-    state: State<'_, AppState>,
-) -> Result<DbResponse<i64>, String> {
-    let db = get_db_connection(&state)?;
-
-    // 执行更新操作
-    match db.execute(
-        "UPDATE ee_book SET title = ?, author = ?, description = ?, updateTime = datetime('now', 'localtime') WHERE id = ?",
-        params![title, author, description, id],
-    ) {
-        Ok(_) => {
-            // 返回成功响应，包含更新的行数
-            Ok(DbResponse::success(1))
+    delete_chapters: bool,
+    operation_id: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<Option<String>> {
+    with_toc_transaction(app_handle, move |tx| {
+        let ctx = RevisionContext::new("delete-toc", operation_id);
+        let book_id = delete_toc_node_tx(tx, id, delete_chapters, &ctx)?;
+        let operation_id = delete_chapters.then_some(ctx.operation_id);
+        Ok((book_id, operation_id))
+    })
+    .await
+}
+
+// 章节查询排序方式
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ChapterOrder {
+    #[default]
+    Id,
+    IdDesc,
+    Label,
+    UpdateTimeDesc,
+}
+
+impl ChapterOrder {
+    fn to_sql(self) -> &'static str {
+        match self {
+            ChapterOrder::Id => "id ASC",
+            ChapterOrder::IdDesc => "id DESC",
+            ChapterOrder::Label => "label ASC, id ASC",
+            ChapterOrder::UpdateTimeDesc => "CAST(updateTime AS INTEGER) DESC, id ASC",
         }
-        Err(err) => {
-            // 构建错误响应
-            Ok(DbResponse::error(err.to_string()))
+    }
+}
+
+// 章节查询条件，由前端传入，所有条件都以绑定参数的方式拼接
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ChapterQuery {
+    pub book_id: Option<i64>,
+    pub ids: Option<Vec<i64>>,
+    pub label_contains: Option<String>,
+    pub href: Option<String>,
+    // 更新时间晚于该时间戳（秒）
+    pub updated_after: Option<i64>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+    pub order: ChapterOrder,
+}
+
+impl ChapterQuery {
+    // 生成 WHERE/ORDER/LIMIT 子句以及对应的参数
+    fn to_sql(&self) -> (String, Vec<Value>) {
+        let mut conditions = Vec::new();
+        let mut values = Vec::new();
+
+        if let Some(book_id) = self.book_id {
+            conditions.push("bookId = ?".to_string());
+            values.push(Value::Integer(book_id));
+        }
+        if let Some(ids) = &self.ids {
+            if ids.is_empty() {
+                conditions.push("0".to_string());
+            } else {
+                let marks = vec!["?"; ids.len()].join(", ");
+                conditions.push(format!("id IN ({})", marks));
+                values.extend(ids.iter().map(|id| Value::Integer(*id)));
+            }
+        }
+        if let Some(label) = &self.label_contains {
+            conditions.push("instr(label, ?) > 0".to_string());
+            values.push(Value::Text(label.clone()));
+        }
+        if let Some(href) = &self.href {
+            conditions.push("href = ?".to_string());
+            values.push(Value::Text(href.clone()));
+        }
+        if let Some(updated_after) = self.updated_after {
+            conditions.push("CAST(updateTime AS INTEGER) > ?".to_string());
+            values.push(Value::Integer(updated_after));
+        }
+
+        let mut sql = String::new();
+        if !conditions.is_empty() {
+            sql.push_str(" WHERE ");
+            sql.push_str(&conditions.join(" AND "));
         }
+        sql.push_str(" ORDER BY ");
+        sql.push_str(self.order.to_sql());
+        if self.limit.is_some() || self.offset.is_some() {
+            sql.push_str(" LIMIT ? OFFSET ?");
+            values.push(Value::Integer(self.limit.unwrap_or(-1)));
+            values.push(Value::Integer(self.offset.unwrap_or(0).max(0)));
+        }
+
+        (sql, values)
+    }
+}
+
+fn row_to_chapter(row: &rusqlite::Row) -> Result<Chapter, rusqlite::Error> {
+    Ok(Chapter {
+        id: row.get(0)?,
+        book_id: row.get(1)?,
+        label: row.get(2)?,
+        href: row.get(3)?,
+        content: read_content(row, 4)?,
+    })
+}
+
+pub fn query_chapters_with(
+    db: &Connection,
+    query: &ChapterQuery,
+) -> Result<Vec<Chapter>, rusqlite::Error> {
+    let (clause, values) = query.to_sql();
+    let sql = format!(
+        "SELECT id, bookId, label, href, content FROM ee_chapter{}",
+        clause
+    );
+    let mut stmt = db.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values), row_to_chapter)?;
+    rows.collect()
+}
+
+// 只查询符合条件的章节 id，不读取正文
+pub fn query_chapter_ids(
+    db: &Connection,
+    query: &ChapterQuery,
+) -> Result<Vec<i64>, rusqlite::Error> {
+    let (clause, values) = query.to_sql();
+    let mut stmt = db.prepare(&format!("SELECT id FROM ee_chapter{}", clause))?;
+    let rows = stmt.query_map(params_from_iter(values), |row| row.get(0))?;
+    rows.collect()
+}
+
+// 按条件查询章节
+#[command]
+pub async fn query_chapters(
+    query: ChapterQuery,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<Chapter>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        Ok(query_chapters_with(&db, &query)?)
+    })
+    .await
+    .into()
+}
+
+// 已弃用：请改用 query_chapters。
+// 为兼容旧前端，只接受 "bookId = 数字" 或 "id = 数字" 形式的条件，不再拼接任意 SQL
+#[command]
+pub async fn get_chapter_where(
+    where_str: String,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<Chapter>> {
+    // 只在第一次调用时提示，避免旧前端频繁调用时刷屏
+    static DEPRECATION_NOTICE: Once = Once::new();
+    DEPRECATION_NOTICE.call_once(|| {
+        eprintln!("[DB] get_chapter_where 已弃用，请使用 query_chapters");
+    });
+
+    run_blocking(app_handle, move |state| {
+        let query = parse_legacy_where(&where_str).ok_or_else(|| {
+            AppError::InvalidInput(format!(
+                "不支持的查询条件: {}，请使用 query_chapters",
+                where_str
+            ))
+        })?;
+
+        let db = get_read_connection(state)?;
+        Ok(query_chapters_with(&db, &query)?)
+    })
+    .await
+    .into()
+}
+
+// 解析旧接口的简单等值条件
+fn parse_legacy_where(where_str: &str) -> Option<ChapterQuery> {
+    let (column, value) = where_str.split_once('=')?;
+    let value: i64 = value.trim().parse().ok()?;
+    match column.trim() {
+        "bookId" => Some(ChapterQuery {
+            book_id: Some(value),
+            ..Default::default()
+        }),
+        "id" => Some(ChapterQuery {
+            ids: Some(vec![value]),
+            ..Default::default()
+        }),
+        _ => None,
+    }
+}
+
+#[command]
+pub async fn delete_book(id: i64, app_handle: AppHandle) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+
+        // 执行删除操作（逻辑删除，将 isDel 设置为 1）
+        db.execute(
+            "UPDATE ee_book SET isDel = 1, deleteTime = ?1, updateTime = ?1 WHERE id = ?2",
+            params![get_current_time_string(), id],
+        )?;
+        // 返回成功响应，包含更新的行数
+        Ok(1)
+    })
+    .await
+    .into()
+}
+
+const IMAGES_DIRNAME: &str = "images";
+const COVERS_DIRNAME: &str = "covers";
+const EPUB_DIRNAME: &str = "epub";
+const SETTING_TRASH_AUTO_PURGE_DAYS: &str = "trash.autoPurgeDays";
+
+// 书籍在磁盘上的资源：封面 covers/{id}.jpg 与图片目录 epub/{id}
+pub fn book_cover_path(app_dir: &Path, id: i64) -> PathBuf {
+    app_dir.join(COVERS_DIRNAME).join(format!("{}.jpg", id))
+}
+
+pub fn book_epub_dir(app_dir: &Path, id: i64) -> PathBuf {
+    app_dir.join(EPUB_DIRNAME).join(id.to_string())
+}
+
+fn get_app_dir(app_handle: &AppHandle) -> AppResult<PathBuf> {
+    Ok(app_handle.path().app_data_dir()?)
+}
+
+// 回收站中的书籍
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeletedBook {
+    pub id: i64,
+    pub title: String,
+    pub author: String,
+    pub delete_time: Option<String>,
+    pub chapter_count: i64,
+}
+
+// 获取回收站中的书籍，最近删除的在前
+#[command]
+pub async fn list_deleted_books(app_handle: AppHandle) -> DbResponse<Vec<DeletedBook>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        let mut stmt = db.prepare(
+            "SELECT b.id, b.title, b.author, b.deleteTime, \
+                    (SELECT COUNT(*) FROM ee_chapter c WHERE c.bookId = b.id) \
+             FROM ee_book b WHERE b.isDel = 1 \
+             ORDER BY CAST(b.deleteTime AS INTEGER) DESC, b.id DESC",
+        )?;
+        let rows = stmt.query_map(params![], |row| {
+            Ok(DeletedBook {
+                id: row.get(0)?,
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                author: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                delete_time: row.get(3)?,
+                chapter_count: row.get(4)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    })
+    .await
+    .into()
+}
+
+// 从回收站恢复书籍
+#[command]
+pub async fn restore_book(id: i64, app_handle: AppHandle) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+
+        let restored = db.execute(
+            "UPDATE ee_book SET isDel = 0, deleteTime = NULL, updateTime = ? \
+             WHERE id = ? AND isDel = 1",
+            params![get_current_time_string(), id],
+        )?;
+        if restored == 0 {
+            return Err(AppError::NotFound(format!("回收站中没有书籍 {}", id)));
+        }
+        Ok(1)
+    })
+    .await
+    .into()
+}
+
+// 在事务中删除书籍的所有数据库记录
+fn purge_book_rows(tx: &Transaction, id: i64) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "DELETE FROM ee_chapter_revision WHERE bookId = ?",
+        params![id],
+    )?;
+    tx.execute("DELETE FROM ee_toc_revision WHERE bookId = ?", params![id])?;
+    tx.execute("DELETE FROM ee_toc_node WHERE bookId = ?", params![id])?;
+    tx.execute("DELETE FROM ee_chapter WHERE bookId = ?", params![id])?;
+    delete_book_metadata(tx, id)?;
+    delete_book_tags(tx, id)?;
+    tx.execute("DELETE FROM ee_book WHERE id = ?", params![id])?;
+    Ok(())
+}
+
+// 删除书籍在磁盘上的封面与图片目录
+fn remove_book_assets(app_dir: &Path, id: i64) -> AppResult<()> {
+    let cover = book_cover_path(app_dir, id);
+    if cover.exists() {
+        fs::remove_file(&cover).map_err(|e| AppError::from(e).context("删除封面失败"))?;
+    }
+    let epub_dir = book_epub_dir(app_dir, id);
+    if epub_dir.exists() {
+        fs::remove_dir_all(&epub_dir).map_err(|e| AppError::from(e).context("删除图片目录失败"))?;
+    }
+    Ok(())
+}
+
+// 彻底删除回收站中的书籍：先在事务中删除记录，成功后再删除磁盘资源
+pub fn purge_books(db: &mut Connection, app_dir: &Path, ids: &[i64]) -> AppResult<()> {
+    let tx = db.transaction()?;
+    for id in ids {
+        let is_deleted: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM ee_book WHERE id = ? AND isDel = 1)",
+            params![id],
+            |row| row.get(0),
+        )?;
+        if !is_deleted {
+            return Err(AppError::NotFound(format!("回收站中没有书籍 {}", id)));
+        }
+        purge_book_rows(&tx, *id)?;
+    }
+    tx.commit()?;
+
+    for id in ids {
+        remove_book_assets(app_dir, *id)?;
+    }
+    Ok(())
+}
+
+// 彻底删除书籍（仅限回收站中的书籍）
+#[command]
+pub async fn purge_book(id: i64, app_handle: AppHandle) -> DbResponse<()> {
+    run_blocking(app_handle.clone(), move |state| {
+        let app_dir = get_app_dir(&app_handle)?;
+        let mut db = get_db_connection(state)?;
+
+        purge_books(&mut db, &app_dir, &[id])
+    })
+    .await
+    .into()
+}
+
+// 清理删除时间超过自动清理天数的书籍（启动时调用），返回清理的数量
+pub fn purge_expired_books(db: &mut Connection, app_dir: &Path) -> AppResult<usize> {
+    let days: i64 = get_setting(db, SETTING_TRASH_AUTO_PURGE_DAYS)?
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(0);
+    if days <= 0 {
+        return Ok(0);
+    }
+    let now: i64 = get_current_time_string().parse().unwrap_or(0);
+    let ids: Vec<i64> = {
+        let mut stmt = db.prepare(
+            "SELECT id FROM ee_book WHERE isDel = 1 AND CAST(deleteTime AS INTEGER) < ?",
+        )?;
+        let rows = stmt.query_map(params![now - days * 86400], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    purge_books(db, app_dir, &ids)?;
+    Ok(ids.len())
+}
+
+// 获取回收站自动清理天数，0 表示不自动清理
+#[command]
+pub async fn get_trash_auto_purge_days(app_handle: AppHandle) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        let value = get_setting(&db, SETTING_TRASH_AUTO_PURGE_DAYS)?;
+        Ok(value.and_then(|v| v.parse().ok()).unwrap_or(0))
+    })
+    .await
+    .into()
+}
+
+// 设置回收站自动清理天数，下次启动时生效
+#[command]
+pub async fn set_trash_auto_purge_days(days: i64, app_handle: AppHandle) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+        Ok(set_setting(
+            &db,
+            SETTING_TRASH_AUTO_PURGE_DAYS,
+            &days.max(0).to_string(),
+        )?)
+    })
+    .await
+    .into()
+}
+
+// 更新书籍信息，metadata 为空时保留原有的扩展元数据
+#[command]
+pub async fn update_book(
+    id: i64,
+    title: String,
+    author: String,
+    description: String,
+    metadata: Option<BookMetadata>,
+    app_handle: AppHandle,
+) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        // 执行更新操作
+        let tx = db.transaction()?;
+        if let Some(metadata) = &metadata {
+            write_book_metadata(&tx, id, metadata)?;
+        }
+        // author 以传入的值为准，作者列表中的第一作者随之更新
+        sync_primary_author(&tx, id, &author)?;
+        tx.execute(
+            "UPDATE ee_book SET title = ?, author = ?, description = ?, updateTime = ? WHERE id = ?",
+            params![title, author, description, get_current_time_string(), id],
+        )?;
+        tx.commit()?;
+        // 返回成功响应，包含更新的行数
+        Ok(1)
+    })
+    .await
+    .into()
+}
+
+// 修改书籍标识（如改用原书的 ISBN/UUID），uuid 为空时重新生成，返回新的标识
+#[command]
+pub async fn set_book_uuid(
+    id: i64,
+    uuid: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<String> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+        set_book_uuid_with(&db, id, uuid.as_deref())
+    })
+    .await
+    .into()
+}
+
+// 复制 ee_book 记录（生成新的 uuid），返回新书籍 id
+fn copy_book_row(tx: &Transaction, id: i64, title: &str) -> Result<i64, rusqlite::Error> {
+    let current_time = get_current_time_string();
+    let inserted = tx.execute(
+        "INSERT INTO ee_book (uuid, title, author, description, toc, isDel, createTime, updateTime) \
+         SELECT ?, ?, author, description, toc, 0, ?, ? FROM ee_book WHERE id = ?",
+        params![generate_book_uuid(), title, current_time, current_time, id],
+    )?;
+    if inserted == 0 {
+        return Err(rusqlite::Error::QueryReturnedNoRows);
+    }
+    let new_id = tx.last_insert_rowid();
+    copy_book_metadata(tx, id, new_id)?;
+    copy_book_tags(tx, id, new_id)?;
+    Ok(new_id)
+}
+
+// 将章节复制到另一本书，返回旧章节 id 到新章节 id 的映射
+pub fn copy_chapters(
+    tx: &Transaction,
+    chapter_ids: &[i64],
+    to_book_id: i64,
+) -> Result<HashMap<i64, i64>, rusqlite::Error> {
+    // 直接复制存储的正文与长度，全文索引按复制后的章节另外写入
+    let mut stmt = tx.prepare(
+        "INSERT INTO ee_chapter \
+         (bookId, label, href, content, charCount, byteCount, createTime, updateTime) \
+         SELECT ?, label, href, content, charCount, byteCount, createTime, updateTime \
+         FROM ee_chapter WHERE id = ?",
+    )?;
+    let mut chapter_map = HashMap::new();
+    for id in chapter_ids {
+        if stmt.execute(params![to_book_id, id])? > 0 {
+            let new_id = tx.last_insert_rowid();
+            reindex_chapter(tx, new_id)?;
+            chapter_map.insert(*id, new_id);
+        }
+    }
+    Ok(chapter_map)
+}
+
+// 在 parent_id 下从 start_index 开始插入目录子树，章节 id 按 chapter_map 重新映射
+pub fn insert_toc_subtree(
+    tx: &Transaction,
+    book_id: i64,
+    parent_id: Option<i64>,
+    start_index: i64,
+    nodes: &[TocNode],
+    chapter_map: &HashMap<i64, i64>,
+) -> Result<(), rusqlite::Error> {
+    for (index, node) in nodes.iter().enumerate() {
+        let chapter_id = node
+            .chapter_id
+            .map(|id| chapter_map.get(&id).copied().unwrap_or(id));
+        tx.execute(
+            "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label, anchor) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                book_id,
+                parent_id,
+                start_index + index as i64,
+                chapter_id,
+                node.label,
+                node.anchor
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        insert_toc_subtree(tx, book_id, Some(id), 0, &node.subitems, chapter_map)?;
+    }
+    Ok(())
+}
+
+// 用 ee_toc_node 重新生成 toc 列，保持旧格式数据与目录节点一致
+pub fn sync_toc_column(tx: &Transaction, book_id: i64) -> Result<(), rusqlite::Error> {
+    let toc = toc_to_json(tx, book_id)?;
+    tx.execute(
+        "UPDATE ee_book SET toc = ? WHERE id = ?",
+        params![toc, book_id],
+    )?;
+    Ok(())
+}
+
+// 复制书籍的封面与图片目录，失败时删除已复制的部分
+pub fn copy_book_assets(app_dir: &Path, from_id: i64, to_id: i64) -> AppResult<()> {
+    let result = (|| -> std::io::Result<()> {
+        let cover = book_cover_path(app_dir, from_id);
+        if cover.exists() {
+            fs::copy(&cover, book_cover_path(app_dir, to_id))?;
+        }
+        let epub_dir = book_epub_dir(app_dir, from_id);
+        if epub_dir.exists() {
+            copy_dir_all(&epub_dir, &book_epub_dir(app_dir, to_id))?;
+        }
+        Ok(())
+    })();
+
+    result.map_err(|err| {
+        let _ = remove_book_assets(app_dir, to_id);
+        AppError::from(err).context("复制书籍资源失败")
+    })
+}
+
+pub fn load_book(db: &Connection, id: i64) -> Result<Book, rusqlite::Error> {
+    db.query_row(
+        "SELECT id, title, author, description, createTime, updateTime, uuid FROM ee_book WHERE id = ?",
+        params![id],
+        |row| {
+            Ok(Book {
+                id: row.get(0)?,
+                uuid: row.get::<_, Option<String>>(6)?.unwrap_or_default(),
+                title: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+                author: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                description: row.get::<_, Option<String>>(3)?.unwrap_or_default(),
+                toc: toc_to_json(db, id)?,
+                create_time: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+                update_time: row.get::<_, Option<String>>(5)?.unwrap_or_default(),
+                metadata: load_book_metadata(db, id)?,
+            })
+        },
+    )
+}
+
+// 复制一本书为新的可编辑副本：书籍信息、章节、目录、封面与图片全部复制
+#[command]
+pub async fn duplicate_book(id: i64, new_title: String, app_handle: AppHandle) -> DbResponse<Book> {
+    run_blocking(app_handle.clone(), move |state| {
+        let app_dir = get_app_dir(&app_handle)?;
+        let mut db = get_db_connection(state)?;
+
+        let tx = db.transaction()?;
+        let new_id = copy_book_row(&tx, id, &new_title).map_err(|err| match err {
+            rusqlite::Error::QueryReturnedNoRows => {
+                AppError::NotFound(format!("书籍 {} 不存在", id))
+            }
+            err => err.into(),
+        })?;
+
+        let query = ChapterQuery {
+            book_id: Some(id),
+            ..Default::default()
+        };
+        let chapter_ids = query_chapter_ids(&tx, &query)?;
+        let chapter_map = copy_chapters(&tx, &chapter_ids, new_id)?;
+
+        let toc = load_toc_tree(&tx, id)?;
+        insert_toc_subtree(&tx, new_id, None, 0, &toc, &chapter_map)?;
+        sync_toc_column(&tx, new_id)?;
+
+        // 资源复制成功后再提交，提交失败时删除已复制的资源
+        copy_book_assets(&app_dir, id, new_id)?;
+        if let Err(err) = tx.commit() {
+            let _ = remove_book_assets(&app_dir, new_id);
+            return Err(err.into());
+        }
+        Ok(load_book(&db, new_id)?)
+    })
+    .await
+    .into()
+}
+
+// 合并方式：直接追加到目录末尾，或每本源书籍在目录中作为一个卷节点
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum MergeMode {
+    Append,
+    Volume,
+}
+
+fn book_images_dir(app_dir: &Path, id: i64) -> PathBuf {
+    book_epub_dir(app_dir, id).join(IMAGES_DIRNAME)
+}
+
+// 章节内容中的图片引用，形如 ../images/xxx.jpg，分组 1 为文件名
+fn image_ref_pattern() -> AppResult<Regex> {
+    Ok(Regex::new(r#"images/([^"'\s<>)]+)"#)?)
+}
+
+// 顶层目录的下一个排序位置
+fn next_top_level_index(tx: &Transaction, book_id: i64) -> Result<i64, rusqlite::Error> {
+    tx.query_row(
+        "SELECT COALESCE(MAX(sortIndex) + 1, 0) FROM ee_toc_node \
+         WHERE bookId = ? AND parentId IS NULL",
+        params![book_id],
+        |row| row.get(0),
+    )
+}
+
+// 在已占用的文件名中为图片选择不冲突的名字：a.jpg -> a_1.jpg -> a_2.jpg ...
+fn unique_file_name(name: &str, taken: &HashSet<String>) -> String {
+    if !taken.contains(name) {
+        return name.to_string();
+    }
+    let (stem, ext) = match name.rfind('.') {
+        Some(pos) if pos > 0 => (&name[..pos], &name[pos..]),
+        _ => (name, ""),
+    };
+    (1..)
+        .map(|n| format!("{}_{}{}", stem, n, ext))
+        .find(|candidate| !taken.contains(candidate))
+        .unwrap_or_default()
+}
+
+// 图片复制计划：源文件路径 -> 目标目录中的文件名
+struct ImagePlan {
+    files: Vec<(PathBuf, String)>,
+    renames: HashMap<String, String>,
+}
+
+fn plan_images(app_dir: &Path, from_id: i64, taken: &mut HashSet<String>) -> AppResult<ImagePlan> {
+    let mut plan = ImagePlan {
+        files: Vec::new(),
+        renames: HashMap::new(),
+    };
+    let dir = book_images_dir(app_dir, from_id);
+    if !dir.exists() {
+        return Ok(plan);
+    }
+    let mut entries = fs::read_dir(&dir)
+        .map_err(|e| AppError::from(e).context("读取图片目录失败"))?
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().is_file())
+        .map(|entry| entry.file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    entries.sort();
+
+    for name in entries {
+        let new_name = unique_file_name(&name, taken);
+        taken.insert(new_name.clone());
+        if new_name != name {
+            plan.renames.insert(name.clone(), new_name.clone());
+        }
+        plan.files.push((dir.join(&name), new_name));
+    }
+    Ok(plan)
+}
+
+// 将章节内容中 images/xxx 形式的图片引用替换为重命名后的文件名，没有变化时返回 None
+fn rewrite_image_refs(
+    content: &str,
+    pattern: &Regex,
+    renames: &HashMap<String, String>,
+) -> Option<String> {
+    if renames.is_empty() {
+        return None;
+    }
+    let mut changed = false;
+    let result = pattern.replace_all(content, |caps: &regex::Captures| {
+        match renames.get(&caps[1]) {
+            Some(new_name) => {
+                changed = true;
+                format!("{}/{}", IMAGES_DIRNAME, new_name)
+            }
+            None => caps[0].to_string(),
+        }
+    });
+    if changed {
+        Some(result.into_owned())
+    } else {
+        None
+    }
+}
+
+// 复制图片到目标书籍目录，返回已复制的文件以便失败时清理
+fn copy_planned_images(
+    app_dir: &Path,
+    to_id: i64,
+    files: &[(PathBuf, String)],
+) -> AppResult<Vec<PathBuf>> {
+    let dir = book_images_dir(app_dir, to_id);
+    let mut copied = Vec::new();
+    let result = (|| -> std::io::Result<()> {
+        if !files.is_empty() {
+            fs::create_dir_all(&dir)?;
+        }
+        for (src, name) in files {
+            let target = dir.join(name);
+            fs::copy(src, &target)?;
+            copied.push(target);
+        }
+        Ok(())
+    })();
+
+    match result {
+        Ok(()) => Ok(copied),
+        Err(err) => {
+            remove_files(&copied);
+            Err(AppError::from(err).context("复制图片失败"))
+        }
+    }
+}
+
+fn remove_files(files: &[PathBuf]) {
+    for file in files {
+        let _ = fs::remove_file(file);
+    }
+}
+
+// 将一本源书籍的章节与目录追加到目标书籍，delete_source 为 true 时完成后将源书籍移入回收站
+fn merge_book_rows(
+    tx: &Transaction,
+    target_id: i64,
+    source_id: i64,
+    mode: MergeMode,
+    delete_source: bool,
+    image_pattern: &Regex,
+    renames: &HashMap<String, String>,
+) -> AppResult<()> {
+    let title: String = tx
+        .query_row(
+            "SELECT title FROM ee_book WHERE id = ? AND isDel = 0",
+            params![source_id],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .ok_or_else(|| AppError::NotFound(format!("书籍 {} 不存在", source_id)))?
+        .unwrap_or_default();
+
+    let query = ChapterQuery {
+        book_id: Some(source_id),
+        ..Default::default()
+    };
+    let chapter_ids = query_chapter_ids(tx, &query)?;
+    let chapter_map = copy_chapters(tx, &chapter_ids, target_id)?;
+
+    // 只有图片重命名时才需要读取正文改写引用
+    if !renames.is_empty() {
+        for new_id in chapter_map.values() {
+            let (label, content): (Option<String>, String) = tx.query_row(
+                "SELECT label, content FROM ee_chapter WHERE id = ?",
+                params![new_id],
+                |row| Ok((row.get(0)?, read_content(row, 1)?)),
+            )?;
+            if let Some(content) = rewrite_image_refs(&content, image_pattern, renames) {
+                let stored = StoredContent::new(&content)?;
+                tx.execute(
+                    "UPDATE ee_chapter SET content = ?, charCount = ?, byteCount = ? WHERE id = ?",
+                    params![stored.value, stored.char_count, stored.byte_count, new_id],
+                )?;
+                index_chapter(tx, *new_id, label.as_deref().unwrap_or_default(), &content)?;
+            }
+        }
+    }
+
+    let toc = load_toc_tree(tx, source_id)?;
+    let index = next_top_level_index(tx, target_id)?;
+    let (parent_id, start_index) = match mode {
+        MergeMode::Append => (None, index),
+        MergeMode::Volume => {
+            tx.execute(
+                "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label, anchor) \
+                 VALUES (?, NULL, ?, NULL, ?, NULL)",
+                params![target_id, index, title],
+            )?;
+            (Some(tx.last_insert_rowid()), 0)
+        }
+    };
+    insert_toc_subtree(tx, target_id, parent_id, start_index, &toc, &chapter_map)?;
+
+    // 不在目录中的源章节接在目录之后补上目录项，否则合并后在目标书籍中看不到
+    let mut listed = Vec::new();
+    toc_chapter_order(&toc, &mut listed, &mut HashSet::new());
+    let listed: HashSet<i64> = listed.into_iter().collect();
+    let mut unlisted = Vec::new();
+    for old_id in reading_order(tx, source_id)? {
+        let Some(new_id) = chapter_map.get(&old_id) else {
+            continue;
+        };
+        if listed.contains(&old_id) {
+            continue;
+        }
+        let label: Option<String> = tx.query_row(
+            "SELECT label FROM ee_chapter WHERE id = ?",
+            params![new_id],
+            |row| row.get(0),
+        )?;
+        unlisted.push(TocNode {
+            id: 0,
+            parent_id: None,
+            sort_index: 0,
+            chapter_id: Some(*new_id),
+            label: label.unwrap_or_default(),
+            anchor: None,
+            subitems: Vec::new(),
+        });
+    }
+    insert_toc_subtree(
+        tx,
+        target_id,
+        parent_id,
+        start_index + toc.len() as i64,
+        &unlisted,
+        &HashMap::new(),
+    )?;
+
+    // 源书籍只做软删除，图片等资源保留到从回收站彻底删除时再清理
+    if delete_source {
+        tx.execute(
+            "UPDATE ee_book SET isDel = 1, deleteTime = ?1, updateTime = ?1 WHERE id = ?2",
+            params![get_current_time_string(), source_id],
+        )?;
+    }
+    Ok(())
+}
+
+// 将多本书合并到目标书籍：章节按顺序追加，图片重名时自动重命名并改写章节中的引用。
+// 源书籍默认保留，delete_sources 为 true 时合并成功后移入回收站
+#[command]
+pub async fn merge_books(
+    target_id: i64,
+    source_ids: Vec<i64>,
+    mode: MergeMode,
+    delete_sources: Option<bool>,
+    app_handle: AppHandle,
+) -> DbResponse<Book> {
+    run_blocking(app_handle.clone(), move |state| {
+        let app_dir = get_app_dir(&app_handle)?;
+        let mut db = get_db_connection(state)?;
+
+        if source_ids.contains(&target_id) {
+            return Err(AppError::InvalidInput(
+                "目标书籍不能同时作为被合并的书籍".to_string(),
+            ));
+        }
+        if source_ids.iter().collect::<HashSet<_>>().len() != source_ids.len() {
+            return Err(AppError::InvalidInput("被合并的书籍重复".to_string()));
+        }
+
+        let tx = db.transaction()?;
+        let target_exists: bool = tx.query_row(
+            "SELECT EXISTS (SELECT 1 FROM ee_book WHERE id = ? AND isDel = 0)",
+            params![target_id],
+            |row| row.get(0),
+        )?;
+        if !target_exists {
+            return Err(AppError::NotFound(format!("书籍 {} 不存在", target_id)));
+        }
+
+        let image_pattern = image_ref_pattern()?;
+        let mut taken: HashSet<String> = match fs::read_dir(book_images_dir(&app_dir, target_id)) {
+            Ok(entries) => entries
+                .filter_map(|entry| entry.ok())
+                .map(|entry| entry.file_name().to_string_lossy().to_string())
+                .collect(),
+            Err(_) => HashSet::new(),
+        };
+
+        let mut files = Vec::new();
+        for source_id in &source_ids {
+            let plan = plan_images(&app_dir, *source_id, &mut taken)?;
+            merge_book_rows(
+                &tx,
+                target_id,
+                *source_id,
+                mode,
+                delete_sources.unwrap_or(false),
+                &image_pattern,
+                &plan.renames,
+            )?;
+            files.extend(plan.files);
+        }
+
+        sync_toc_column(&tx, target_id)?;
+        tx.execute(
+            "UPDATE ee_book SET updateTime = ? WHERE id = ?",
+            params![get_current_time_string(), target_id],
+        )?;
+
+        // 图片先复制到目标目录，提交失败时清理已复制的文件。
+        // 源书籍保留或在回收站中仍可恢复，因此复制而不是移动，源图片在彻底删除源书籍时随目录一起清理
+        let copied = copy_planned_images(&app_dir, target_id, &files)?;
+        if let Err(err) = tx.commit() {
+            remove_files(&copied);
+            return Err(err.into());
+        }
+        Ok(load_book(&db, target_id)?)
+    })
+    .await
+    .into()
+}
+
+// 拆分范围：按目录节点（卷）或按阅读顺序中起止章节之间的区间（含两端）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SplitRange {
+    Volume {
+        toc_node_id: i64,
+        title: Option<String>,
+    },
+    Chapters {
+        start_id: i64,
+        end_id: i64,
+        title: String,
+    },
+}
+
+// 按 keep 过滤目录树。章节不保留但子节点保留的节点变为不指向章节的标题节点
+fn filter_toc(nodes: &[TocNode], keep: &impl Fn(i64) -> bool) -> Vec<TocNode> {
+    nodes
+        .iter()
+        .filter_map(|node| {
+            let subitems = filter_toc(&node.subitems, keep);
+            let chapter_kept = node.chapter_id.is_some_and(keep);
+            if !chapter_kept && subitems.is_empty() {
+                return None;
+            }
+            Some(TocNode {
+                id: node.id,
+                parent_id: node.parent_id,
+                sort_index: node.sort_index,
+                chapter_id: node.chapter_id.filter(|_| chapter_kept),
+                label: node.label.clone(),
+                anchor: node.anchor.clone(),
+                subitems,
+            })
+        })
+        .collect()
+}
+
+fn find_toc_node(nodes: &[TocNode], id: i64) -> Option<&TocNode> {
+    nodes.iter().find_map(|node| {
+        if node.id == id {
+            Some(node)
+        } else {
+            find_toc_node(&node.subitems, id)
+        }
+    })
+}
+
+fn collect_toc_chapters(nodes: &[TocNode], chapter_ids: &mut HashSet<i64>) {
+    for node in nodes {
+        if let Some(chapter_id) = node.chapter_id {
+            chapter_ids.insert(chapter_id);
+        }
+        collect_toc_chapters(&node.subitems, chapter_ids);
+    }
+}
+
+// 拆分出的一本新书
+struct SplitPart {
+    title: String,
+    chapter_ids: Vec<i64>,
+    toc: Vec<TocNode>,
+}
+
+// order 为书籍章节的阅读顺序
+fn resolve_split_part(
+    range: &SplitRange,
+    book_title: &str,
+    order: &[i64],
+    toc: &[TocNode],
+) -> AppResult<SplitPart> {
+    let (title, selected, part_toc) = match range {
+        SplitRange::Volume { toc_node_id, title } => {
+            let node = find_toc_node(toc, *toc_node_id)
+                .ok_or_else(|| AppError::NotFound(format!("目录节点 {} 不存在", toc_node_id)))?;
+            let mut selected = HashSet::new();
+            collect_toc_chapters(std::slice::from_ref(node), &mut selected);
+            // 卷节点本身不指向章节时，新书以卷内的子节点作为顶层目录
+            let part_toc = if node.chapter_id.is_none() {
+                node.subitems.clone()
+            } else {
+                vec![node.clone()]
+            };
+            let title = title
+                .clone()
+                .unwrap_or_else(|| format!("{} {}", book_title, node.label));
+            (title, selected, part_toc)
+        }
+        SplitRange::Chapters {
+            start_id,
+            end_id,
+            title,
+        } => {
+            let position = |id: &i64| {
+                order
+                    .iter()
+                    .position(|chapter_id| chapter_id == id)
+                    .ok_or_else(|| AppError::NotFound(format!("章节 {} 不存在", id)))
+            };
+            let (start, end) = (position(start_id)?, position(end_id)?);
+            let selected: HashSet<i64> = order[start.min(end)..=start.max(end)]
+                .iter()
+                .copied()
+                .collect();
+            let part_toc = filter_toc(toc, &|id| selected.contains(&id));
+            (title.clone(), selected, part_toc)
+        }
+    };
+
+    let chapter_ids: Vec<i64> = order
+        .iter()
+        .copied()
+        .filter(|id| selected.contains(id))
+        .collect();
+    if chapter_ids.is_empty() {
+        return Err(AppError::InvalidInput(format!(
+            "拆分范围“{}”中没有章节",
+            title
+        )));
+    }
+    Ok(SplitPart {
+        title,
+        chapter_ids,
+        toc: part_toc,
+    })
+}
+
+// 选中章节中引用到的、源书籍目录中存在的图片
+fn referenced_images(
+    tx: &Transaction,
+    app_dir: &Path,
+    book_id: i64,
+    chapter_ids: &[i64],
+    pattern: &Regex,
+) -> AppResult<Vec<(PathBuf, String)>> {
+    let dir = book_images_dir(app_dir, book_id);
+    let mut names = HashSet::new();
+    let mut stmt = tx.prepare_cached("SELECT content FROM ee_chapter WHERE id = ?")?;
+    for id in chapter_ids {
+        let content = stmt.query_row(params![id], |row| read_content(row, 0))?;
+        for caps in pattern.captures_iter(&content) {
+            names.insert(caps[1].to_string());
+        }
+    }
+    let mut files: Vec<(PathBuf, String)> = names
+        .into_iter()
+        .map(|name| (dir.join(&name), name))
+        .filter(|(path, _)| path.is_file())
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+// 从原书中移除被拆出的章节与目录项，删除前保存章节内容与目录，可通过 undo_operation 恢复
+fn remove_split_chapters(
+    tx: &Transaction,
+    book_id: i64,
+    toc: &[TocNode],
+    moved: &HashSet<i64>,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    snapshot_toc(tx, book_id, ctx)?;
+    let remaining = filter_toc(toc, &|id| !moved.contains(&id));
+    tx.execute("DELETE FROM ee_toc_node WHERE bookId = ?", params![book_id])?;
+    insert_toc_subtree(tx, book_id, None, 0, &remaining, &HashMap::new())?;
+    for id in moved {
+        snapshot_deleted_chapter(tx, *id, ctx)?;
+        tx.execute("DELETE FROM ee_chapter WHERE id = ?", params![id])?;
+    }
+    sync_toc_column(tx, book_id)?;
+    tx.execute(
+        "UPDATE ee_book SET updateTime = ? WHERE id = ?",
+        params![get_current_time_string(), book_id],
+    )?;
+    Ok(())
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitBookResult {
+    pub books: Vec<Book>,
+    // 移动章节时从原书中移除章节的操作 id，不移动时为空
+    pub operation_id: Option<String>,
+}
+
+// 将一本书按卷或阅读顺序中的章节区间拆分为多本新书。新书继承作者、简介与封面，并带上引用到的图片；
+// move_chapters 为 true 时从原书中移除被拆出的章节与目录，可通过 undo_operation 撤销
+#[command]
+pub async fn split_book(
+    book_id: i64,
+    ranges: Vec<SplitRange>,
+    move_chapters: bool,
+    operation_id: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<SplitBookResult> {
+    run_blocking(app_handle.clone(), move |state| {
+        let app_dir = get_app_dir(&app_handle)?;
+        let mut db = get_db_connection(state)?;
+
+        if ranges.is_empty() {
+            return Err(AppError::InvalidInput("没有指定拆分范围".to_string()));
+        }
+        let tx = db.transaction()?;
+        let book_title: String = tx
+            .query_row(
+                "SELECT title FROM ee_book WHERE id = ? AND isDel = 0",
+                params![book_id],
+                |row| row.get::<_, Option<String>>(0),
+            )
+            .optional()?
+            .ok_or_else(|| AppError::NotFound(format!("书籍 {} 不存在", book_id)))?
+            .unwrap_or_default();
+
+        let order = reading_order(&tx, book_id)?;
+        let toc = load_toc_tree(&tx, book_id)?;
+        let parts = ranges
+            .iter()
+            .map(|range| resolve_split_part(range, &book_title, &order, &toc))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if move_chapters {
+            let mut seen = HashSet::new();
+            if parts
+                .iter()
+                .flat_map(|part| &part.chapter_ids)
+                .any(|id| !seen.insert(*id))
+            {
+                return Err(AppError::InvalidInput(
+                    "移动章节时拆分范围不能重叠".to_string(),
+                ));
+            }
+        }
+
+        let image_pattern = image_ref_pattern()?;
+        let mut new_ids = Vec::new();
+        let mut copied = Vec::new();
+        let copy_result = (|| -> AppResult<()> {
+            for part in &parts {
+                let new_id = copy_book_row(&tx, book_id, &part.title)?;
+                new_ids.push(new_id);
+                let chapter_map = copy_chapters(&tx, &part.chapter_ids, new_id)?;
+                insert_toc_subtree(&tx, new_id, None, 0, &part.toc, &chapter_map)?;
+                sync_toc_column(&tx, new_id)?;
+
+                let cover = book_cover_path(&app_dir, book_id);
+                if cover.exists() {
+                    let target = book_cover_path(&app_dir, new_id);
+                    fs::copy(&cover, &target)
+                        .map_err(|e| AppError::from(e).context("复制封面失败"))?;
+                    copied.push(target);
+                }
+                let images =
+                    referenced_images(&tx, &app_dir, book_id, &part.chapter_ids, &image_pattern)?;
+                copied.extend(copy_planned_images(&app_dir, new_id, &images)?);
+            }
+            Ok(())
+        })();
+        if let Err(err) = copy_result {
+            remove_files(&copied);
+            for id in &new_ids {
+                let _ = remove_book_assets(&app_dir, *id);
+            }
+            return Err(err);
+        }
+
+        let operation_id = if move_chapters {
+            let moved: HashSet<i64> = parts
+                .iter()
+                .flat_map(|part| part.chapter_ids.iter().copied())
+                .collect();
+            let ctx = RevisionContext::new("split-book", operation_id);
+            if let Err(err) = remove_split_chapters(&tx, book_id, &toc, &moved, &ctx) {
+                for id in &new_ids {
+                    let _ = remove_book_assets(&app_dir, *id);
+                }
+                return Err(err.into());
+            }
+            Some(ctx.operation_id)
+        } else {
+            None
+        };
+
+        if let Err(err) = tx.commit() {
+            for id in &new_ids {
+                let _ = remove_book_assets(&app_dir, *id);
+            }
+            return Err(err.into());
+        }
+        Ok(SplitBookResult {
+            books: new_ids
+                .iter()
+                .map(|id| load_book(&db, *id))
+                .collect::<Result<_, _>>()?,
+            operation_id,
+        })
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{self, content};
+
+    fn open_book() -> Connection {
+        testutil::open_book(&[("第一章", "甲"), ("第二章", "乙")])
+    }
+
+    fn update(id: i64, label: &str, content: &str) -> ChapterUpdate {
+        ChapterUpdate {
+            id,
+            label: label.to_string(),
+            content: Some(content.to_string()),
+        }
+    }
+
+    #[test]
+    fn update_chapters_snapshots_only_changed_chapters() {
+        let mut db = open_book();
+        let tx = db.transaction().unwrap();
+        let ctx = RevisionContext::new("batch", Some("batch-1".to_string()));
+        let results = update_chapters_tx(
+            &tx,
+            &[update(1, "第一章", "甲甲"), update(2, "第二章", "乙")],
+            &ctx,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let changed: Vec<(i64, bool)> = results.iter().map(|r| (r.id, r.changed)).collect();
+        assert_eq!(changed, vec![(1, true), (2, false)]);
+        assert_eq!(content(&db, 1), "甲甲");
+        let revisions: Vec<(i64, String)> = db
+            .prepare("SELECT chapterId, operationId FROM ee_chapter_revision")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(revisions, vec![(1, "batch-1".to_string())]);
+    }
+
+    #[test]
+    fn update_chapters_fails_on_missing_chapter_without_partial_writes() {
+        let mut db = open_book();
+        {
+            let tx = db.transaction().unwrap();
+            let ctx = RevisionContext::new("batch", None);
+            let result = update_chapters_tx(
+                &tx,
+                &[update(1, "第一章", "改"), update(99, "不存在", "改")],
+                &ctx,
+            );
+            assert!(matches!(result, Err(AppError::NotFound(_))));
+        }
+        assert_eq!(content(&db, 1), "甲");
+        let revisions: i64 = db
+            .query_row("SELECT COUNT(*) FROM ee_chapter_revision", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(revisions, 0);
+    }
+
+    #[test]
+    fn split_book_move_can_be_undone() {
+        let mut db = open_book();
+        let tx = db.transaction().unwrap();
+        replace_toc_from_json(
+            &tx,
+            1,
+            r#"[{"label":"第一章","href":1},{"label":"第二章","href":2}]"#,
+        )
+        .unwrap();
+        let toc = load_toc_tree(&tx, 1).unwrap();
+        let ctx = RevisionContext::new("split-book", Some("split-1".to_string()));
+        remove_split_chapters(&tx, 1, &toc, &HashSet::from([2]), &ctx).unwrap();
+        tx.commit().unwrap();
+        assert_eq!(reading_order(&db, 1).unwrap(), vec![1]);
+
+        let tx = db.transaction().unwrap();
+        crate::revision::undo_operation_tx(&tx, "split-1").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(reading_order(&db, 1).unwrap(), vec![1, 2]);
+        assert_eq!(content(&db, 2), "乙");
+    }
+
+    #[test]
+    fn purge_removes_chapter_and_toc_revisions() {
+        let mut db = open_book();
+        let tx = db.transaction().unwrap();
+        let ctx = RevisionContext::new("batch", None);
+        snapshot_toc(&tx, 1, &ctx).unwrap();
+        update_chapters_tx(&tx, &[update(1, "第一章", "甲甲")], &ctx).unwrap();
+        tx.commit().unwrap();
+        db.execute("UPDATE ee_book SET isDel = 1 WHERE id = 1", [])
+            .unwrap();
+
+        let app_dir = std::env::temp_dir().join(format!("myebook-purge-{}", std::process::id()));
+        purge_books(&mut db, &app_dir, &[1]).unwrap();
+        for table in ["ee_chapter_revision", "ee_toc_revision", "ee_chapter"] {
+            let count: i64 = db
+                .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                    row.get(0)
+                })
+                .unwrap();
+            assert_eq!(count, 0, "{}", table);
+        }
+    }
+
+    #[test]
+    fn deleting_toc_chapters_removes_anchor_nodes_and_can_be_undone() {
+        let mut db = open_book();
+        let tx = db.transaction().unwrap();
+        replace_toc_from_json(
+            &tx,
+            1,
+            r#"[{"label":"第一章","href":1},{"label":"第二章","href":2},
+                {"label":"锚","href":"1#a"}]"#,
+        )
+        .unwrap();
+        let node_id: i64 = tx
+            .query_row(
+                "SELECT id FROM ee_toc_node WHERE label = '第一章'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        let ctx = RevisionContext::new("delete-toc", Some("delete-1".to_string()));
+        delete_toc_node_tx(&tx, node_id, true, &ctx).unwrap();
+        tx.commit().unwrap();
+
+        let labels: Vec<String> = load_toc_tree(&db, 1)
+            .unwrap()
+            .into_iter()
+            .map(|node| node.label)
+            .collect();
+        assert_eq!(labels, vec!["第二章"]);
+        assert_eq!(reading_order(&db, 1).unwrap(), vec![2]);
+
+        let tx = db.transaction().unwrap();
+        crate::revision::undo_operation_tx(&tx, "delete-1").unwrap();
+        tx.commit().unwrap();
+        assert_eq!(load_toc_tree(&db, 1).unwrap().len(), 3);
+        assert_eq!(reading_order(&db, 1).unwrap(), vec![1, 2]);
+        assert_eq!(content(&db, 1), "甲");
+    }
+
+    #[test]
+    fn merged_source_uses_epoch_seconds_for_delete_and_update_time() {
+        let mut db = open_book();
+        db.execute("INSERT INTO ee_book (title, isDel) VALUES ('源', 0)", [])
+            .unwrap();
+        insert_chapter(&db, 2, "第三章", "c3", "丙", None).unwrap();
+        let tx = db.transaction().unwrap();
+        merge_book_rows(
+            &tx,
+            1,
+            2,
+            MergeMode::Append,
+            true,
+            &image_ref_pattern().unwrap(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        let (delete_time, update_time): (String, String) = db
+            .query_row(
+                "SELECT deleteTime, updateTime FROM ee_book WHERE id = 2",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .unwrap();
+        assert!(delete_time.parse::<i64>().is_ok());
+        assert_eq!(update_time, delete_time);
+    }
+
+    #[test]
+    fn merge_keeps_sources_and_lists_chapters_missing_from_toc() {
+        let mut db = open_book();
+        db.execute("INSERT INTO ee_book (title, isDel) VALUES ('源', 0)", [])
+            .unwrap();
+        insert_chapter(&db, 2, "第三章", "c3", "丙", None).unwrap();
+        insert_chapter(&db, 2, "第四章", "c4", "丁", None).unwrap();
+        db.execute(
+            "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label) \
+             VALUES (2, NULL, 0, 4, '第四章')",
+            [],
+        )
+        .unwrap();
+        let tx = db.transaction().unwrap();
+        merge_book_rows(
+            &tx,
+            1,
+            2,
+            MergeMode::Volume,
+            false,
+            &image_ref_pattern().unwrap(),
+            &HashMap::new(),
+        )
+        .unwrap();
+        tx.commit().unwrap();
+
+        // 目录中的第四章在前，不在目录中的第三章接在其后
+        let toc = load_toc_tree(&db, 1).unwrap();
+        let volume = toc.last().unwrap();
+        let labels: Vec<&str> = volume
+            .subitems
+            .iter()
+            .map(|node| node.label.as_str())
+            .collect();
+        assert_eq!(labels, vec!["第四章", "第三章"]);
+        let contents: Vec<String> = volume
+            .subitems
+            .iter()
+            .map(|node| content(&db, node.chapter_id.unwrap()))
+            .collect();
+        assert_eq!(contents, vec!["丁", "丙"]);
+
+        let is_del: i64 = db
+            .query_row("SELECT isDel FROM ee_book WHERE id = 2", [], |row| {
+                row.get(0)
+            })
+            .unwrap();
+        assert_eq!(is_del, 0);
+    }
+
+    #[test]
+    fn merged_images_are_copied_until_the_source_is_purged() {
+        let app_dir = std::env::temp_dir().join(format!("myebook-merge-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&app_dir);
+        let source_images = book_images_dir(&app_dir, 2);
+        fs::create_dir_all(&source_images).unwrap();
+        fs::write(source_images.join("a.jpg"), b"a").unwrap();
+
+        let mut db = open_book();
+        db.execute("INSERT INTO ee_book (title, isDel) VALUES ('源', 0)", [])
+            .unwrap();
+        let plan = plan_images(&app_dir, 2, &mut HashSet::new()).unwrap();
+        let tx = db.transaction().unwrap();
+        merge_book_rows(
+            &tx,
+            1,
+            2,
+            MergeMode::Append,
+            true,
+            &image_ref_pattern().unwrap(),
+            &plan.renames,
+        )
+        .unwrap();
+        copy_planned_images(&app_dir, 1, &plan.files).unwrap();
+        tx.commit().unwrap();
+
+        // 源书籍在回收站中可以恢复，图片仍然保留
+        assert!(source_images.join("a.jpg").exists());
+        purge_books(&mut db, &app_dir, &[2]).unwrap();
+        assert!(!book_epub_dir(&app_dir, 2).exists());
+        assert!(book_images_dir(&app_dir, 1).join("a.jpg").exists());
+        fs::remove_dir_all(&app_dir).unwrap();
     }
 }
@@ -0,0 +1,245 @@
+use crate::database::{DbResponse, BACKUP_DIRNAME, DB_FILENAME};
+use crate::error::{AppError, AppResult};
+use crate::pool::{open_reader, open_writer, run_blocking};
+use crate::setup::{init_state, AppState};
+use rusqlite::{params, Connection, DatabaseName};
+use serde::Serialize;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use tauri::{command, AppHandle, Manager};
+
+// 未加密的 SQLite 文件以固定的 16 字节开头，SQLCipher 加密后整个文件（包括文件头）都是密文
+const SQLITE_HEADER: &[u8; 16] = b"SQLite format 3\0";
+
+// 根据文件头判断数据库是否已加密，文件不存在或为空时视为未加密
+pub fn is_encrypted(path: &Path) -> bool {
+    let mut header = [0u8; 16];
+    match fs::File::open(path).and_then(|mut file| file.read_exact(&mut header)) {
+        Ok(()) => &header != SQLITE_HEADER,
+        Err(_) => false,
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptionStatus {
+    // 数据库文件是否已加密
+    pub encrypted: bool,
+    // 数据库是否已打开，加密库需先调用 unlock_database
+    pub unlocked: bool,
+}
+
+fn encryption_status(app_handle: &AppHandle) -> AppResult<EncryptionStatus> {
+    let path = app_handle.path().app_data_dir()?.join(DB_FILENAME);
+    Ok(EncryptionStatus {
+        encrypted: is_encrypted(&path),
+        unlocked: app_handle.try_state::<AppState>().is_some(),
+    })
+}
+
+fn check_passphrase(passphrase: &str) -> AppResult<()> {
+    if passphrase.is_empty() {
+        return Err(AppError::InvalidInput("密码不能为空".to_string()));
+    }
+    Ok(())
+}
+
+fn db_path(state: &AppState) -> AppResult<&Path> {
+    state
+        .db
+        .path()
+        .ok_or_else(|| AppError::InvalidInput("数据库未打开".to_string()))
+}
+
+// 加密库在修改或移除密码前需要再次输入当前密码
+fn verify_passphrase(state: &AppState, current: &str) -> AppResult<()> {
+    let path = db_path(state)?;
+    if !is_encrypted(path) {
+        return Err(AppError::InvalidInput("数据库未加密".to_string()));
+    }
+    open_reader(path, Some(current))
+        .map(|_| ())
+        .map_err(|err| err.context("当前密码错误"))
+}
+
+// 把当前库导出到新文件，key 为空字符串时导出为未加密的库
+// sqlcipher_export 不复制 user_version，需手动写入以免下次启动重新执行迁移
+fn export_database(db: &Connection, target: &Path, key: &str) -> AppResult<()> {
+    let version: i64 = db.pragma_query_value(None, "user_version", |row| row.get(0))?;
+    db.execute(
+        "ATTACH DATABASE ?1 AS export KEY ?2",
+        params![target.to_string_lossy(), key],
+    )?;
+    let export = DatabaseName::Attached("export");
+    let result = db
+        .pragma_update(Some(export), "auto_vacuum", "INCREMENTAL")
+        .and_then(|_| db.query_row("SELECT sqlcipher_export('export')", [], |_| Ok(())))
+        .and_then(|_| db.pragma_update(Some(export), "user_version", version));
+    db.execute("DETACH DATABASE export", [])?;
+    Ok(result?)
+}
+
+// 迁移前用 VACUUM INTO 生成的备份与数据库使用相同的密钥，避免设置密码后磁盘上仍留有明文备份
+// current 为加密备份的当前密码；无法重新加密的明文备份直接删除，无法打开的加密备份保持不变
+fn rekey_backups(backup_dir: &Path, current: Option<&str>, key: Option<&str>) -> AppResult<()> {
+    let entries = match fs::read_dir(backup_dir) {
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        entries => entries?,
+    };
+    for entry in entries {
+        let path = entry?.path();
+        if !path.is_file() || path.extension().and_then(|ext| ext.to_str()) != Some("db") {
+            continue;
+        }
+        let plaintext = !is_encrypted(&path);
+        if plaintext && key.is_none() {
+            continue;
+        }
+        let tmp = path.with_extension("db.tmp");
+        let result = open_writer(&path, if plaintext { None } else { current })
+            .and_then(|db| export_database(&db, &tmp, key.unwrap_or("")))
+            .and_then(|_| Ok(fs::rename(&tmp, &path)?));
+        if let Err(err) = result {
+            let _ = fs::remove_file(&tmp);
+            if plaintext {
+                fs::remove_file(&path)?;
+            } else {
+                eprintln!("[DB] 无法重新加密备份 {}: {}", path.display(), err);
+            }
+        }
+    }
+    Ok(())
+}
+
+// 以新密钥重写整个数据库文件及备份，key 为 None 时移除加密；current 为当前密码，未加密时为 None
+fn rekey_database(state: &AppState, current: Option<&str>, key: Option<&str>) -> AppResult<()> {
+    state.db.replace_file(current, key, |db, target| {
+        export_database(db, target, key.unwrap_or(""))
+    })?;
+    let backup_dir = db_path(state)?.with_file_name(BACKUP_DIRNAME);
+    rekey_backups(&backup_dir, current, key).map_err(|err| err.context("处理数据库备份失败"))
+}
+
+// 查询数据库是否加密、是否已解锁，前端启动时据此决定是否显示解锁对话框
+#[command]
+pub fn get_encryption_status(app_handle: AppHandle) -> DbResponse<EncryptionStatus> {
+    encryption_status(&app_handle).into()
+}
+
+// 输入密码解锁加密库，成功后才注册 AppState，其他命令才能访问数据库
+#[command]
+pub async fn unlock_database(
+    passphrase: String,
+    app_handle: AppHandle,
+) -> DbResponse<EncryptionStatus> {
+    tauri::async_runtime::spawn_blocking(move || -> AppResult<EncryptionStatus> {
+        if app_handle.try_state::<AppState>().is_none() {
+            check_passphrase(&passphrase)?;
+            // 密码错误时 init_state 返回的是 AppError::Encrypted
+            let state = match init_state(&app_handle, Some(&passphrase)) {
+                Ok(state) => state,
+                Err(err) => {
+                    return Err(match err.downcast::<AppError>() {
+                        Ok(err) => *err,
+                        Err(err) => AppError::Database(err.to_string()),
+                    })
+                }
+            };
+            app_handle.manage(state);
+        }
+        encryption_status(&app_handle)
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))
+    .and_then(|result| result)
+    .into()
+}
+
+// 为未加密的库设置密码
+#[command]
+pub async fn set_passphrase(passphrase: String, app_handle: AppHandle) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        check_passphrase(&passphrase)?;
+        if is_encrypted(db_path(state)?) {
+            return Err(AppError::Conflict(
+                "数据库已加密，请使用修改密码".to_string(),
+            ));
+        }
+        rekey_database(state, None, Some(&passphrase))
+    })
+    .await
+    .into()
+}
+
+// 修改加密库的密码
+#[command]
+pub async fn change_passphrase(
+    current: String,
+    passphrase: String,
+    app_handle: AppHandle,
+) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        check_passphrase(&passphrase)?;
+        verify_passphrase(state, &current)?;
+        rekey_database(state, Some(&current), Some(&passphrase))
+    })
+    .await
+    .into()
+}
+
+// 移除加密，数据库恢复为普通 SQLite 文件
+#[command]
+pub async fn remove_passphrase(current: String, app_handle: AppHandle) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        verify_passphrase(state, &current)?;
+        rekey_database(state, Some(&current), None)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn backup_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("myebook-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn write_backup(path: &Path, key: Option<&str>) {
+        let db = Connection::open(path).unwrap();
+        if let Some(key) = key {
+            db.pragma_update(None, "key", key).unwrap();
+        }
+        db.execute_batch("CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('书');")
+            .unwrap();
+    }
+
+    fn read_backup(path: &Path, key: Option<&str>) -> AppResult<String> {
+        let db = open_writer(path, key)?;
+        Ok(db.query_row("SELECT v FROM t", [], |row| row.get(0))?)
+    }
+
+    #[test]
+    fn backups_follow_the_database_key() {
+        let dir = backup_dir("rekey");
+        let backup = dir.join("books-v1-0.db");
+        write_backup(&backup, None);
+
+        rekey_backups(&dir, None, Some("一")).unwrap();
+        assert!(is_encrypted(&backup));
+        assert_eq!(read_backup(&backup, Some("一")).unwrap(), "书");
+
+        rekey_backups(&dir, Some("一"), Some("二")).unwrap();
+        assert!(read_backup(&backup, Some("一")).is_err());
+        assert_eq!(read_backup(&backup, Some("二")).unwrap(), "书");
+
+        rekey_backups(&dir, Some("二"), None).unwrap();
+        assert!(!is_encrypted(&backup));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,236 @@
+use serde::{Serialize, Serializer};
+use std::fmt;
+use std::io;
+use std::sync::PoisonError;
+
+// 所有命令统一使用的错误类型
+// 序列化为 { code, messageKey, detail }：code 供前端判断错误类型（如忙时重试、文件缺失时提示），
+// messageKey 用于查找本地化文案，detail 为具体原因（不保证稳定，只用于展示和排查）
+#[derive(Debug, Clone, PartialEq)]
+pub enum AppError {
+    // 书籍、章节、目录、标签等记录不存在
+    NotFound(String),
+    // 参数不合法，如空名称、错误的正则表达式
+    InvalidInput(String),
+    // 与已有数据冲突，如名称或标识重复
+    Conflict(String),
+    // 数据库被其他连接锁定，可稍后重试
+    Busy(String),
+    // 磁盘空间不足
+    DiskFull(String),
+    // 没有读写权限或数据库为只读
+    PermissionDenied(String),
+    // 文件或目录不存在
+    FileNotFound(String),
+    // 数据库或压缩包已损坏
+    Corrupt(String),
+    // 数据库已加密但尚未解锁，或密码错误
+    Encrypted(String),
+    // 网络请求失败，如检查更新时无法连接服务器
+    Network(String),
+    // 操作被用户取消
+    Cancelled,
+    // 其他数据库错误
+    Database(String),
+    // 其他文件读写错误
+    Io(String),
+    // 程序内部错误，如锁中毒、线程异常退出
+    Internal(String),
+}
+
+pub type AppResult<T> = Result<T, AppError>;
+
+impl AppError {
+    // 稳定的错误代码，前端据此判断错误类型
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "NOT_FOUND",
+            AppError::InvalidInput(_) => "INVALID_INPUT",
+            AppError::Conflict(_) => "CONFLICT",
+            AppError::Busy(_) => "BUSY",
+            AppError::DiskFull(_) => "DISK_FULL",
+            AppError::PermissionDenied(_) => "PERMISSION_DENIED",
+            AppError::FileNotFound(_) => "FILE_NOT_FOUND",
+            AppError::Corrupt(_) => "CORRUPT",
+            AppError::Encrypted(_) => "ENCRYPTED",
+            AppError::Network(_) => "NETWORK",
+            AppError::Cancelled => "CANCELLED",
+            AppError::Database(_) => "DATABASE",
+            AppError::Io(_) => "IO",
+            AppError::Internal(_) => "INTERNAL",
+        }
+    }
+
+    // 本地化文案的键
+    pub fn message_key(&self) -> &'static str {
+        match self {
+            AppError::NotFound(_) => "error.notFound",
+            AppError::InvalidInput(_) => "error.invalidInput",
+            AppError::Conflict(_) => "error.conflict",
+            AppError::Busy(_) => "error.busy",
+            AppError::DiskFull(_) => "error.diskFull",
+            AppError::PermissionDenied(_) => "error.permissionDenied",
+            AppError::FileNotFound(_) => "error.fileNotFound",
+            AppError::Corrupt(_) => "error.corrupt",
+            AppError::Encrypted(_) => "error.encrypted",
+            AppError::Network(_) => "error.network",
+            AppError::Cancelled => "error.cancelled",
+            AppError::Database(_) => "error.database",
+            AppError::Io(_) => "error.io",
+            AppError::Internal(_) => "error.internal",
+        }
+    }
+
+    pub fn detail(&self) -> Option<&str> {
+        match self {
+            AppError::NotFound(detail)
+            | AppError::InvalidInput(detail)
+            | AppError::Conflict(detail)
+            | AppError::Busy(detail)
+            | AppError::DiskFull(detail)
+            | AppError::PermissionDenied(detail)
+            | AppError::FileNotFound(detail)
+            | AppError::Corrupt(detail)
+            | AppError::Encrypted(detail)
+            | AppError::Network(detail)
+            | AppError::Database(detail)
+            | AppError::Io(detail)
+            | AppError::Internal(detail) => Some(detail),
+            AppError::Cancelled => None,
+        }
+    }
+
+    // 在原因前加上说明，保留错误类型，如“创建ZIP文件失败: 磁盘空间不足”
+    pub fn context(self, context: &str) -> Self {
+        let detail = match self.detail() {
+            Some(detail) => format!("{}: {}", context, detail),
+            None => context.to_string(),
+        };
+        self.with_detail(detail)
+    }
+
+    fn with_detail(self, detail: String) -> Self {
+        match self {
+            AppError::NotFound(_) => AppError::NotFound(detail),
+            AppError::InvalidInput(_) => AppError::InvalidInput(detail),
+            AppError::Conflict(_) => AppError::Conflict(detail),
+            AppError::Busy(_) => AppError::Busy(detail),
+            AppError::DiskFull(_) => AppError::DiskFull(detail),
+            AppError::PermissionDenied(_) => AppError::PermissionDenied(detail),
+            AppError::FileNotFound(_) => AppError::FileNotFound(detail),
+            AppError::Corrupt(_) => AppError::Corrupt(detail),
+            AppError::Encrypted(_) => AppError::Encrypted(detail),
+            AppError::Network(_) => AppError::Network(detail),
+            AppError::Cancelled => AppError::Cancelled,
+            AppError::Database(_) => AppError::Database(detail),
+            AppError::Io(_) => AppError::Io(detail),
+            AppError::Internal(_) => AppError::Internal(detail),
+        }
+    }
+}
+
+impl fmt::Display for AppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.detail() {
+            Some(detail) => write!(f, "[{}] {}", self.code(), detail),
+            None => write!(f, "[{}]", self.code()),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ErrorBody<'a> {
+    code: &'static str,
+    message_key: &'static str,
+    detail: Option<&'a str>,
+}
+
+impl Serialize for AppError {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ErrorBody {
+            code: self.code(),
+            message_key: self.message_key(),
+            detail: self.detail(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        use rusqlite::ErrorCode;
+        let detail = err.to_string();
+        match &err {
+            rusqlite::Error::QueryReturnedNoRows => AppError::NotFound(detail),
+            rusqlite::Error::SqliteFailure(failure, _) => match failure.code {
+                ErrorCode::DatabaseBusy | ErrorCode::DatabaseLocked => AppError::Busy(detail),
+                ErrorCode::DiskFull => AppError::DiskFull(detail),
+                ErrorCode::ReadOnly | ErrorCode::PermissionDenied => {
+                    AppError::PermissionDenied(detail)
+                }
+                ErrorCode::CannotOpen => AppError::FileNotFound(detail),
+                ErrorCode::DatabaseCorrupt | ErrorCode::NotADatabase => AppError::Corrupt(detail),
+                ErrorCode::ConstraintViolation => AppError::Conflict(detail),
+                ErrorCode::OperationInterrupted => AppError::Cancelled,
+                _ => AppError::Database(detail),
+            },
+            _ => AppError::Database(detail),
+        }
+    }
+}
+
+impl From<io::Error> for AppError {
+    fn from(err: io::Error) -> Self {
+        let detail = err.to_string();
+        match err.kind() {
+            io::ErrorKind::NotFound => AppError::FileNotFound(detail),
+            io::ErrorKind::PermissionDenied | io::ErrorKind::ReadOnlyFilesystem => {
+                AppError::PermissionDenied(detail)
+            }
+            io::ErrorKind::StorageFull | io::ErrorKind::QuotaExceeded => AppError::DiskFull(detail),
+            io::ErrorKind::AlreadyExists => AppError::Conflict(detail),
+            io::ErrorKind::InvalidInput => AppError::InvalidInput(detail),
+            _ => AppError::Io(detail),
+        }
+    }
+}
+
+impl From<zip::result::ZipError> for AppError {
+    fn from(err: zip::result::ZipError) -> Self {
+        match err {
+            zip::result::ZipError::Io(err) => err.into(),
+            zip::result::ZipError::FileNotFound => AppError::FileNotFound(err.to_string()),
+            err => AppError::Corrupt(err.to_string()),
+        }
+    }
+}
+
+impl From<serde_json::Error> for AppError {
+    fn from(err: serde_json::Error) -> Self {
+        AppError::InvalidInput(err.to_string())
+    }
+}
+
+impl From<regex::Error> for AppError {
+    fn from(err: regex::Error) -> Self {
+        AppError::InvalidInput(err.to_string())
+    }
+}
+
+impl From<tauri::Error> for AppError {
+    fn from(err: tauri::Error) -> Self {
+        match err {
+            tauri::Error::Io(err) => err.into(),
+            err => AppError::Internal(err.to_string()),
+        }
+    }
+}
+
+impl<T> From<PoisonError<T>> for AppError {
+    fn from(err: PoisonError<T>) -> Self {
+        AppError::Internal(err.to_string())
+    }
+}
@@ -1,3 +1,7 @@
+use crate::database::{DbResponse, BACKUP_DIRNAME};
+use crate::error::{AppError, AppResult};
+use crate::maintenance::{checkpoint, CheckpointMode};
+use crate::setup::AppState;
 use base64::engine::general_purpose;
 use base64::engine::Engine as _;
 use std::fs;
@@ -10,10 +14,15 @@ use zip::write::FileOptions;
 use zip::read::ZipFile;
 use zip::result::ZipError;
 
+// 文件命令与数据库命令一样返回 DbResponse，前端只需处理一种结果格式
 #[command]
-pub fn read_image(path: String) -> Result<String, String> {
+pub fn read_image(path: String) -> DbResponse<String> {
+    read_image_with(&path).into()
+}
+
+fn read_image_with(path: &str) -> AppResult<String> {
     // 读取图片文件
-    let image_data = fs::read(path).map_err(|e| e.to_string())?;
+    let image_data = fs::read(path)?;
     // 转换为 Base64
     let base64_data = general_purpose::STANDARD.encode(&image_data);
     Ok(base64_data)
@@ -21,19 +30,22 @@ pub fn read_image(path: String) -> Result<String, String> {
 
 //删除应用数据目录所有文件
 #[command]
-pub fn clear_app_data(app_handle: AppHandle) -> Result<(), String> {
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data directory");
-    fs::remove_dir_all(app_dir).map_err(|e| e.to_string())?;
-    Ok(())
+pub fn clear_app_data(app_handle: AppHandle) -> DbResponse<()> {
+    clear_app_data_with(&app_handle).into()
 }
 
-
+fn clear_app_data_with(app_handle: &AppHandle) -> AppResult<()> {
+    let app_dir = app_handle.path().app_data_dir()?;
+    fs::remove_dir_all(app_dir)?;
+    Ok(())
+}
 
 #[command]
-pub async fn open_folder(path: String) -> Result<(), String> {
+pub async fn open_folder(path: String) -> DbResponse<()> {
+    open_folder_with(&path).into()
+}
+
+fn open_folder_with(path: &str) -> AppResult<()> {
     #[cfg(target_os = "macos")]
     let command = "open";
 
@@ -43,34 +55,44 @@ pub async fn open_folder(path: String) -> Result<(), String> {
     #[cfg(target_os = "linux")]
     let command = "xdg-open";
 
-    std::process::Command::new(command)
-        .arg(&path)
-        .spawn()
-        .map_err(|e| e.to_string())?;
+    std::process::Command::new(command).arg(path).spawn()?;
 
     Ok(())
 }
 
 #[command]
-pub async fn zip_app_directory(app_handle: AppHandle, output_path: String) -> Result<(), String> {
+pub async fn zip_app_directory(app_handle: AppHandle, output_path: String) -> DbResponse<()> {
+    zip_app_directory_with(&app_handle, &output_path).into()
+}
+
+fn zip_app_directory_with(app_handle: &AppHandle, output_path: &str) -> AppResult<()> {
     // 获取应用数据目录
-    let app_dir = app_handle
-        .path()
-        .app_data_dir()
-        .expect("Failed to get app data directory");
+    let app_dir = app_handle.path().app_data_dir()?;
+
+    // 先把 WAL 写回数据库文件，打包期间持有写连接，避免备份到写了一半的数据
+    // 加密库的数据文件本身就是密文，原样打包即可保持加密，恢复后仍需原密码解锁
+    let state = app_handle.try_state::<AppState>();
+    let writer = match &state {
+        Some(state) => Some(state.db.writer()?),
+        None => None,
+    };
+    if let Some(db) = &writer {
+        checkpoint(db, CheckpointMode::Truncate)?;
+    }
 
     // 创建ZIP文件
-    let zip_file = fs::File::create(&output_path).map_err(|e| format!("创建ZIP文件失败: {}", e))?;
+    let zip_file =
+        fs::File::create(output_path).map_err(|e| AppError::from(e).context("创建ZIP文件失败"))?;
     let mut zip = zip::ZipWriter::new(zip_file);
 
     // 遍历应用目录并添加所有文件
     if let Err(err) = add_dir_to_zip(&mut zip, &app_dir, "") {
-        return Err(format!("打包目录失败: {}", err));
+        return Err(AppError::from(err).context("打包目录失败"));
     }
 
     // 完成ZIP写入
     zip.finish()
-        .map_err(|e| format!("完成ZIP打包失败: {}", e))?;
+        .map_err(|e| AppError::from(e).context("完成ZIP打包失败"))?;
 
     Ok(())
 }
@@ -81,6 +103,10 @@ fn add_dir_to_zip(zip: &mut zip::ZipWriter<fs::File>, path: &Path, base: &str) -
         let entry = entry?;
         let path = entry.path();
         let name = path.file_name().unwrap().to_str().unwrap();
+        // 迁移前的备份只用于本机恢复，不打包
+        if base.is_empty() && name == BACKUP_DIRNAME {
+            continue;
+        }
         let file_path = if base.is_empty() {
             name.to_string()
         } else {
@@ -103,49 +129,69 @@ fn add_dir_to_zip(zip: &mut zip::ZipWriter<fs::File>, path: &Path, base: &str) -
     Ok(())
 }
 
+// 递归复制目录
+pub fn copy_dir_all(src: &Path, dst: &Path) -> io::Result<()> {
+    fs::create_dir_all(dst)?;
+    for entry in fs::read_dir(src)? {
+        let entry = entry?;
+        let target = dst.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir_all(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
+    Ok(())
+}
+
 #[command]
-pub async fn unzip_file(zip_file: String, dest_dir: String) -> Result<(), String> {
+pub async fn unzip_file(zip_file: String, dest_dir: String) -> DbResponse<()> {
+    unzip_file_with(&zip_file, &dest_dir).into()
+}
+
+fn unzip_file_with(zip_file: &str, dest_dir: &str) -> AppResult<()> {
     // 打开zip文件
-    let file = fs::File::open(&zip_file).map_err(|e| format!("无法打开ZIP文件: {}", e))?;
+    let file =
+        fs::File::open(zip_file).map_err(|e| AppError::from(e).context("无法打开ZIP文件"))?;
     let reader = io::BufReader::new(file);
     let mut archive =
-        zip::ZipArchive::new(reader).map_err(|e| format!("解析ZIP文件失败: {}", e))?;
+        zip::ZipArchive::new(reader).map_err(|e| AppError::from(e).context("解析ZIP文件失败"))?;
 
     // 确保目标目录存在
-    fs::create_dir_all(&dest_dir).map_err(|e| format!("无法创建目标目录: {}", e))?;
+    fs::create_dir_all(dest_dir).map_err(|e| AppError::from(e).context("无法创建目标目录"))?;
 
     // 遍历ZIP中的所有文件并解压
     for i in 0..archive.len() {
         let mut file = match archive.by_index(i) {
             Ok(file) => file,
             Err(ZipError::FileNotFound) => continue, // 处理文件未找到的情况
-            Err(e) => return Err(format!("读取ZIP条目失败: {}", e)),
+            Err(e) => return Err(AppError::from(e).context("读取ZIP条目失败")),
         };
 
         // 构建目标文件路径
-        let outpath = match sanitize_path(&dest_dir, &file) {
-            Ok(path) => path,
-            Err(e) => return Err(e),
-        };
+        let outpath = sanitize_path(dest_dir, &file)?;
 
         // 转换为Path对象以便使用Path特有的方法
         let outpath_path = Path::new(&outpath);
 
         // 如果是目录，创建目录
         if file.name().ends_with('/') {
-            fs::create_dir_all(outpath_path).map_err(|e| format!("无法创建目录: {}", e))?;
+            fs::create_dir_all(outpath_path)
+                .map_err(|e| AppError::from(e).context("无法创建目录"))?;
         } else {
             // 如果是文件，确保父目录存在
             if let Some(parent) = outpath_path.parent() {
-                fs::create_dir_all(parent).map_err(|e| format!("无法创建父目录: {}", e))?;
+                fs::create_dir_all(parent)
+                    .map_err(|e| AppError::from(e).context("无法创建父目录"))?;
             }
 
             // 创建目标文件
-            let mut outfile =
-                fs::File::create(outpath_path).map_err(|e| format!("无法创建文件: {}", e))?;
+            let mut outfile = fs::File::create(outpath_path)
+                .map_err(|e| AppError::from(e).context("无法创建文件"))?;
 
             // 复制文件内容
-            io::copy(&mut file, &mut outfile).map_err(|e| format!("无法写入文件内容: {}", e))?;
+            io::copy(&mut file, &mut outfile)
+                .map_err(|e| AppError::from(e).context("无法写入文件内容"))?;
 
             // 在Unix上设置文件权限
             #[cfg(unix)]
@@ -164,7 +210,7 @@ pub async fn unzip_file(zip_file: String, dest_dir: String) -> Result<(), String
 }
 
 // 修改sanitize_path函数
-fn sanitize_path(dest_dir: &str, file: &ZipFile) -> Result<String, String> {
+fn sanitize_path(dest_dir: &str, file: &ZipFile) -> AppResult<String> {
     let dest_path = Path::new(dest_dir);
     let file_path = Path::new(file.name());
 
@@ -174,7 +220,7 @@ fn sanitize_path(dest_dir: &str, file: &ZipFile) -> Result<String, String> {
         .all(|c| !matches!(c, std::path::Component::ParentDir))
     {
         true => file_path,
-        false => return Err("检测到路径遍历尝试".to_string()),
+        false => return Err(AppError::InvalidInput("检测到路径遍历尝试".to_string())),
     };
 
     // 组合目标目录和文件路径
@@ -183,6 +229,6 @@ fn sanitize_path(dest_dir: &str, file: &ZipFile) -> Result<String, String> {
     // 确保组合后的路径在目标目录内
     match combined_path.strip_prefix(dest_path) {
         Ok(_) => Ok(combined_path.to_string_lossy().to_string()),
-        Err(_) => Err("文件路径不在目标目录内".to_string()),
+        Err(_) => Err(AppError::InvalidInput("文件路径不在目标目录内".to_string())),
     }
 }
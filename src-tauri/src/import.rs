@@ -0,0 +1,742 @@
+use crate::database::{
+    get_current_time_string, get_db_connection, insert_chapter, load_book, sync_toc_column,
+    toc_sibling_ids, Book, DbResponse,
+};
+use crate::error::{AppError, AppResult};
+use crate::metadata::{
+    resolve_book_uuid, write_book_metadata, BookMetadata, Contributor, ROLE_AUTHOR,
+};
+use crate::pool::run_blocking;
+use crate::restructure::chapter_hrefs;
+use crate::search::escape_html;
+use encoding_rs::{CoderResult, DecoderResult, Encoding, BIG5, GB18030, UTF_8};
+use regex::{Regex, RegexBuilder};
+use rusqlite::{params, Transaction};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{Read, Seek};
+use std::path::Path;
+use tauri::ipc::Channel;
+use tauri::{command, AppHandle};
+
+// 每次读取的字节数，每读完一块发送一次进度
+const READ_CHUNK: usize = 256 * 1024;
+// 章节序号中可出现的数字
+const CHAPTER_NUMBERS: &str = "一二三四五六七八九十百千万零〇两0-9０-９";
+// 没有设置分割前/后缀时使用的默认值，与前端设置中的初始值相同
+const DEFAULT_PREFIXES: [&str; 4] = ["", "第", "卷", "chapter"];
+const DEFAULT_SUFFIXES: [&str; 8] = ["", "章", "回", "节", "集", "部", "篇", "部分"];
+// GB2312 常用字的双字节比例低于该值且能按 Big5 解码时，认为是 Big5
+const GB2312_RATIO: f64 = 0.9;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TxtImportOptions {
+    // 导入到已有书籍的目录末尾，为空时新建书籍
+    pub book_id: Option<i64>,
+    // 新建书籍的书名与作者，书名默认为文件名
+    pub title: Option<String>,
+    pub author: Option<String>,
+    // 指定文件编码（如 gbk、big5），为空时自动检测
+    pub encoding: Option<String>,
+    // 章节标题的前缀与后缀（设置中的分割前/后缀），空字符串表示可以没有前缀或后缀
+    pub prefixes: Vec<String>,
+    pub suffixes: Vec<String>,
+    // 额外作为章节标题的正则，如 序章|楔子|番外.*
+    pub extra: Option<String>,
+    // 自定义章节标题正则，设置后不再使用前后缀规则
+    pub chapter_pattern: Option<String>,
+    // 是否识别卷标题，卷在目录中作为章节的上级
+    pub detect_volumes: bool,
+    // 自定义卷标题正则
+    pub volume_pattern: Option<String>,
+    // 章节标题中序号之后最多允许的字符数
+    pub max_title_chars: usize,
+    // 正文开头保留 <h3>章名</h3>
+    pub title_in_content: bool,
+}
+
+impl Default for TxtImportOptions {
+    fn default() -> Self {
+        Self {
+            book_id: None,
+            title: None,
+            author: None,
+            encoding: None,
+            prefixes: DEFAULT_PREFIXES.iter().map(|s| s.to_string()).collect(),
+            suffixes: DEFAULT_SUFFIXES.iter().map(|s| s.to_string()).collect(),
+            extra: None,
+            chapter_pattern: None,
+            detect_volumes: true,
+            volume_pattern: None,
+            max_title_chars: 20,
+            title_in_content: false,
+        }
+    }
+}
+
+// 导入进度，通过 Channel 发送给前端
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImportProgress {
+    pub bytes: u64,
+    pub total_bytes: u64,
+    // 已写入的章节数与最后一个章节的标题
+    pub chapters: usize,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TxtImportResult {
+    pub book: Book,
+    // 实际使用的编码名称，如 UTF-8、gb18030、Big5
+    pub encoding: String,
+    pub chapters: usize,
+    pub volumes: usize,
+}
+
+// 非空的候选项转换为正则分支，较长的在前，避免“部”先于“部分”匹配
+fn alternation(items: &[String]) -> Option<String> {
+    let mut items = items
+        .iter()
+        .map(|item| item.trim())
+        .filter(|item| !item.is_empty())
+        .collect::<Vec<_>>();
+    items.sort_by_key(|item| std::cmp::Reverse(item.chars().count()));
+    items.dedup();
+    if items.is_empty() {
+        return None;
+    }
+    Some(
+        items
+            .into_iter()
+            .map(regex::escape)
+            .collect::<Vec<_>>()
+            .join("|"),
+    )
+}
+
+fn build_regex(pattern: &str) -> AppResult<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|err| AppError::from(err).context("章节规则错误"))
+}
+
+// 按前缀 + 序号 + 后缀生成章节标题正则，与前端“开始分割”的规则一致：
+// 前缀与后缀不能同时为空，没有后缀时序号之后须是行尾、空白或标点
+fn chapter_regex(options: &TxtImportOptions) -> AppResult<Regex> {
+    if let Some(pattern) = options.chapter_pattern.as_deref().filter(|p| !p.is_empty()) {
+        return build_regex(pattern);
+    }
+    let number = format!("[{}]+", CHAPTER_NUMBERS);
+    let separator = r"(?:$|[\s:：.．、，,])";
+    let prefixes = alternation(&options.prefixes);
+    let suffixes = alternation(&options.suffixes);
+    let empty_prefix = options.prefixes.iter().any(|p| p.trim().is_empty());
+    let empty_suffix = options.suffixes.iter().any(|s| s.trim().is_empty());
+
+    let mut heads = Vec::new();
+    if let Some(prefixes) = &prefixes {
+        if let Some(suffixes) = &suffixes {
+            heads.push(format!(r"(?:{})\s*{}\s*(?:{})", prefixes, number, suffixes));
+        }
+        if empty_suffix {
+            heads.push(format!(r"(?:{})\s*{}{}", prefixes, number, separator));
+        }
+    }
+    if let (true, Some(suffixes)) = (empty_prefix, &suffixes) {
+        heads.push(format!(r"{}\s*(?:{})", number, suffixes));
+    }
+    if let Some(extra) = options.extra.as_deref().filter(|e| !e.trim().is_empty()) {
+        heads.push(format!("(?:{})", extra));
+    }
+    if heads.is_empty() {
+        return Err(AppError::InvalidInput(
+            "分割前缀与后缀不能都为空".to_string(),
+        ));
+    }
+    build_regex(&format!(
+        r"^\s*(?:{}).{{0,{}}}$",
+        heads.join("|"),
+        options.max_title_chars
+    ))
+}
+
+fn volume_regex(options: &TxtImportOptions) -> AppResult<Option<Regex>> {
+    if !options.detect_volumes {
+        return Ok(None);
+    }
+    let pattern = match options.volume_pattern.as_deref().filter(|p| !p.is_empty()) {
+        Some(pattern) => pattern.to_string(),
+        None => format!(
+            r"^\s*(?:第\s*[{n}]+\s*卷|卷\s*[{n}]+)(?:$|[\s:：.．、，,].{{0,{max}}}$)",
+            n = CHAPTER_NUMBERS,
+            max = options.max_title_chars
+        ),
+    };
+    build_regex(&pattern).map(Some)
+}
+
+// 统计双字节字符中落在 GB2312 常用字区（首字节 A1-F7，尾字节 A1-FE）的比例
+// Big5 的尾字节有很大一部分在 40-7E，按 GBK 解码虽然不报错，但这一比例明显偏低
+fn gb2312_ratio(bytes: &[u8]) -> f64 {
+    let (mut total, mut common) = (0usize, 0usize);
+    let mut index = 0;
+    while index + 1 < bytes.len() {
+        let lead = bytes[index];
+        if lead < 0x80 {
+            index += 1;
+            continue;
+        }
+        let trail = bytes[index + 1];
+        total += 1;
+        if (0xA1..=0xF7).contains(&lead) && (0xA1..=0xFE).contains(&trail) {
+            common += 1;
+        }
+        index += 2;
+    }
+    if total == 0 {
+        1.0
+    } else {
+        common as f64 / total as f64
+    }
+}
+
+fn decodes_cleanly(encoding: &'static Encoding, bytes: &[u8], last: bool) -> bool {
+    let mut decoder = encoding.new_decoder_without_bom_handling();
+    let mut output = String::with_capacity(
+        decoder
+            .max_utf8_buffer_length_without_replacement(bytes.len())
+            .unwrap_or(bytes.len() * 3),
+    );
+    let (result, _) = decoder.decode_to_string_without_replacement(bytes, &mut output, last);
+    matches!(result, DecoderResult::InputEmpty)
+}
+
+// 检测编码：优先按 BOM 判断，其次是否为合法的 UTF-8，最后在 GB18030 与 Big5 之间选择
+// sample 为文件开头的一部分，last 表示已包含整个文件（末尾的不完整字符视为错误）
+fn detect_encoding(sample: &[u8], last: bool) -> &'static Encoding {
+    if let Some((encoding, _)) = Encoding::for_bom(sample) {
+        return encoding;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => return UTF_8,
+        Err(err) if err.error_len().is_none() && !last => return UTF_8,
+        Err(_) => {}
+    }
+    detect_chinese_encoding(sample, last, &[]).unwrap_or(GB18030)
+}
+
+// 在 GB18030 与 Big5 之间选择，跳过已经解码失败的编码，两者都不可用时返回 None
+fn detect_chinese_encoding(
+    sample: &[u8],
+    last: bool,
+    failed: &[&'static Encoding],
+) -> Option<&'static Encoding> {
+    let gb18030 = !failed.contains(&GB18030);
+    let big5 = !failed.contains(&BIG5) && decodes_cleanly(BIG5, sample, last);
+    match (gb18030, big5) {
+        (true, true) if gb2312_ratio(sample) < GB2312_RATIO => Some(BIG5),
+        (true, _) => Some(GB18030),
+        (false, true) => Some(BIG5),
+        (false, false) => None,
+    }
+}
+
+#[derive(PartialEq)]
+enum SectionKind {
+    // 第一个标题之前的内容，以书名作为章名
+    Prologue,
+    // 卷标题与第一章之间的内容，作为卷目录项对应的章节
+    VolumeIntro,
+    Chapter,
+}
+
+// 逐行接收文本，遇到卷或章节标题时把上一段写入数据库
+struct ChapterWriter<'a> {
+    tx: &'a Transaction<'a>,
+    book_id: i64,
+    chapter_regex: Regex,
+    volume_regex: Option<Regex>,
+    title_in_content: bool,
+    hrefs: Box<dyn Iterator<Item = String>>,
+    current_time: String,
+    // 当前卷的目录节点，章节目录项放在卷下
+    volume: Option<i64>,
+    root_index: i64,
+    volume_index: i64,
+    kind: SectionKind,
+    label: String,
+    lines: Vec<String>,
+    chapters: usize,
+    volumes: usize,
+}
+
+impl<'a> ChapterWriter<'a> {
+    fn push_line(&mut self, line: &str) -> AppResult<()> {
+        let line = line.trim_end();
+        let trimmed = line.trim();
+        let is_volume = self
+            .volume_regex
+            .as_ref()
+            .is_some_and(|regex| regex.is_match(line));
+        if is_volume {
+            self.flush()?;
+            self.tx.execute(
+                "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label) \
+                 VALUES (?, NULL, ?, NULL, ?)",
+                params![self.book_id, self.root_index, trimmed],
+            )?;
+            self.volume = Some(self.tx.last_insert_rowid());
+            self.root_index += 1;
+            self.volume_index = 0;
+            self.volumes += 1;
+            self.kind = SectionKind::VolumeIntro;
+            self.label = trimmed.to_string();
+        } else if self.chapter_regex.is_match(line) {
+            self.flush()?;
+            self.kind = SectionKind::Chapter;
+            self.label = trimmed.to_string();
+        } else {
+            self.lines.push(line.to_string());
+        }
+        Ok(())
+    }
+
+    // 写入当前段落，正文为空时跳过（如文件开头的目录列表）
+    fn flush(&mut self) -> AppResult<()> {
+        let start = self.lines.iter().position(|line| !line.trim().is_empty());
+        let end = self.lines.iter().rposition(|line| !line.trim().is_empty());
+        let body = match (start, end) {
+            (Some(start), Some(end)) => self.lines[start..=end].join("\n"),
+            _ => {
+                self.lines.clear();
+                return Ok(());
+            }
+        };
+        self.lines.clear();
+        // 章名按文本写入标题，其中的 < & 等需要转义
+        let content = if self.title_in_content {
+            format!("<h3>{}</h3>\n\n{}", escape_html(&self.label), body)
+        } else {
+            body
+        };
+
+        let href = self.hrefs.next().unwrap_or_default();
+        let chapter_id = insert_chapter(
+            self.tx,
+            self.book_id,
+            &self.label,
+            &href,
+            &content,
+            Some(&self.current_time),
+        )?;
+        self.chapters += 1;
+
+        match (&self.kind, self.volume) {
+            (SectionKind::VolumeIntro, Some(volume)) => {
+                self.tx.execute(
+                    "UPDATE ee_toc_node SET chapterId = ? WHERE id = ?",
+                    params![chapter_id, volume],
+                )?;
+            }
+            (_, volume) => {
+                let index = match volume {
+                    Some(_) => &mut self.volume_index,
+                    None => &mut self.root_index,
+                };
+                self.tx
+                    .prepare_cached(
+                        "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label) \
+                         VALUES (?, ?, ?, ?, ?)",
+                    )?
+                    .execute(params![
+                        self.book_id,
+                        volume,
+                        *index,
+                        chapter_id,
+                        self.label
+                    ])?;
+                *index += 1;
+            }
+        }
+        Ok(())
+    }
+}
+
+// 新建书籍，作者不为空时同时写入作者列表
+fn create_book(tx: &Transaction, title: &str, author: Option<&str>) -> AppResult<i64> {
+    let current_time = get_current_time_string();
+    let author = author.map(str::trim).filter(|a| !a.is_empty());
+    let uuid = resolve_book_uuid(tx, None)?;
+    tx.execute(
+        "INSERT INTO ee_book (uuid, title, author, description, toc, isDel, createTime, updateTime) \
+         VALUES (?, ?, ?, ?, '', 0, ?, ?)",
+        params![
+            uuid,
+            title,
+            author.unwrap_or("Unknown"),
+            "Unknown",
+            current_time,
+            current_time
+        ],
+    )?;
+    let book_id = tx.last_insert_rowid();
+    if let Some(author) = author {
+        let metadata = BookMetadata {
+            authors: vec![Contributor {
+                name: author.to_string(),
+                role: ROLE_AUTHOR.to_string(),
+            }],
+            ..Default::default()
+        };
+        write_book_metadata(tx, book_id, &metadata)?;
+    }
+    Ok(book_id)
+}
+
+// 把 text 中完整的行交给 writer，last 为 false 时保留最后一行（可能还不完整）
+// \r\n 与单独的 \r 都视为换行
+fn drain_lines(text: &mut String, writer: &mut ChapterWriter, last: bool) -> AppResult<()> {
+    // end 为最后一个完整行的结尾，drained 还包括其后的换行符
+    let (end, drained) = if last {
+        (text.len(), text.len())
+    } else {
+        match text.rfind('\n') {
+            Some(index) => (index, index + 1),
+            None => return Ok(()),
+        }
+    };
+    for line in text[..end].split('\n') {
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        for part in line.split('\r') {
+            writer.push_line(part)?;
+        }
+    }
+    text.drain(..drained);
+    Ok(())
+}
+
+// 解码结果：完成，或严格解码时遇到无法解码的字节，附带出错的那一块用于重新检测编码
+enum Decoded {
+    Done,
+    Malformed(Vec<u8>),
+}
+
+// 逐块解码并交给 writer，只在内存中保留当前章节与未完成的一行。
+// strict 为 true 时遇到无法解码的字节立即停止，否则替换为 U+FFFD
+fn decode_file(
+    file: &mut File,
+    encoding: &'static Encoding,
+    strict: bool,
+    total_bytes: u64,
+    writer: &mut ChapterWriter,
+    on_progress: &Channel<ImportProgress>,
+) -> AppResult<Decoded> {
+    let mut buffer = vec![0u8; READ_CHUNK];
+    let mut decoder = encoding.new_decoder_with_bom_removal();
+    let mut text = String::new();
+    let mut bytes = 0u64;
+    loop {
+        let read = file.read(&mut buffer)?;
+        let last = read == 0;
+        let mut input = &buffer[..read];
+        loop {
+            if strict {
+                text.reserve(
+                    decoder
+                        .max_utf8_buffer_length_without_replacement(input.len())
+                        .unwrap_or(input.len() * 3),
+                );
+                let (result, consumed) =
+                    decoder.decode_to_string_without_replacement(input, &mut text, last);
+                input = &input[consumed..];
+                match result {
+                    DecoderResult::InputEmpty => break,
+                    DecoderResult::OutputFull => {}
+                    DecoderResult::Malformed(..) => {
+                        return Ok(Decoded::Malformed(buffer[..read].to_vec()))
+                    }
+                }
+            } else {
+                text.reserve(
+                    decoder
+                        .max_utf8_buffer_length(input.len())
+                        .unwrap_or(input.len() * 3),
+                );
+                let (result, consumed, _) = decoder.decode_to_string(input, &mut text, last);
+                input = &input[consumed..];
+                if let CoderResult::InputEmpty = result {
+                    break;
+                }
+            }
+        }
+        drain_lines(&mut text, writer, last)?;
+        bytes += read as u64;
+        let _ = on_progress.send(ImportProgress {
+            bytes,
+            total_bytes,
+            chapters: writer.chapters,
+            label: writer.label.clone(),
+        });
+        if last {
+            return Ok(Decoded::Done);
+        }
+    }
+}
+
+fn import_txt_with(
+    tx: &Transaction,
+    path: &Path,
+    options: &TxtImportOptions,
+    on_progress: &Channel<ImportProgress>,
+) -> AppResult<(i64, &'static Encoding, usize, usize)> {
+    let mut file = File::open(path).map_err(|e| AppError::from(e).context("打开文件失败"))?;
+    let total_bytes = file.metadata()?.len();
+
+    let book_id = match options.book_id {
+        Some(book_id) => {
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS (SELECT 1 FROM ee_book WHERE id = ? AND isDel = 0)",
+                params![book_id],
+                |row| row.get(0),
+            )?;
+            if !exists {
+                return Err(AppError::NotFound(format!("书籍 {} 不存在", book_id)));
+            }
+            book_id
+        }
+        None => {
+            let stem = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let title = options
+                .title
+                .clone()
+                .filter(|t| !t.trim().is_empty())
+                .unwrap_or(stem);
+            create_book(tx, &title, options.author.as_deref())?
+        }
+    };
+    let label = match options.book_id {
+        Some(_) => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default(),
+        None => tx.query_row(
+            "SELECT title FROM ee_book WHERE id = ?",
+            params![book_id],
+            |row| row.get(0),
+        )?,
+    };
+
+    let forced = match options.encoding.as_deref().filter(|e| !e.is_empty()) {
+        Some(label) => Some(
+            Encoding::for_label(label.trim().as_bytes())
+                .ok_or_else(|| AppError::InvalidInput(format!("不支持的编码 {}", label)))?,
+        ),
+        None => None,
+    };
+    let mut buffer = vec![0u8; READ_CHUNK];
+    let read = file.read(&mut buffer)?;
+    let sample = &buffer[..read];
+    let mut encoding =
+        forced.unwrap_or_else(|| detect_encoding(sample, (read as u64) >= total_bytes));
+    // 自动检测只依据文件开头，没有 BOM 时严格解码，后面的内容无法解码时换用其他编码从头重新导入
+    let mut strict = forced.is_none() && Encoding::for_bom(sample).is_none();
+    let mut failed = Vec::new();
+    file.rewind()?;
+
+    let (chapters, volumes) = loop {
+        tx.execute_batch("SAVEPOINT import_txt")?;
+        let mut writer = ChapterWriter {
+            tx,
+            book_id,
+            chapter_regex: chapter_regex(options)?,
+            volume_regex: volume_regex(options)?,
+            title_in_content: options.title_in_content,
+            hrefs: Box::new(chapter_hrefs()),
+            current_time: get_current_time_string(),
+            volume: None,
+            root_index: toc_sibling_ids(tx, book_id, None)?.len() as i64,
+            volume_index: 0,
+            kind: SectionKind::Prologue,
+            label: label.clone(),
+            lines: Vec::new(),
+            chapters: 0,
+            volumes: 0,
+        };
+        match decode_file(
+            &mut file,
+            encoding,
+            strict,
+            total_bytes,
+            &mut writer,
+            on_progress,
+        )? {
+            Decoded::Done => {
+                writer.flush()?;
+                tx.execute_batch("RELEASE import_txt")?;
+                break (writer.chapters, writer.volumes);
+            }
+            Decoded::Malformed(sample) => {
+                tx.execute_batch("ROLLBACK TO import_txt; RELEASE import_txt")?;
+                file.rewind()?;
+                failed.push(encoding);
+                // 都无法完整解码时按最初检测的编码导入，无法解码的字节替换为 U+FFFD
+                encoding = match detect_chinese_encoding(&sample, false, &failed) {
+                    Some(next) => next,
+                    None => {
+                        strict = false;
+                        failed[0]
+                    }
+                };
+            }
+        }
+    };
+
+    if chapters == 0 {
+        return Err(AppError::InvalidInput("文件中没有内容".to_string()));
+    }
+    sync_toc_column(tx, book_id)?;
+    tx.execute(
+        "UPDATE ee_book SET updateTime = ? WHERE id = ?",
+        params![get_current_time_string(), book_id],
+    )?;
+    Ok((book_id, encoding, chapters, volumes))
+}
+
+// 导入 TXT 文件：自动检测编码（UTF-8/UTF-16 BOM、GB18030、Big5），按章节与卷标题规则拆分，
+// 边读取边写入章节与目录，整个导入在同一事务中完成，失败时不留下不完整的书籍
+#[command]
+pub async fn import_txt(
+    path: String,
+    options: Option<TxtImportOptions>,
+    on_progress: Channel<ImportProgress>,
+    app_handle: AppHandle,
+) -> DbResponse<TxtImportResult> {
+    run_blocking(app_handle, move |state| {
+        let options = options.unwrap_or_default();
+        let mut db = get_db_connection(state)?;
+        let tx = db.transaction()?;
+        let (book_id, encoding, chapters, volumes) =
+            import_txt_with(&tx, Path::new(&path), &options, &on_progress)?;
+        tx.commit()?;
+        Ok(TxtImportResult {
+            book: load_book(&db, book_id)?,
+            encoding: encoding.name().to_string(),
+            chapters,
+            volumes,
+        })
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::{content, open_db};
+    use encoding_rs::{GBK, UTF_16LE};
+
+    const SIMPLIFIED: &str =
+        "第一章 天地玄黄\n宇宙洪荒，日月盈昃，辰宿列张。寒来暑往，秋收冬藏。\n";
+    const TRADITIONAL: &str =
+        "第一章 天地玄黃\n宇宙洪荒，日月盈昃，辰宿列張。寒來暑往，秋收冬藏。\n";
+
+    fn encode(encoding: &'static Encoding, text: &str) -> Vec<u8> {
+        let (bytes, _, unmappable) = encoding.encode(text);
+        assert!(!unmappable);
+        bytes.into_owned()
+    }
+
+    #[test]
+    fn detects_gbk_as_gb18030() {
+        assert_eq!(detect_encoding(&encode(GBK, SIMPLIFIED), true), GB18030);
+    }
+
+    #[test]
+    fn detects_big5() {
+        assert_eq!(detect_encoding(&encode(BIG5, TRADITIONAL), true), BIG5);
+    }
+
+    #[test]
+    fn detects_utf16_bom() {
+        let mut bytes = vec![0xFF, 0xFE];
+        bytes.extend(SIMPLIFIED.encode_utf16().flat_map(u16::to_le_bytes));
+        assert_eq!(detect_encoding(&bytes, true), UTF_16LE);
+    }
+
+    #[test]
+    fn detects_utf8_with_truncated_tail() {
+        // 样本在多字节字符中间截断时仍是 UTF-8
+        let bytes = SIMPLIFIED.as_bytes();
+        assert_eq!(detect_encoding(&bytes[..bytes.len() - 2], false), UTF_8);
+    }
+
+    #[test]
+    fn restarts_when_later_chunk_is_not_utf8() {
+        // 文件开头超过一块的纯 ASCII 会被检测为 UTF-8，之后的 GBK 正文解码失败时应重新检测
+        let mut bytes = "Preface line\n".repeat(READ_CHUNK / 13 + 1).into_bytes();
+        bytes.extend(encode(GBK, SIMPLIFIED));
+        let path =
+            std::env::temp_dir().join(format!("import-txt-restart-{}.txt", std::process::id()));
+        std::fs::write(&path, &bytes).unwrap();
+
+        let mut db = open_db();
+        let tx = db.transaction().unwrap();
+        let result = import_txt_with(
+            &tx,
+            &path,
+            &TxtImportOptions::default(),
+            &Channel::new(|_| Ok(())),
+        );
+        std::fs::remove_file(&path).unwrap();
+        let (book_id, encoding, chapters, _) = result.unwrap();
+        assert_eq!(encoding, GB18030);
+        assert_eq!(chapters, 2);
+        let label: String = tx
+            .query_row(
+                "SELECT label FROM ee_chapter WHERE bookId = ? ORDER BY id DESC LIMIT 1",
+                params![book_id],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(label, "第一章 天地玄黄");
+        // 回滚的第一次尝试不应留下章节
+        let count: i64 = tx
+            .query_row("SELECT COUNT(*) FROM ee_chapter", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 2);
+    }
+
+    #[test]
+    fn title_in_content_is_escaped() {
+        let path =
+            std::env::temp_dir().join(format!("import-txt-escape-{}.txt", std::process::id()));
+        std::fs::write(&path, "第一章 A & B<上>\n正文\n").unwrap();
+
+        let mut db = open_db();
+        let tx = db.transaction().unwrap();
+        let options = TxtImportOptions {
+            title_in_content: true,
+            ..Default::default()
+        };
+        let result = import_txt_with(&tx, &path, &options, &Channel::new(|_| Ok(())));
+        std::fs::remove_file(&path).unwrap();
+        result.unwrap();
+        let (id, label): (i64, String) = tx
+            .query_row("SELECT id, label FROM ee_chapter", [], |row| {
+                Ok((row.get(0)?, row.get(1)?))
+            })
+            .unwrap();
+        assert_eq!(label, "第一章 A & B<上>");
+        assert_eq!(
+            content(&tx, id),
+            "<h3>第一章 A &amp; B&lt;上&gt;</h3>\n\n正文"
+        );
+    }
+}
@@ -1,7 +1,24 @@
 // 导入自定义模块
+mod compress; // 正文压缩模块，zstd 压缩与解压章节正文
 mod database; // 数据库操作模块，处理书籍和章节的数据存储
+mod encryption; // 数据库加密模块，基于 SQLCipher 设置、修改、移除密码与解锁
+mod error; // 统一错误类型模块，为前端提供错误代码与本地化文案键
 mod fileutil; // 文件操作工具模块，提供文件读写、压缩解压等功能
+mod import; // TXT 导入模块，检测编码并按章节与卷标题规则拆分
+mod maintenance; // 数据库维护模块，完整性检查、WAL 检查点、整理与空间统计
+mod metadata; // 书籍扩展元数据模块，作者角色、标识、主题与系列
+mod migration; // 数据库迁移模块，按 user_version 升级数据库结构
+mod pool; // 数据库连接池模块，一个写连接与多个 WAL 只读连接
+mod replace; // 查找替换模块，整本书按文本或正则查找、预览与替换
+mod restructure; // 章节拆分与合并模块，同时维护目录
+mod revision; // 章节历史版本模块，支持比较、恢复与撤销批量操作
+mod search; // 全文检索模块，基于 SQLite FTS5
 mod setup; // 应用程序设置模块，负责初始化应用环境
+mod stats; // 统计模块，按字素统计字数、段落与图片
+mod tag; // 标签与书单模块，用于对书籍分组
+#[cfg(test)]
+mod testutil; // 测试共用的数据库夹具
+mod transform; // 批量文本处理模块，对整本书的章节执行处理操作
 
 // 导入必要的 Tauri 类型use tauri::{ Emitter};  // Emitter trait 用于在前端和后端之间发送事件
 
@@ -76,14 +93,22 @@ fn allow_file_in_scopes(app: &AppHandle, files: Vec<PathBuf>) {
 
 // Tauri命令宏，定义检查更新的异步函数
 #[tauri::command]
-async fn check_for_updates(app_handle: tauri::AppHandle) -> Result<serde_json::Value, String> {
+async fn check_for_updates(
+    app_handle: tauri::AppHandle,
+) -> database::DbResponse<serde_json::Value> {
+    check_for_updates_with(&app_handle).await.into()
+}
+
+async fn check_for_updates_with(
+    app_handle: &tauri::AppHandle,
+) -> error::AppResult<serde_json::Value> {
     // 使用更新器插件的扩展特性
     use tauri_plugin_updater::UpdaterExt;
 
     // 首先解包updater的Result，获取更新器实例
     let updater = match app_handle.updater() {
         Ok(u) => u,
-        Err(e) => return Err(format!("获取更新器失败: {}", e)),
+        Err(e) => return Err(error::AppError::Internal(format!("获取更新器失败: {}", e))),
     };
 
     // 打印调试信息，表示正在检查更新
@@ -138,20 +163,20 @@ async fn check_for_updates(app_handle: tauri::AppHandle) -> Result<serde_json::V
                 }
             }
             // 在非开发环境或非签名错误的情况下，返回具体错误
-            Err(format!("检查更新失败: {}", e))
+            Err(error::AppError::Network(format!("检查更新失败: {}", e)))
         }
     }
 }
 
 #[tauri::command]
-async fn get_app_info(app_handle: tauri::AppHandle) -> Result<serde_json::Value, String> {
+async fn get_app_info(app_handle: tauri::AppHandle) -> database::DbResponse<serde_json::Value> {
     // 获取应用程序的版本号
     let version = app_handle.package_info().version.to_string();
     // 获取应用程序的名称
     let name = app_handle.package_info().name.to_string();
 
     // 返回包含版本和名称的JSON对象
-    Ok(serde_json::json!({
+    database::DbResponse::success(serde_json::json!({
         "version": version,
         "name": name
     }))
@@ -175,23 +200,78 @@ pub fn run() {
         .plugin(tauri_plugin_updater::Builder::new().build())
         // 注册可从前端调用的 Rust 函数
         .invoke_handler(tauri::generate_handler![
-            database::close_database,    // 关闭数据库连接
-            database::add_book,          // 添加书籍信息
-            database::get_all_books,     // 获取所有书籍列表
-            database::add_chapter,       // 添加章节内容
-            database::get_chapter,       // 获取章节内容
-            database::update_toc,        // 更新书籍目录
-            database::get_chapter_where, // 条件查询章节
-            database::update_chapter,    // 更新章节内容
-            database::delete_book,       // 删除书籍
-            database::update_book,       // 更新书籍信息
-            fileutil::read_image,        // 读取图片文件
-            fileutil::clear_app_data,    // 清除应用数据
-            fileutil::open_folder,       // 打开文件夹
-            fileutil::zip_app_directory, // 压缩应用目录
-            fileutil::unzip_file,
-            check_for_updates,
-            get_app_info // 解压文件
+            database::close_database,            // 关闭数据库连接
+            database::add_book,                  // 添加书籍信息
+            database::get_all_books,             // 获取所有书籍列表
+            database::list_books,                // 分页获取书籍摘要列表
+            database::get_book,                  // 获取单本书籍的完整信息
+            database::add_chapter,               // 添加章节内容
+            database::get_chapter,               // 获取章节内容
+            database::list_chapters,             // 获取章节列表（不含正文）
+            database::get_chapter_content,       // 只获取章节正文
+            database::update_toc,                // 更新书籍目录
+            database::get_toc_tree,              // 获取目录树
+            database::insert_toc_node,           // 插入目录节点
+            database::move_toc_node,             // 移动目录节点
+            database::indent_toc_node,           // 目录节点增加缩进
+            database::outdent_toc_node,          // 目录节点减少缩进
+            database::delete_toc_node,           // 删除目录节点
+            database::get_chapter_where,         // 条件查询章节（已弃用）
+            database::query_chapters,            // 按参数化条件查询章节
+            database::update_chapter,            // 更新章节内容
+            database::update_chapters,           // 批量更新章节（事务）
+            database::delete_book,               // 删除书籍
+            database::list_deleted_books,        // 回收站书籍列表
+            database::restore_book,              // 从回收站恢复书籍
+            database::purge_book,                // 彻底删除书籍及其资源
+            database::get_trash_auto_purge_days, // 获取回收站自动清理天数
+            database::set_trash_auto_purge_days, // 设置回收站自动清理天数
+            database::update_book,               // 更新书籍信息
+            database::set_book_uuid,             // 修改书籍标识
+            database::duplicate_book,            // 复制书籍
+            database::merge_books,               // 合并书籍
+            database::split_book,                // 拆分书籍
+            database::get_migration_status,      // 获取数据库迁移结果
+            encryption::get_encryption_status,   // 获取数据库加密状态
+            encryption::unlock_database,         // 输入密码解锁数据库
+            encryption::set_passphrase,          // 设置数据库密码
+            encryption::change_passphrase,       // 修改数据库密码
+            encryption::remove_passphrase,       // 移除数据库密码
+            search::search_chapters,             // 全文检索章节
+            stats::book_stats,                   // 书籍与章节统计
+            transform::apply_transforms,         // 整本书批量处理
+            transform::cancel_transform,         // 取消批量处理
+            replace::find_replace,               // 整本书查找替换
+            restructure::split_chapter,          // 拆分章节
+            restructure::merge_chapters,         // 合并相邻章节
+            import::import_txt,                  // 导入 TXT 文件
+            revision::list_revisions,            // 章节历史版本列表
+            revision::get_revision,              // 获取历史版本内容
+            revision::diff_revisions,            // 比较历史版本
+            revision::restore_revision,          // 恢复历史版本
+            revision::undo_operation,            // 撤销批量操作
+            revision::get_revision_retention,    // 获取历史版本保留策略
+            revision::set_revision_retention,    // 设置历史版本保留策略
+            tag::list_tags,                      // 标签与书单列表
+            tag::create_tag,                     // 新建标签或书单
+            tag::rename_tag,                     // 重命名标签
+            tag::delete_tag,                     // 删除标签
+            tag::get_book_tags,                  // 获取书籍的标签
+            tag::set_book_tags,                  // 设置书籍的标签
+            tag::assign_tag,                     // 批量添加或移除标签
+            tag::get_books_by_tags,              // 按标签查询书籍
+            maintenance::integrity_check,        // 检查数据库完整性
+            maintenance::checkpoint_wal,         // 执行 WAL 检查点
+            maintenance::vacuum_database,        // 整理数据库释放空间
+            maintenance::database_stats,         // 数据库空间占用统计
+            compress::recompress_library,        // 压缩书库中未压缩的正文
+            fileutil::read_image,                // 读取图片文件
+            fileutil::clear_app_data,            // 清除应用数据
+            fileutil::open_folder,               // 打开文件夹
+            fileutil::zip_app_directory,         // 压缩应用目录
+            fileutil::unzip_file,                // 解压文件
+            check_for_updates,                   // 检查更新
+            get_app_info                         // 获取应用信息
         ]);
 
     // 仅在桌面环境下初始化窗口状态插件（用于保存和恢复窗口状态）
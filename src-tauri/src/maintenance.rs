@@ -0,0 +1,471 @@
+use crate::database::{get_db_connection, get_read_connection, DbResponse};
+use crate::error::AppResult;
+use crate::pool::run_blocking;
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use tauri::ipc::Channel;
+use tauri::{command, AppHandle};
+
+// integrity_check 默认最多报告的问题数量
+const MAX_INTEGRITY_ERRORS: u32 = 100;
+// 增量整理每次释放的页数，每批之间发送一次进度
+const INCREMENTAL_VACUUM_STEP: i64 = 256;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IntegrityReport {
+    pub ok: bool,
+    // 是否只做了快速检查（quick_check 不校验索引内容）
+    pub quick: bool,
+    // integrity_check 报告的问题，每条一行
+    pub errors: Vec<String>,
+    // 外键约束检查的问题
+    pub foreign_key_errors: Vec<String>,
+    // 全文索引与章节表不一致时的错误信息
+    pub fts_error: Option<String>,
+}
+
+// 检查点模式，对应 PRAGMA wal_checkpoint 的参数
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum CheckpointMode {
+    Passive,
+    Full,
+    Restart,
+    // 写回后把 WAL 文件截断为 0 字节
+    #[default]
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CheckpointMode::Passive => "PASSIVE",
+            CheckpointMode::Full => "FULL",
+            CheckpointMode::Restart => "RESTART",
+            CheckpointMode::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalCheckpoint {
+    // 有读连接正在使用 WAL，检查点未能全部完成
+    pub busy: bool,
+    // WAL 中的帧数
+    pub log_frames: i64,
+    // 已写回数据库文件的帧数
+    pub checkpointed_frames: i64,
+    // 检查点完成后 WAL 文件的大小（字节）
+    pub wal_size: u64,
+}
+
+// 整理进度，通过 Channel 发送给前端
+// phase 依次为 optimize（合并全文索引）、vacuum（完整整理，无法细分进度）或 incremental（增量整理，按页计数）、checkpoint
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VacuumProgress {
+    pub phase: String,
+    pub current: i64,
+    pub total: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VacuumResult {
+    // 是否执行了完整的 VACUUM
+    pub full: bool,
+    // 释放的空闲页数
+    pub freed_pages: i64,
+    // 整理前后数据库文件与 WAL 文件的总大小（字节）
+    pub size_before: u64,
+    pub size_after: u64,
+}
+
+// 单个表或索引占用的空间
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ObjectSize {
+    pub name: String,
+    // table 或 index，FTS 的内部表也是 table
+    pub kind: String,
+    // 所属的表，表自身为其名称
+    pub table: String,
+    pub pages: i64,
+    // 占用字节数与其中未使用的字节数
+    pub size: i64,
+    pub unused: i64,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DatabaseStats {
+    pub file_size: u64,
+    pub wal_size: u64,
+    pub page_size: i64,
+    pub page_count: i64,
+    // 空闲页数，整理后可释放
+    pub freelist_count: i64,
+    // none、full 或 incremental
+    pub auto_vacuum: String,
+    // 按占用空间从大到小排列
+    pub objects: Vec<ObjectSize>,
+}
+
+// 数据库文件与 WAL 文件的大小，内存库返回 0
+fn file_sizes(db: &Connection) -> (u64, u64) {
+    let size = |path: String| fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+    match db.path() {
+        Some(path) if !path.is_empty() => (size(path.to_string()), size(format!("{}-wal", path))),
+        _ => (0, 0),
+    }
+}
+
+fn pragma_i64(db: &Connection, name: &str) -> Result<i64, rusqlite::Error> {
+    db.pragma_query_value(None, name, |row| row.get(0))
+}
+
+pub fn checkpoint(db: &Connection, mode: CheckpointMode) -> AppResult<WalCheckpoint> {
+    let (busy, log_frames, checkpointed_frames) = db.query_row(
+        &format!("PRAGMA wal_checkpoint({})", mode.as_str()),
+        [],
+        |row| Ok((row.get::<_, i64>(0)? != 0, row.get(1)?, row.get(2)?)),
+    )?;
+    Ok(WalCheckpoint {
+        busy,
+        log_frames,
+        checkpointed_frames,
+        wal_size: file_sizes(db).1,
+    })
+}
+
+fn run_integrity_check(
+    db: &Connection,
+    quick: bool,
+    max_errors: u32,
+) -> AppResult<IntegrityReport> {
+    let pragma = if quick {
+        "quick_check"
+    } else {
+        "integrity_check"
+    };
+    let mut stmt = db.prepare(&format!("PRAGMA {}({})", pragma, max_errors))?;
+    let errors = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?
+        .into_iter()
+        .filter(|line| line != "ok")
+        .collect::<Vec<_>>();
+
+    let mut stmt = db.prepare("PRAGMA foreign_key_check")?;
+    let foreign_key_errors = stmt
+        .query_map([], |row| {
+            Ok(format!(
+                "表 {} 第 {} 行引用的 {} 记录不存在",
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<i64>>(1)?
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+                row.get::<_, String>(2)?
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(IntegrityReport {
+        ok: errors.is_empty() && foreign_key_errors.is_empty(),
+        quick,
+        errors,
+        foreign_key_errors,
+        fts_error: None,
+    })
+}
+
+// 全文索引表：trigram 索引与短词索引
+const FTS_TABLES: [&str; 2] = ["ee_chapter_fts", "ee_chapter_bigram"];
+
+// FTS5 的 integrity-check 以 INSERT 形式执行，只能在写连接上运行
+fn check_fts(db: &Connection) -> Option<String> {
+    FTS_TABLES.iter().find_map(|table| {
+        db.execute(
+            &format!(
+                "INSERT INTO {0} ({0}, rank) VALUES ('integrity-check', 1)",
+                table
+            ),
+            [],
+        )
+        .err()
+        .map(|err| format!("{}: {}", table, err))
+    })
+}
+
+fn run_vacuum(
+    db: &Connection,
+    full: bool,
+    on_progress: &Channel<VacuumProgress>,
+) -> AppResult<VacuumResult> {
+    let send = |phase: &str, current: i64, total: i64| {
+        let _ = on_progress.send(VacuumProgress {
+            phase: phase.to_string(),
+            current,
+            total,
+        });
+    };
+
+    let (file_size, wal_size) = file_sizes(db);
+
+    // 删除章节后全文索引中仍保留已删除的条目，先合并索引段使这些页变为空闲页
+    send("optimize", 0, 1);
+    for table in FTS_TABLES {
+        db.execute(
+            &format!("INSERT INTO {0} ({0}) VALUES ('optimize')", table),
+            [],
+        )?;
+    }
+    send("optimize", 1, 1);
+
+    let freelist_count = pragma_i64(db, "freelist_count")?;
+    // auto_vacuum: 0 = none，1 = full，2 = incremental
+    let incremental = pragma_i64(db, "auto_vacuum")? == 2;
+
+    // 旧库未开启增量整理，需要一次完整的 VACUUM 才能切换模式
+    let full = full || !incremental;
+    if full {
+        send("vacuum", 0, 1);
+        db.pragma_update(None, "auto_vacuum", "INCREMENTAL")?;
+        db.execute_batch("VACUUM")?;
+        send("vacuum", 1, 1);
+    } else {
+        send("incremental", 0, freelist_count);
+        let mut remaining = freelist_count;
+        while remaining > 0 {
+            db.execute_batch(&format!(
+                "PRAGMA incremental_vacuum({})",
+                INCREMENTAL_VACUUM_STEP
+            ))?;
+            let left = pragma_i64(db, "freelist_count")?;
+            if left >= remaining {
+                break;
+            }
+            remaining = left;
+            send("incremental", freelist_count - remaining, freelist_count);
+        }
+    }
+
+    // VACUUM 的结果先写入 WAL，检查点之后数据库文件才会真正变小
+    send("checkpoint", 0, 1);
+    checkpoint(db, CheckpointMode::Truncate)?;
+    send("checkpoint", 1, 1);
+
+    let (file_size_after, wal_size_after) = file_sizes(db);
+    Ok(VacuumResult {
+        full,
+        freed_pages: freelist_count - pragma_i64(db, "freelist_count")?,
+        size_before: file_size + wal_size,
+        size_after: file_size_after + wal_size_after,
+    })
+}
+
+fn load_database_stats(db: &Connection) -> AppResult<DatabaseStats> {
+    let (file_size, wal_size) = file_sizes(db);
+    let auto_vacuum = match pragma_i64(db, "auto_vacuum")? {
+        1 => "full",
+        2 => "incremental",
+        _ => "none",
+    };
+
+    // aggregate = TRUE 时 dbstat 每个对象只返回一行，pageno 为页数，pgsize 与 unused 为总和
+    let mut stmt = db.prepare(
+        "SELECT s.name, COALESCE(m.type, 'table'), COALESCE(m.tbl_name, s.name), \
+                s.pageno, s.pgsize, s.unused \
+         FROM dbstat s LEFT JOIN sqlite_schema m ON m.name = s.name \
+         WHERE s.aggregate = TRUE \
+         ORDER BY s.pgsize DESC, s.name",
+    )?;
+    let objects = stmt
+        .query_map([], |row| {
+            Ok(ObjectSize {
+                name: row.get(0)?,
+                kind: row.get(1)?,
+                table: row.get(2)?,
+                pages: row.get(3)?,
+                size: row.get(4)?,
+                unused: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(DatabaseStats {
+        file_size,
+        wal_size,
+        page_size: pragma_i64(db, "page_size")?,
+        page_count: pragma_i64(db, "page_count")?,
+        freelist_count: pragma_i64(db, "freelist_count")?,
+        auto_vacuum: auto_vacuum.to_string(),
+        objects,
+    })
+}
+
+// 检查数据库完整性，quick 为 true 时使用较快的 quick_check
+// 主体检查在只读连接上执行，不阻塞写入
+#[command]
+pub async fn integrity_check(
+    quick: Option<bool>,
+    max_errors: Option<u32>,
+    app_handle: AppHandle,
+) -> DbResponse<IntegrityReport> {
+    run_blocking(app_handle, move |state| {
+        let mut report = {
+            let db = get_read_connection(state)?;
+            run_integrity_check(
+                &db,
+                quick.unwrap_or(false),
+                max_errors.unwrap_or(MAX_INTEGRITY_ERRORS).max(1),
+            )?
+        };
+        let db = get_db_connection(state)?;
+        report.fts_error = check_fts(&db);
+        report.ok = report.ok && report.fts_error.is_none();
+        Ok(report)
+    })
+    .await
+    .into()
+}
+
+// 把 WAL 中的内容写回数据库文件，默认截断 WAL
+#[command]
+pub async fn checkpoint_wal(
+    mode: Option<CheckpointMode>,
+    app_handle: AppHandle,
+) -> DbResponse<WalCheckpoint> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+        checkpoint(&db, mode.unwrap_or_default())
+    })
+    .await
+    .into()
+}
+
+// 整理数据库、释放空闲页。已开启增量整理的库默认只做增量整理，full 为 true 时重建整个文件
+#[command]
+pub async fn vacuum_database(
+    full: Option<bool>,
+    on_progress: Channel<VacuumProgress>,
+    app_handle: AppHandle,
+) -> DbResponse<VacuumResult> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+        run_vacuum(&db, full.unwrap_or(false), &on_progress)
+    })
+    .await
+    .into()
+}
+
+// 数据库空间占用统计，按表和索引列出
+#[command]
+pub async fn database_stats(app_handle: AppHandle) -> DbResponse<DatabaseStats> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        load_database_stats(&db)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::insert_chapter;
+    use crate::migration::run_migrations;
+    use crate::testutil::open_book;
+    use std::path::PathBuf;
+
+    // 文件数据库，空闲页与文件大小只有落盘后才有意义
+    fn open_file_db(name: &str) -> (Connection, PathBuf) {
+        let path = std::env::temp_dir().join(format!("myebook-{}-{}.db", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        let mut db = Connection::open(&path).unwrap();
+        run_migrations(&mut db, &std::env::temp_dir()).unwrap();
+        db.execute("INSERT INTO ee_book (title, isDel) VALUES ('书', 0)", [])
+            .unwrap();
+        (db, path)
+    }
+
+    // 写入一批难以压缩的章节后再全部删除，留下空闲页
+    fn fill_and_delete(db: &Connection) {
+        let mut state: u32 = 7;
+        for index in 0..100 {
+            let text: String = (0..2000)
+                .map(|_| {
+                    state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                    char::from(b'a' + (state >> 16) as u8 % 26)
+                })
+                .collect();
+            insert_chapter(db, 1, "章", &format!("c{}", index), &text, None).unwrap();
+        }
+        db.execute("DELETE FROM ee_chapter", []).unwrap();
+    }
+
+    #[test]
+    fn integrity_check_passes_on_a_clean_database() {
+        let db = open_book(&[("第一章", "<p>甲</p>")]);
+        for quick in [false, true] {
+            let report = run_integrity_check(&db, quick, MAX_INTEGRITY_ERRORS).unwrap();
+            assert!(report.ok);
+            assert_eq!(report.quick, quick);
+            assert!(report.errors.is_empty());
+            assert!(report.foreign_key_errors.is_empty());
+        }
+        assert_eq!(check_fts(&db), None);
+    }
+
+    #[test]
+    fn vacuum_frees_pages_on_full_and_incremental_paths() {
+        let (db, path) = open_file_db("vacuum");
+        let progress = Channel::new(|_| Ok(()));
+
+        // 未开启增量整理的库先做一次完整整理并切换模式
+        fill_and_delete(&db);
+        let result = run_vacuum(&db, false, &progress).unwrap();
+        assert!(result.full);
+        assert!(result.freed_pages > 0);
+        assert!(result.size_after < result.size_before);
+        assert_eq!(pragma_i64(&db, "auto_vacuum").unwrap(), 2);
+
+        fill_and_delete(&db);
+        let result = run_vacuum(&db, false, &progress).unwrap();
+        assert!(!result.full);
+        assert!(result.freed_pages > 0);
+        assert_eq!(pragma_i64(&db, "freelist_count").unwrap(), 0);
+
+        drop(db);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn database_stats_lists_dbstat_rows() {
+        let db = open_book(&[("第一章", "<p>甲</p>")]);
+        let stats = load_database_stats(&db).unwrap();
+        assert!(stats.page_size > 0);
+        assert!(stats.page_count > 0);
+
+        let chapter = stats
+            .objects
+            .iter()
+            .find(|object| object.name == "ee_chapter")
+            .expect("ee_chapter 应出现在统计中");
+        assert_eq!(chapter.kind, "table");
+        assert_eq!(chapter.table, "ee_chapter");
+        assert!(chapter.pages > 0);
+        assert!(stats
+            .objects
+            .iter()
+            .any(|object| object.kind == "index" && object.table == "ee_chapter_stats"));
+        // 按占用空间从大到小排列
+        assert!(stats
+            .objects
+            .windows(2)
+            .all(|pair| pair[0].size >= pair[1].size));
+    }
+}
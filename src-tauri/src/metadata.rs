@@ -0,0 +1,437 @@
+use crate::error::{AppError, AppResult};
+use rusqlite::{params, Connection, Transaction};
+use serde::{Deserialize, Serialize};
+
+// 作者的默认角色（MARC relator 代码）
+pub const ROLE_AUTHOR: &str = "aut";
+
+// 作者、译者、编者等，role 使用 MARC relator 代码：aut 作者、trl 译者、edt 编者、ill 插画
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Contributor {
+    pub name: String,
+    #[serde(default = "default_role")]
+    pub role: String,
+}
+
+fn default_role() -> String {
+    ROLE_AUTHOR.to_string()
+}
+
+// 书籍标识，scheme 如 isbn、doi、uuid、calibre
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Identifier {
+    pub scheme: String,
+    pub value: String,
+}
+
+// 书籍扩展元数据，导入时从 EPUB 的 OPF 中读取，导出时写回 OPF
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BookMetadata {
+    pub authors: Vec<Contributor>,
+    pub language: Option<String>,
+    pub publisher: Option<String>,
+    pub pub_date: Option<String>,
+    pub identifiers: Vec<Identifier>,
+    pub series: Option<String>,
+    pub series_index: Option<f64>,
+    pub subjects: Vec<String>,
+    pub rights: Option<String>,
+}
+
+// 空字符串视为未设置
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+}
+
+// 读取一本书的扩展元数据
+pub fn load_book_metadata(db: &Connection, book_id: i64) -> Result<BookMetadata, rusqlite::Error> {
+    let mut metadata = db.query_row(
+        "SELECT language, publisher, pubDate, series, seriesIndex, rights FROM ee_book WHERE id = ?",
+        params![book_id],
+        |row| {
+            Ok(BookMetadata {
+                language: row.get(0)?,
+                publisher: row.get(1)?,
+                pub_date: row.get(2)?,
+                series: row.get(3)?,
+                series_index: row.get(4)?,
+                rights: row.get(5)?,
+                ..Default::default()
+            })
+        },
+    )?;
+
+    let mut stmt = db.prepare(
+        "SELECT name, role FROM ee_book_contributor WHERE bookId = ? ORDER BY sortIndex, id",
+    )?;
+    metadata.authors = stmt
+        .query_map(params![book_id], |row| {
+            Ok(Contributor {
+                name: row.get(0)?,
+                role: row.get(1)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut stmt =
+        db.prepare("SELECT scheme, value FROM ee_book_identifier WHERE bookId = ? ORDER BY id")?;
+    metadata.identifiers = stmt
+        .query_map(params![book_id], |row| {
+            Ok(Identifier {
+                scheme: row.get(0)?,
+                value: row.get(1)?,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+
+    let mut stmt =
+        db.prepare("SELECT subject FROM ee_book_subject WHERE bookId = ? ORDER BY sortIndex, id")?;
+    metadata.subjects = stmt
+        .query_map(params![book_id], |row| row.get(0))?
+        .collect::<Result<_, _>>()?;
+
+    Ok(metadata)
+}
+
+fn replace_contributors(
+    tx: &Transaction,
+    book_id: i64,
+    authors: &[Contributor],
+) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "DELETE FROM ee_book_contributor WHERE bookId = ?",
+        params![book_id],
+    )?;
+    let mut stmt = tx.prepare(
+        "INSERT INTO ee_book_contributor (bookId, sortIndex, name, role) VALUES (?, ?, ?, ?)",
+    )?;
+    for (index, author) in authors
+        .iter()
+        .filter(|author| !author.name.trim().is_empty())
+        .enumerate()
+    {
+        let role = if author.role.trim().is_empty() {
+            ROLE_AUTHOR
+        } else {
+            author.role.trim()
+        };
+        stmt.execute(params![book_id, index as i64, author.name.trim(), role])?;
+    }
+    Ok(())
+}
+
+// 整体写入一本书的扩展元数据（覆盖原有数据）
+pub fn write_book_metadata(
+    tx: &Transaction,
+    book_id: i64,
+    metadata: &BookMetadata,
+) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "UPDATE ee_book SET language = ?, publisher = ?, pubDate = ?, series = ?, \
+         seriesIndex = ?, rights = ? WHERE id = ?",
+        params![
+            non_empty(&metadata.language),
+            non_empty(&metadata.publisher),
+            non_empty(&metadata.pub_date),
+            non_empty(&metadata.series),
+            metadata.series_index,
+            non_empty(&metadata.rights),
+            book_id
+        ],
+    )?;
+
+    replace_contributors(tx, book_id, &metadata.authors)?;
+
+    tx.execute(
+        "DELETE FROM ee_book_identifier WHERE bookId = ?",
+        params![book_id],
+    )?;
+    let mut stmt =
+        tx.prepare("INSERT INTO ee_book_identifier (bookId, scheme, value) VALUES (?, ?, ?)")?;
+    for identifier in metadata
+        .identifiers
+        .iter()
+        .filter(|identifier| !identifier.value.trim().is_empty())
+    {
+        stmt.execute(params![
+            book_id,
+            identifier.scheme.trim().to_lowercase(),
+            identifier.value.trim()
+        ])?;
+    }
+
+    tx.execute(
+        "DELETE FROM ee_book_subject WHERE bookId = ?",
+        params![book_id],
+    )?;
+    let mut stmt =
+        tx.prepare("INSERT INTO ee_book_subject (bookId, sortIndex, subject) VALUES (?, ?, ?)")?;
+    for (index, subject) in metadata
+        .subjects
+        .iter()
+        .map(|subject| subject.trim())
+        .filter(|subject| !subject.is_empty())
+        .enumerate()
+    {
+        stmt.execute(params![book_id, index as i64, subject])?;
+    }
+    Ok(())
+}
+
+// ee_book.author 中显示的作者：所有 aut 角色的作者以 " & " 连接
+pub fn display_author(authors: &[Contributor]) -> Option<String> {
+    let names: Vec<&str> = authors
+        .iter()
+        .filter(|author| author.role == ROLE_AUTHOR)
+        .map(|author| author.name.trim())
+        .filter(|name| !name.is_empty())
+        .collect();
+    if names.is_empty() {
+        None
+    } else {
+        Some(names.join(" & "))
+    }
+}
+
+// 只修改了 author 字段时，让作者列表与之保持一致（保留译者等其他角色）
+pub fn sync_primary_author(
+    tx: &Transaction,
+    book_id: i64,
+    author: &str,
+) -> Result<(), rusqlite::Error> {
+    let metadata = load_book_metadata(tx, book_id)?;
+    if display_author(&metadata.authors).as_deref() == Some(author.trim()) {
+        return Ok(());
+    }
+    let mut authors = vec![Contributor {
+        name: author.to_string(),
+        role: default_role(),
+    }];
+    authors.extend(
+        metadata
+            .authors
+            .into_iter()
+            .filter(|contributor| contributor.role != ROLE_AUTHOR),
+    );
+    replace_contributors(tx, book_id, &authors)
+}
+
+// 复制扩展元数据到另一本书（用于复制、拆分书籍）
+pub fn copy_book_metadata(
+    tx: &Transaction,
+    from_id: i64,
+    to_id: i64,
+) -> Result<(), rusqlite::Error> {
+    let metadata = load_book_metadata(tx, from_id)?;
+    write_book_metadata(tx, to_id, &metadata)
+}
+
+// 删除书籍时一并删除扩展元数据
+pub fn delete_book_metadata(tx: &Transaction, book_id: i64) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "DELETE FROM ee_book_contributor WHERE bookId = ?",
+        params![book_id],
+    )?;
+    tx.execute(
+        "DELETE FROM ee_book_identifier WHERE bookId = ?",
+        params![book_id],
+    )?;
+    tx.execute(
+        "DELETE FROM ee_book_subject WHERE bookId = ?",
+        params![book_id],
+    )?;
+    Ok(())
+}
+
+// 生成新的书籍 UUID，导出 EPUB 时作为包标识（dtb:uid）
+pub fn generate_book_uuid() -> String {
+    uuid::Uuid::new_v4().to_string()
+}
+
+// 导入的标识去掉 urn:uuid: 前缀，空字符串视为未设置
+fn normalize_book_uuid(value: &str) -> Option<String> {
+    let value = value.trim();
+    let value = value
+        .strip_prefix("urn:uuid:")
+        .or_else(|| value.strip_prefix("URN:UUID:"))
+        .unwrap_or(value)
+        .trim();
+    if value.is_empty() {
+        None
+    } else {
+        Some(value.to_string())
+    }
+}
+
+pub fn book_uuid_in_use(
+    db: &Connection,
+    uuid: &str,
+    except_id: Option<i64>,
+) -> Result<bool, rusqlite::Error> {
+    db.query_row(
+        "SELECT EXISTS (SELECT 1 FROM ee_book WHERE uuid = ? AND id IS NOT ?)",
+        params![uuid, except_id],
+        |row| row.get(0),
+    )
+}
+
+// 优先使用导入的标识；未提供或已被其他书籍占用时生成新的 UUID
+pub fn resolve_book_uuid(
+    db: &Connection,
+    requested: Option<&str>,
+) -> Result<String, rusqlite::Error> {
+    if let Some(uuid) = requested.and_then(normalize_book_uuid) {
+        if !book_uuid_in_use(db, &uuid, None)? {
+            return Ok(uuid);
+        }
+    }
+    Ok(generate_book_uuid())
+}
+
+// 修改书籍标识，uuid 为空时重新生成
+pub fn set_book_uuid_with(db: &Connection, book_id: i64, uuid: Option<&str>) -> AppResult<String> {
+    let uuid = match uuid.and_then(normalize_book_uuid) {
+        Some(uuid) => {
+            if book_uuid_in_use(db, &uuid, Some(book_id))? {
+                return Err(AppError::Conflict(format!(
+                    "标识 {} 已被其他书籍使用",
+                    uuid
+                )));
+            }
+            uuid
+        }
+        None => generate_book_uuid(),
+    };
+    let updated = db.execute(
+        "UPDATE ee_book SET uuid = ? WHERE id = ?",
+        params![uuid, book_id],
+    )?;
+    if updated == 0 {
+        return Err(AppError::NotFound(format!("书籍 {} 不存在", book_id)));
+    }
+    Ok(uuid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::open_db;
+
+    fn contributor(name: &str, role: &str) -> Contributor {
+        Contributor {
+            name: name.to_string(),
+            role: role.to_string(),
+        }
+    }
+
+    // 两本书，id 分别为 1、2
+    fn open_books() -> Connection {
+        let db = open_db();
+        db.execute_batch(
+            "INSERT INTO ee_book (title, isDel) VALUES ('甲', 0);
+             INSERT INTO ee_book (title, isDel) VALUES ('乙', 0);",
+        )
+        .unwrap();
+        db
+    }
+
+    #[test]
+    fn resolve_book_uuid_prefers_free_imported_identifier() {
+        let db = open_books();
+        let uuid = resolve_book_uuid(&db, Some(" urn:uuid:abc ")).unwrap();
+        assert_eq!(uuid, "abc");
+
+        db.execute("UPDATE ee_book SET uuid = 'abc' WHERE id = 1", [])
+            .unwrap();
+        // 已被占用或未提供时生成新的 UUID
+        let generated = resolve_book_uuid(&db, Some("abc")).unwrap();
+        assert_ne!(generated, "abc");
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+        assert!(uuid::Uuid::parse_str(&resolve_book_uuid(&db, Some("  ")).unwrap()).is_ok());
+    }
+
+    #[test]
+    fn set_book_uuid_detects_conflicts() {
+        let db = open_books();
+        assert_eq!(
+            set_book_uuid_with(&db, 1, Some("urn:uuid:abc")).unwrap(),
+            "abc"
+        );
+        // 书籍自己的标识可以重复设置
+        assert_eq!(set_book_uuid_with(&db, 1, Some("abc")).unwrap(), "abc");
+        assert!(matches!(
+            set_book_uuid_with(&db, 2, Some("abc")),
+            Err(AppError::Conflict(_))
+        ));
+        assert!(matches!(
+            set_book_uuid_with(&db, 3, Some("def")),
+            Err(AppError::NotFound(_))
+        ));
+        let generated = set_book_uuid_with(&db, 2, None).unwrap();
+        assert!(uuid::Uuid::parse_str(&generated).is_ok());
+    }
+
+    #[test]
+    fn contributor_roles_round_trip() {
+        let mut db = open_books();
+        let metadata = BookMetadata {
+            authors: vec![
+                contributor(" 张三 ", "aut"),
+                contributor("李四", "trl"),
+                contributor("王五", ""),
+                contributor("  ", "edt"),
+            ],
+            ..Default::default()
+        };
+        let tx = db.transaction().unwrap();
+        write_book_metadata(&tx, 1, &metadata).unwrap();
+        tx.commit().unwrap();
+
+        // 去掉空白与空名字，角色为空时视为作者
+        let authors = load_book_metadata(&db, 1).unwrap().authors;
+        assert_eq!(
+            authors,
+            vec![
+                contributor("张三", "aut"),
+                contributor("李四", "trl"),
+                contributor("王五", "aut"),
+            ]
+        );
+        assert_eq!(display_author(&authors).as_deref(), Some("张三 & 王五"));
+
+        // JSON 中省略 role 时默认为作者
+        let parsed: Contributor = serde_json::from_str(r#"{"name":"赵六"}"#).unwrap();
+        assert_eq!(parsed, contributor("赵六", "aut"));
+    }
+
+    #[test]
+    fn sync_primary_author_keeps_other_roles() {
+        let mut db = open_books();
+        let tx = db.transaction().unwrap();
+        let metadata = BookMetadata {
+            authors: vec![contributor("张三", "aut"), contributor("李四", "trl")],
+            ..Default::default()
+        };
+        write_book_metadata(&tx, 1, &metadata).unwrap();
+
+        // 作者未变化时不修改
+        sync_primary_author(&tx, 1, " 张三 ").unwrap();
+        assert_eq!(
+            load_book_metadata(&tx, 1).unwrap().authors,
+            metadata.authors
+        );
+
+        sync_primary_author(&tx, 1, "王五").unwrap();
+        assert_eq!(
+            load_book_metadata(&tx, 1).unwrap().authors,
+            vec![contributor("王五", "aut"), contributor("李四", "trl")]
+        );
+        tx.commit().unwrap();
+    }
+}
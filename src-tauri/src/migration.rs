@@ -0,0 +1,661 @@
+use rusqlite::{params, Connection, Transaction};
+use serde::Serialize;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 单个迁移：版本号必须从 1 开始连续递增
+pub struct Migration {
+    pub version: i32,
+    pub description: &'static str,
+    pub up: fn(&Transaction) -> Result<(), rusqlite::Error>,
+}
+
+// 迁移列表，按版本顺序排列。已发布的迁移不允许修改，只能在末尾追加。
+// 迁移中的 SQL 与数据处理都写在本文件中，不调用其他模块的函数，以免这些函数日后修改时改变旧迁移的行为
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        description: "创建 ee_book 与 ee_chapter 基础表",
+        up: migrate_v1_base_tables,
+    },
+    Migration {
+        version: 2,
+        description: "目录拆分为 ee_toc_node 表",
+        up: migrate_v2_toc_nodes,
+    },
+    Migration {
+        version: 3,
+        description: "章节全文索引 ee_chapter_fts",
+        up: migrate_v3_chapter_fts,
+    },
+    Migration {
+        version: 4,
+        description: "章节历史版本 ee_chapter_revision 与配置表 ee_setting",
+        up: migrate_v4_revisions,
+    },
+    Migration {
+        version: 5,
+        description: "ee_book 增加删除时间 deleteTime",
+        up: migrate_v5_book_delete_time,
+    },
+    Migration {
+        version: 6,
+        description: "书籍扩展元数据：语言、出版社、系列、作者角色、标识与主题",
+        up: migrate_v6_book_metadata,
+    },
+    Migration {
+        version: 7,
+        description: "ee_book 增加唯一标识 uuid",
+        up: migrate_v7_book_uuid,
+    },
+    Migration {
+        version: 8,
+        description: "标签与书单 ee_tag、ee_book_tag",
+        up: migrate_v8_tags,
+    },
+    Migration {
+        version: 9,
+        description: "章节统计缓存 ee_chapter_stats",
+        up: migrate_v9_chapter_stats,
+    },
+    Migration {
+        version: 10,
+        description: "章节正文压缩：长度列与全文索引重建",
+        up: migrate_v10_content_compression,
+    },
+    Migration {
+        version: 11,
+        description: "历史版本记录章节的新建与删除，并保存操作前的目录",
+        up: migrate_v11_structure_revisions,
+    },
+    Migration {
+        version: 12,
+        description: "短词全文索引 ee_chapter_bigram",
+        up: migrate_v12_chapter_bigram,
+    },
+    Migration {
+        version: 13,
+        description: "全文索引改由应用写入去掉标签后的正文",
+        up: migrate_v13_plain_text_fts,
+    },
+    Migration {
+        version: 14,
+        description: "段落改按块级元素统计，清空章节统计缓存",
+        up: migrate_v14_reset_chapter_stats,
+    },
+];
+
+// 迁移失败时返回给前端的信息
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MigrationError {
+    pub from_version: i32,
+    pub version: i32,
+    pub description: String,
+    pub error: String,
+    pub backup_path: Option<String>,
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "数据库迁移 v{} ({}) 失败: {}",
+            self.version, self.description, self.error
+        )?;
+        if let Some(path) = &self.backup_path {
+            write!(f, "，原数据已备份到 {}", path)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+// 当前代码对应的数据库版本
+pub fn latest_version() -> i32 {
+    MIGRATIONS.last().map(|m| m.version).unwrap_or(0)
+}
+
+pub fn get_user_version(db: &Connection) -> Result<i32, rusqlite::Error> {
+    db.query_row("PRAGMA user_version", [], |row| row.get(0))
+}
+
+// 执行所有未应用的迁移。全部迁移在同一事务中执行并逐个更新 user_version，
+// 任一迁移失败时整体回滚，原库保持在升级前的版本
+pub fn run_migrations(db: &mut Connection, backup_dir: &Path) -> Result<(), MigrationError> {
+    let from_version = get_user_version(db).map_err(|err| MigrationError {
+        from_version: 0,
+        version: 0,
+        description: "读取数据库版本".to_string(),
+        error: err.to_string(),
+        backup_path: None,
+    })?;
+
+    let pending: Vec<&Migration> = MIGRATIONS
+        .iter()
+        .filter(|m| m.version > from_version)
+        .collect();
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    // 已有数据的库在升级前先备份
+    let backup_path = if is_empty_database(db).unwrap_or(false) {
+        None
+    } else {
+        let path = backup_database(db, backup_dir, from_version).map_err(|err| MigrationError {
+            from_version,
+            version: from_version,
+            description: "升级前备份数据库".to_string(),
+            error: err.to_string(),
+            backup_path: None,
+        })?;
+        Some(path.to_string_lossy().to_string())
+    };
+
+    let to_error = |migration: &Migration, err: rusqlite::Error| MigrationError {
+        from_version,
+        version: migration.version,
+        description: migration.description.to_string(),
+        error: err.to_string(),
+        backup_path: backup_path.clone(),
+    };
+    let last = pending[pending.len() - 1];
+    let tx = db.transaction().map_err(|err| to_error(pending[0], err))?;
+    for migration in &pending {
+        apply_migration(&tx, migration).map_err(|err| to_error(migration, err))?;
+    }
+    tx.commit().map_err(|err| to_error(last, err))?;
+    for migration in pending {
+        println!(
+            "[DB] 已应用迁移 v{}: {}",
+            migration.version, migration.description
+        );
+    }
+
+    Ok(())
+}
+
+fn apply_migration(tx: &Transaction, migration: &Migration) -> Result<(), rusqlite::Error> {
+    (migration.up)(tx)?;
+    tx.pragma_update(None, "user_version", migration.version)
+}
+
+fn is_empty_database(db: &Connection) -> Result<bool, rusqlite::Error> {
+    let count: i64 = db.query_row(
+        "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count == 0)
+}
+
+// 使用 VACUUM INTO 生成一致的备份（包含 WAL 中尚未写回的数据）
+fn backup_database(
+    db: &Connection,
+    backup_dir: &Path,
+    from_version: i32,
+) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    fs::create_dir_all(backup_dir)?;
+    let timestamp = std::time::SystemTime::now()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .map(|dur| dur.as_secs())
+        .unwrap_or(0);
+    let path = backup_dir.join(format!("books-v{}-{}.db", from_version, timestamp));
+    db.execute("VACUUM INTO ?", [path.to_string_lossy().to_string()])?;
+    Ok(path)
+}
+
+// v1: 原有的建表语句，老用户的库中表已存在，因此保留 IF NOT EXISTS
+fn migrate_v1_base_tables(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS ee_book (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT,
+            author TEXT,
+            description TEXT,
+            toc TEXT,
+            isDel INTEGER,
+            createTime TEXT,
+            updateTime TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS ee_chapter (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bookId INTEGER,
+            label TEXT,
+            href TEXT,
+            content TEXT,
+            createTime TEXT,
+            updateTime TEXT
+        );
+    ",
+    )
+}
+
+// v2: 新建目录节点表，并把每本书原有的 toc JSON 解析为节点
+fn migrate_v2_toc_nodes(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS ee_toc_node (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bookId INTEGER NOT NULL,
+            parentId INTEGER,
+            sortIndex INTEGER NOT NULL DEFAULT 0,
+            chapterId INTEGER,
+            label TEXT,
+            anchor TEXT
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_toc_node_parent
+            ON ee_toc_node (bookId, parentId, sortIndex);
+    ",
+    )?;
+
+    let books: Vec<(i64, Option<String>)> = {
+        let mut stmt = tx.prepare("SELECT id, toc FROM ee_book")?;
+        let rows = stmt.query_map(params![], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for (book_id, toc) in books {
+        let toc = toc.unwrap_or_default();
+        if toc.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<serde_json::Value>(&toc) {
+            Ok(items) => insert_v2_toc_items(tx, book_id, None, &items)?,
+            // 无法解析的目录保留在 toc 列中，不阻止整个迁移
+            Err(_) => eprintln!("[DB] 书籍 {} 的目录 JSON 无法解析，已跳过", book_id),
+        }
+    }
+    Ok(())
+}
+
+// v2 使用的目录 JSON 格式：href 为章节 id，或 "章节id#锚点" 形式的字符串
+fn parse_v2_toc_href(href: &serde_json::Value) -> (Option<i64>, Option<String>) {
+    match href {
+        serde_json::Value::Number(num) => (num.as_i64(), None),
+        serde_json::Value::String(text) => {
+            let (id, anchor) = match text.split_once('#') {
+                Some((id, anchor)) => (id, Some(anchor.to_string())),
+                None => (text.as_str(), None),
+            };
+            (id.trim().parse().ok(), anchor.filter(|a| !a.is_empty()))
+        }
+        _ => (None, None),
+    }
+}
+
+fn insert_v2_toc_items(
+    tx: &Transaction,
+    book_id: i64,
+    parent_id: Option<i64>,
+    items: &serde_json::Value,
+) -> Result<(), rusqlite::Error> {
+    let items = match items.as_array() {
+        Some(items) => items,
+        None => return Ok(()),
+    };
+    for (index, item) in items.iter().enumerate() {
+        let (chapter_id, anchor) = parse_v2_toc_href(&item["href"]);
+        let label = item["label"].as_str().unwrap_or_default();
+        tx.execute(
+            "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label, anchor) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![book_id, parent_id, index as i64, chapter_id, label, anchor],
+        )?;
+        let id = tx.last_insert_rowid();
+        insert_v2_toc_items(tx, book_id, Some(id), &item["subitems"])?;
+    }
+    Ok(())
+}
+
+// v3: 章节全文索引（trigram 分词，支持中文），由触发器与 ee_chapter 保持同步
+fn migrate_v3_chapter_fts(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS ee_chapter_fts USING fts5(
+            label,
+            content,
+            content = 'ee_chapter',
+            content_rowid = 'id',
+            tokenize = 'trigram'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_fts_insert AFTER INSERT ON ee_chapter BEGIN
+            INSERT INTO ee_chapter_fts (rowid, label, content)
+            VALUES (new.id, new.label, new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_fts_delete AFTER DELETE ON ee_chapter BEGIN
+            INSERT INTO ee_chapter_fts (ee_chapter_fts, rowid, label, content)
+            VALUES ('delete', old.id, old.label, old.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_fts_update AFTER UPDATE OF label, content ON ee_chapter BEGIN
+            INSERT INTO ee_chapter_fts (ee_chapter_fts, rowid, label, content)
+            VALUES ('delete', old.id, old.label, old.content);
+            INSERT INTO ee_chapter_fts (rowid, label, content)
+            VALUES (new.id, new.label, new.content);
+        END;
+
+        INSERT INTO ee_chapter_fts (ee_chapter_fts) VALUES ('rebuild');
+    ",
+    )
+}
+
+// v4: 章节历史版本与通用配置表
+fn migrate_v4_revisions(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS ee_chapter_revision (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            chapterId INTEGER NOT NULL,
+            bookId INTEGER,
+            label TEXT,
+            content TEXT,
+            operation TEXT NOT NULL,
+            operationId TEXT NOT NULL,
+            createTime TEXT NOT NULL
+        );
+
+        CREATE INDEX IF NOT EXISTS idx_revision_chapter
+            ON ee_chapter_revision (chapterId, id);
+        CREATE INDEX IF NOT EXISTS idx_revision_operation
+            ON ee_chapter_revision (operationId);
+
+        CREATE TABLE IF NOT EXISTS ee_setting (
+            key TEXT PRIMARY KEY,
+            value TEXT
+        );
+    ",
+    )
+}
+
+// v5: 记录书籍删除时间，已在回收站中的书籍以迁移时间作为删除时间
+fn migrate_v5_book_delete_time(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        ALTER TABLE ee_book ADD COLUMN deleteTime TEXT;
+        UPDATE ee_book SET deleteTime = strftime('%s', 'now') WHERE isDel = 1;
+    ",
+    )
+}
+
+// v6: 书籍扩展元数据。原有的 author 作为第一作者写入 ee_book_contributor
+fn migrate_v6_book_metadata(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        ALTER TABLE ee_book ADD COLUMN language TEXT;
+        ALTER TABLE ee_book ADD COLUMN publisher TEXT;
+        ALTER TABLE ee_book ADD COLUMN pubDate TEXT;
+        ALTER TABLE ee_book ADD COLUMN series TEXT;
+        ALTER TABLE ee_book ADD COLUMN seriesIndex REAL;
+        ALTER TABLE ee_book ADD COLUMN rights TEXT;
+
+        CREATE TABLE IF NOT EXISTS ee_book_contributor (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bookId INTEGER NOT NULL,
+            sortIndex INTEGER NOT NULL DEFAULT 0,
+            name TEXT NOT NULL,
+            role TEXT NOT NULL DEFAULT 'aut'
+        );
+        CREATE INDEX IF NOT EXISTS idx_book_contributor_book
+            ON ee_book_contributor (bookId, sortIndex);
+
+        CREATE TABLE IF NOT EXISTS ee_book_identifier (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bookId INTEGER NOT NULL,
+            scheme TEXT NOT NULL,
+            value TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_book_identifier_book
+            ON ee_book_identifier (bookId);
+
+        CREATE TABLE IF NOT EXISTS ee_book_subject (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bookId INTEGER NOT NULL,
+            sortIndex INTEGER NOT NULL DEFAULT 0,
+            subject TEXT NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_book_subject_book
+            ON ee_book_subject (bookId, sortIndex);
+
+        INSERT INTO ee_book_contributor (bookId, sortIndex, name, role)
+        SELECT id, 0, trim(author), 'aut' FROM ee_book
+        WHERE author IS NOT NULL AND trim(author) != '';
+    ",
+    )
+}
+
+// v7: 每本书的唯一标识，已有书籍逐本生成随机的 v4 UUID
+fn migrate_v7_book_uuid(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        ALTER TABLE ee_book ADD COLUMN uuid TEXT;
+        UPDATE ee_book SET uuid = lower(
+            hex(randomblob(4)) || '-' || hex(randomblob(2)) || '-4' ||
+            substr(hex(randomblob(2)), 2) || '-' ||
+            substr('89ab', 1 + (abs(random()) % 4), 1) || substr(hex(randomblob(2)), 2) || '-' ||
+            hex(randomblob(6))
+        );
+        CREATE UNIQUE INDEX IF NOT EXISTS idx_book_uuid ON ee_book (uuid);
+    ",
+    )
+}
+
+// v8: 标签与书单（kind 为 tag 或 collection）
+fn migrate_v8_tags(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS ee_tag (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE,
+            kind TEXT NOT NULL DEFAULT 'tag',
+            sortIndex INTEGER NOT NULL DEFAULT 0,
+            createTime TEXT
+        );
+
+        CREATE TABLE IF NOT EXISTS ee_book_tag (
+            bookId INTEGER NOT NULL,
+            tagId INTEGER NOT NULL,
+            PRIMARY KEY (bookId, tagId)
+        );
+        CREATE INDEX IF NOT EXISTS idx_book_tag_tag ON ee_book_tag (tagId);
+    ",
+    )
+}
+
+// v9: 章节统计缓存，由触发器在章节内容修改时清除
+fn migrate_v9_chapter_stats(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE TABLE IF NOT EXISTS ee_chapter_stats (
+            chapterId INTEGER PRIMARY KEY,
+            bookId INTEGER NOT NULL,
+            chars INTEGER NOT NULL,
+            cjkChars INTEGER NOT NULL,
+            latinWords INTEGER NOT NULL,
+            paragraphs INTEGER NOT NULL,
+            images INTEGER NOT NULL
+        );
+        CREATE INDEX IF NOT EXISTS idx_chapter_stats_book ON ee_chapter_stats (bookId);
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_stats_update AFTER UPDATE OF content, bookId ON ee_chapter BEGIN
+            DELETE FROM ee_chapter_stats WHERE chapterId = old.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_stats_delete AFTER DELETE ON ee_chapter BEGIN
+            DELETE FROM ee_chapter_stats WHERE chapterId = old.id;
+        END;
+    ",
+    )
+}
+
+// v10: 正文改为 zstd 压缩存储，压缩与解压都在应用中完成，触发器只处理 TEXT 存储的正文。
+// 已有数据保持 TEXT，可通过 recompress_library 压缩。
+// 全文索引改为无内容表（contentless_delete），避免在索引中再保存一份未压缩的正文
+fn migrate_v10_content_compression(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        ALTER TABLE ee_chapter ADD COLUMN charCount INTEGER;
+        ALTER TABLE ee_chapter ADD COLUMN byteCount INTEGER;
+        UPDATE ee_chapter SET
+            charCount = length(content),
+            byteCount = length(CAST(content AS BLOB));
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_length_insert AFTER INSERT ON ee_chapter
+        WHEN typeof(new.content) IS NOT 'blob'
+        BEGIN
+            UPDATE ee_chapter SET
+                charCount = length(new.content),
+                byteCount = length(CAST(new.content AS BLOB))
+            WHERE id = new.id;
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_length_update AFTER UPDATE OF content ON ee_chapter
+        WHEN typeof(new.content) IS NOT 'blob'
+        BEGIN
+            UPDATE ee_chapter SET
+                charCount = length(new.content),
+                byteCount = length(CAST(new.content AS BLOB))
+            WHERE id = new.id;
+        END;
+
+        DROP TRIGGER IF EXISTS ee_chapter_fts_insert;
+        DROP TRIGGER IF EXISTS ee_chapter_fts_delete;
+        DROP TRIGGER IF EXISTS ee_chapter_fts_update;
+        DROP TABLE IF EXISTS ee_chapter_fts;
+
+        CREATE VIRTUAL TABLE IF NOT EXISTS ee_chapter_fts USING fts5(
+            label,
+            content,
+            content = '',
+            contentless_delete = 1,
+            tokenize = 'trigram'
+        );
+        INSERT INTO ee_chapter_fts (rowid, label, content)
+            SELECT id, label, content FROM ee_chapter;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_fts_insert AFTER INSERT ON ee_chapter
+        WHEN typeof(new.content) IS NOT 'blob'
+        BEGIN
+            INSERT OR REPLACE INTO ee_chapter_fts (rowid, label, content)
+            VALUES (new.id, new.label, new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_fts_update AFTER UPDATE OF label, content ON ee_chapter
+        WHEN typeof(new.content) IS NOT 'blob'
+            AND (old.label IS NOT new.label OR old.content IS NOT new.content)
+        BEGIN
+            INSERT OR REPLACE INTO ee_chapter_fts (rowid, label, content)
+            VALUES (new.id, new.label, new.content);
+        END;
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_fts_delete AFTER DELETE ON ee_chapter BEGIN
+            DELETE FROM ee_chapter_fts WHERE rowid = old.id;
+        END;
+
+        DROP TRIGGER IF EXISTS ee_chapter_stats_update;
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_stats_update AFTER UPDATE OF content, bookId ON ee_chapter
+        WHEN old.bookId IS NOT new.bookId OR old.content IS NOT new.content
+        BEGIN
+            DELETE FROM ee_chapter_stats WHERE chapterId = old.id;
+        END;
+    ",
+    )
+}
+
+// v11: 拆分、合并章节可撤销。changeType 区分修改前的版本（update）、操作中新建的章节（create）
+// 与操作中删除的章节（delete），删除的章节同时保存 href 以便重新插入；
+// ee_toc_revision 保存每次操作前整本书的目录节点（JSON 数组），同一操作只保存第一次
+fn migrate_v11_structure_revisions(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        ALTER TABLE ee_chapter_revision ADD COLUMN changeType TEXT NOT NULL DEFAULT 'update';
+        ALTER TABLE ee_chapter_revision ADD COLUMN href TEXT;
+
+        CREATE TABLE IF NOT EXISTS ee_toc_revision (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            bookId INTEGER NOT NULL,
+            operationId TEXT NOT NULL,
+            nodes TEXT NOT NULL,
+            createTime TEXT NOT NULL,
+            UNIQUE (operationId, bookId)
+        );
+    ",
+    )
+}
+
+// v12: trigram 无法检索不足 3 个字符的词（如两个字的中文词），另建短词索引。
+// 分词（相邻两个字符一组）由应用生成后写入，表在这里只建空表，已有章节在启动时由应用补建索引
+fn migrate_v12_chapter_bigram(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        CREATE VIRTUAL TABLE IF NOT EXISTS ee_chapter_bigram USING fts5(
+            label,
+            content,
+            content = '',
+            contentless_delete = 1,
+            tokenize = 'unicode61 remove_diacritics 0'
+        );
+
+        CREATE TRIGGER IF NOT EXISTS ee_chapter_bigram_delete AFTER DELETE ON ee_chapter BEGIN
+            DELETE FROM ee_chapter_bigram WHERE rowid = old.id;
+        END;
+    ",
+    )
+}
+
+// v13: trigram 索引的触发器写入的是原始 HTML，标签与属性中的文字也能被检索到。
+// 去掉触发器，两个索引都由应用写入去掉标签后的正文，已有章节在启动时由应用重建索引
+fn migrate_v13_plain_text_fts(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch(
+        "
+        DROP TRIGGER IF EXISTS ee_chapter_fts_insert;
+        DROP TRIGGER IF EXISTS ee_chapter_fts_update;
+    ",
+    )
+}
+
+// v14: 段落数改为按块级元素统计，旧缓存按行统计，清空后在下次统计时重新计算
+fn migrate_v14_reset_chapter_stats(tx: &Transaction) -> Result<(), rusqlite::Error> {
+    tx.execute_batch("DELETE FROM ee_chapter_stats;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn failed_migration_leaves_the_database_unchanged() {
+        let mut db = Connection::open_in_memory().unwrap();
+        // 旧库中已有 publisher 列，v6 添加同名列时失败
+        db.execute_batch(
+            "CREATE TABLE ee_book (id INTEGER PRIMARY KEY AUTOINCREMENT, title TEXT, author TEXT, \
+                 description TEXT, toc TEXT, isDel INTEGER, createTime TEXT, updateTime TEXT, \
+                 publisher TEXT);
+             INSERT INTO ee_book (title, toc, isDel) VALUES ('书', '[{\"label\":\"一\",\"href\":1}]', 0);",
+        )
+        .unwrap();
+
+        let err = run_migrations(&mut db, &std::env::temp_dir()).unwrap_err();
+        assert_eq!(err.version, 6);
+        let backup_path = err.backup_path.expect("已有数据的库应先备份");
+        fs::remove_file(backup_path).unwrap();
+
+        // v1 到 v5 的修改一起回滚
+        assert_eq!(get_user_version(&db).unwrap(), 0);
+        let toc_table: i64 = db
+            .query_row(
+                "SELECT COUNT(*) FROM sqlite_master WHERE name = 'ee_toc_node'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(toc_table, 0);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::error::{AppError, AppResult};
+use crate::migration::MigrationError;
+use crate::setup::{AppState, MigrationStatus};
+use rusqlite::{Connection, ErrorCode, OpenFlags};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+use tauri::{AppHandle, Manager};
+
+// 只读连接数量。WAL 模式下读连接不会被写事务阻塞
+const READER_COUNT: usize = 4;
+// 连接遇到锁时的等待时间
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+// 数据库连接池：一个写连接，多个只读连接
+pub struct DbPool {
+    // 数据库文件路径，内存库为 None
+    path: Option<PathBuf>,
+    writer: Mutex<Connection>,
+    readers: Vec<Mutex<Connection>>,
+    next_reader: AtomicUsize,
+    // 替换数据库文件失败且无法恢复原文件时置位，之后的读写都返回错误，避免在内存库上继续操作
+    failed: AtomicBool,
+}
+
+// 设置 SQLCipher 密钥并确认能读取数据库。未加密的库 key 为 None
+// 密钥错误或加密库未提供密钥时，读取表结构会返回 NotADatabase
+fn apply_key(db: &Connection, key: Option<&str>) -> AppResult<()> {
+    if let Some(key) = key {
+        db.pragma_update(None, "key", key)?;
+    }
+    match db.query_row("SELECT COUNT(*) FROM sqlite_schema", [], |_| Ok(())) {
+        Err(rusqlite::Error::SqliteFailure(failure, _))
+            if failure.code == ErrorCode::NotADatabase =>
+        {
+            Err(AppError::Encrypted("密码错误或数据库已加密".to_string()))
+        }
+        result => Ok(result?),
+    }
+}
+
+// 打开写连接
+pub fn open_writer(path: &Path, key: Option<&str>) -> AppResult<Connection> {
+    let db = Connection::open(path)?;
+    apply_key(&db, key)?;
+    Ok(db)
+}
+
+pub fn open_reader(path: &Path, key: Option<&str>) -> AppResult<Connection> {
+    let db = Connection::open_with_flags(
+        path,
+        OpenFlags::SQLITE_OPEN_READ_ONLY
+            | OpenFlags::SQLITE_OPEN_NO_MUTEX
+            | OpenFlags::SQLITE_OPEN_URI,
+    )?;
+    apply_key(&db, key)?;
+    db.busy_timeout(BUSY_TIMEOUT)?;
+    db.pragma_update(None, "query_only", true)?;
+    Ok(db)
+}
+
+impl DbPool {
+    // writer 必须已完成迁移并处于 WAL 模式，读连接在此之后打开以看到最新的表结构
+    pub fn open(path: &Path, writer: Connection, key: Option<&str>) -> AppResult<Self> {
+        writer.busy_timeout(BUSY_TIMEOUT)?;
+        let readers = (0..READER_COUNT)
+            .map(|_| open_reader(path, key).map(Mutex::new))
+            .collect::<AppResult<Vec<_>>>()?;
+        Ok(Self {
+            path: Some(path.to_path_buf()),
+            writer: Mutex::new(writer),
+            readers,
+            next_reader: AtomicUsize::new(0),
+            failed: AtomicBool::new(false),
+        })
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    fn check_failed(&self) -> AppResult<()> {
+        if self.failed.load(Ordering::Acquire) {
+            return Err(AppError::Database(
+                "数据库文件替换失败，请重新启动应用".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn writer(&self) -> AppResult<MutexGuard<'_, Connection>> {
+        self.check_failed()?;
+        Ok(self.writer.lock()?)
+    }
+
+    // 优先取空闲的读连接，全部繁忙时按轮询顺序等待其中一个
+    pub fn reader(&self) -> AppResult<MutexGuard<'_, Connection>> {
+        self.check_failed()?;
+        if self.readers.is_empty() {
+            return self.writer();
+        }
+        let start = self.next_reader.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..self.readers.len() {
+            let reader = &self.readers[(start + offset) % self.readers.len()];
+            if let Ok(guard) = reader.try_lock() {
+                return Ok(guard);
+            }
+        }
+        Ok(self.readers[start % self.readers.len()].lock()?)
+    }
+
+    // 关闭所有连接（替换为内存库），以便删除数据库文件
+    pub fn close(&self) -> AppResult<()> {
+        for reader in &self.readers {
+            *reader.lock()? = Connection::open_in_memory()?;
+        }
+        *self.writer()? = Connection::open_in_memory()?;
+        Ok(())
+    }
+
+    // 用 build 生成的新文件替换数据库文件，并以新密钥重新打开所有连接
+    // build 在持有全部连接时执行，期间没有其他读写；第二个参数为新文件应写入的路径
+    // 新文件无法替换或打开时恢复原文件并以 current 重新打开；恢复也失败时连接池进入失效状态
+    pub fn replace_file<F>(
+        &self,
+        current: Option<&str>,
+        key: Option<&str>,
+        build: F,
+    ) -> AppResult<()>
+    where
+        F: FnOnce(&Connection, &Path) -> AppResult<()>,
+    {
+        let path = self
+            .path
+            .as_deref()
+            .ok_or_else(|| AppError::InvalidInput("内存数据库无法替换".to_string()))?;
+        let tmp = path.with_extension("db.tmp");
+
+        let mut writer = self.writer()?;
+        let mut readers = self
+            .readers
+            .iter()
+            .map(|reader| reader.lock())
+            .collect::<Result<Vec<_>, _>>()?;
+
+        remove_if_exists(&tmp)?;
+        if let Err(err) = build(&writer, &tmp) {
+            let _ = fs::remove_file(&tmp);
+            return Err(err);
+        }
+
+        if let Err(err) = swap_file(path, &tmp, key, &mut writer, &mut readers) {
+            let _ = fs::remove_file(&tmp);
+            if let Err(restore_err) = open_all(path, current, &mut writer, &mut readers) {
+                eprintln!("[DB] 无法重新打开原数据库: {}", restore_err);
+                self.failed.store(true, Ordering::Release);
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+// 关闭所有连接（替换为内存库），旧库的 WAL 会在最后一个连接关闭时写回并删除
+fn close_all(writer: &mut Connection, readers: &mut [MutexGuard<'_, Connection>]) -> AppResult<()> {
+    for reader in readers.iter_mut() {
+        **reader = Connection::open_in_memory()?;
+    }
+    *writer = Connection::open_in_memory()?;
+    Ok(())
+}
+
+fn open_all(
+    path: &Path,
+    key: Option<&str>,
+    writer: &mut Connection,
+    readers: &mut [MutexGuard<'_, Connection>],
+) -> AppResult<()> {
+    let db = open_writer(path, key)?;
+    db.pragma_update(None, "journal_mode", "WAL")?;
+    db.busy_timeout(BUSY_TIMEOUT)?;
+    *writer = db;
+    for reader in readers.iter_mut() {
+        **reader = open_reader(path, key)?;
+    }
+    Ok(())
+}
+
+fn remove_side_files(path: &Path) -> io::Result<()> {
+    for suffix in ["-wal", "-shm"] {
+        let mut side = path.as_os_str().to_owned();
+        side.push(suffix);
+        remove_if_exists(Path::new(&side))?;
+    }
+    Ok(())
+}
+
+// 用 tmp 替换 path 并以 key 打开所有连接。原文件先改名保留，新文件无法打开时改回
+fn swap_file(
+    path: &Path,
+    tmp: &Path,
+    key: Option<&str>,
+    writer: &mut Connection,
+    readers: &mut [MutexGuard<'_, Connection>],
+) -> AppResult<()> {
+    close_all(writer, readers)?;
+    remove_side_files(path)?;
+    let old = path.with_extension("db.old");
+    remove_if_exists(&old)?;
+    fs::rename(path, &old)?;
+    if let Err(err) = fs::rename(tmp, path) {
+        fs::rename(&old, path)?;
+        return Err(err.into());
+    }
+    if let Err(err) = open_all(path, key, writer, readers) {
+        close_all(writer, readers)?;
+        remove_side_files(path)?;
+        fs::rename(&old, path)?;
+        return Err(err);
+    }
+    let _ = fs::remove_file(&old);
+    Ok(())
+}
+
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err),
+        _ => Ok(()),
+    }
+}
+
+fn migration_failure(app_handle: &AppHandle) -> Option<MigrationError> {
+    app_handle
+        .try_state::<MigrationStatus>()
+        .and_then(|status| status.0.lock().ok().and_then(|err| err.clone()))
+}
+
+// 在阻塞线程池中执行数据库操作，避免占用异步运行时和主线程
+pub async fn run_blocking<T, F>(app_handle: AppHandle, f: F) -> AppResult<T>
+where
+    T: Send + 'static,
+    F: FnOnce(&AppState) -> AppResult<T> + Send + 'static,
+{
+    tauri::async_runtime::spawn_blocking(move || match app_handle.try_state::<AppState>() {
+        Some(state) => f(&state),
+        // 加密库在解锁前、迁移失败时都不会注册 AppState
+        None => Err(match migration_failure(&app_handle) {
+            Some(err) => AppError::Database(err.to_string()),
+            None => AppError::Encrypted("数据库尚未解锁".to_string()),
+        }),
+    })
+    .await
+    .map_err(|e| AppError::Internal(e.to_string()))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replace_file_restores_the_original_when_the_new_file_cannot_open() {
+        let dir = std::env::temp_dir().join(format!("myebook-pool-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("books.db");
+        let writer = open_writer(&path, None).unwrap();
+        writer.pragma_update(None, "journal_mode", "WAL").unwrap();
+        writer
+            .execute_batch("CREATE TABLE t (v TEXT); INSERT INTO t VALUES ('书');")
+            .unwrap();
+        let pool = DbPool::open(&path, writer, None).unwrap();
+
+        let result = pool.replace_file(None, None, |_, target| {
+            Ok(fs::write(target, b"not a database")?)
+        });
+        assert!(result.is_err());
+        let read = |db: &Connection| -> String {
+            db.query_row("SELECT v FROM t", [], |row| row.get(0))
+                .unwrap()
+        };
+        assert_eq!(read(&pool.writer().unwrap()), "书");
+        assert_eq!(read(&pool.reader().unwrap()), "书");
+        drop(pool);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,543 @@
+use crate::database::{
+    get_db_connection, get_read_connection, query_chapters_with, reading_order, update_chapters_tx,
+    Chapter, ChapterQuery, ChapterUpdate, DbResponse,
+};
+use crate::error::{AppError, AppResult};
+use crate::pool::run_blocking;
+use crate::revision::RevisionContext;
+use regex::{Captures, Regex, RegexBuilder};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tauri::{command, AppHandle};
+use unicode_segmentation::UnicodeSegmentation;
+
+// 预览中匹配前后显示的字符数
+const CONTEXT_CHARS: usize = 30;
+// 预览默认最多返回的匹配数，超出部分只计数
+const MAX_PREVIEW_MATCHES: usize = 500;
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct FindReplaceOptions {
+    // pattern 为正则表达式，replacement 中可使用 $1 等分组引用；否则按普通文本查找与替换。
+    // 只在正文文本中查找，不匹配标签、属性与字符实体
+    pub regex: bool,
+    pub case_insensitive: bool,
+    // 只匹配完整的单词，按 Unicode 分词规则确定边界（中文每个字单独成词）
+    pub whole_word: bool,
+    // 只处理这些章节
+    pub chapter_ids: Option<Vec<i64>>,
+    // 按阅读顺序限定起止章节（含两端），值为章节 id
+    pub from_chapter: Option<i64>,
+    pub to_chapter: Option<i64>,
+    // 只处理标题包含该文本的章节
+    pub label_contains: Option<String>,
+    // 只预览匹配结果，不写入数据库
+    pub dry_run: bool,
+    // 预览最多返回的匹配数
+    pub max_matches: Option<usize>,
+    // 写入时的操作 id，可通过 undo_operation 撤销整个替换
+    pub operation_id: Option<String>,
+}
+
+// 预览中的一处匹配
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReplaceMatch {
+    pub chapter_id: i64,
+    pub label: String,
+    pub matched: String,
+    // 展开分组引用后的替换文本
+    pub replacement: String,
+    // 匹配前后的上下文
+    pub before: String,
+    pub after: String,
+}
+
+#[derive(Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FindReplaceResult {
+    pub dry_run: bool,
+    // 查找范围内的章节数
+    pub chapters: usize,
+    // 有匹配的章节数与匹配总数
+    pub matched_chapters: usize,
+    pub matches: usize,
+    // 预览的匹配，超过 max_matches 时 truncated 为 true
+    pub preview: Vec<ReplaceMatch>,
+    pub truncated: bool,
+    // 实际写入的章节数，预览时为 0
+    pub changed: usize,
+    pub operation_id: Option<String>,
+}
+
+fn build_regex(pattern: &str, options: &FindReplaceOptions) -> AppResult<Regex> {
+    if pattern.is_empty() {
+        return Err(AppError::InvalidInput("查找内容不能为空".to_string()));
+    }
+    let source = if options.regex {
+        pattern.to_string()
+    } else {
+        regex::escape(pattern)
+    };
+    let regex = RegexBuilder::new(&source)
+        .case_insensitive(options.case_insensitive)
+        .multi_line(true)
+        .build()
+        .map_err(|err| AppError::from(err).context("正则表达式错误"))?;
+    // 能匹配空字符串的表达式会在每个位置插入替换文本
+    if regex.is_match("") {
+        return Err(AppError::InvalidInput(
+            "正则表达式不能匹配空字符串".to_string(),
+        ));
+    }
+    Ok(regex)
+}
+
+fn load_chapters(
+    db: &Connection,
+    book_id: i64,
+    options: &FindReplaceOptions,
+) -> AppResult<Vec<Chapter>> {
+    let query = ChapterQuery {
+        book_id: Some(book_id),
+        ids: options.chapter_ids.clone(),
+        label_contains: options.label_contains.clone(),
+        ..Default::default()
+    };
+    // 章节按阅读顺序处理，起止章节也按其在阅读顺序中的位置确定范围
+    let positions: HashMap<i64, usize> = reading_order(db, book_id)?
+        .into_iter()
+        .enumerate()
+        .map(|(position, id)| (id, position))
+        .collect();
+    let position = |id: Option<i64>, default: usize| match id {
+        Some(id) => positions
+            .get(&id)
+            .copied()
+            .ok_or_else(|| AppError::NotFound(format!("章节 {} 不存在", id))),
+        None => Ok(default),
+    };
+    let from = position(options.from_chapter, 0)?;
+    let to = position(options.to_chapter, usize::MAX)?;
+    let range = from.min(to)..=from.max(to);
+
+    let mut chapters: Vec<Chapter> = query_chapters_with(db, &query)?
+        .into_iter()
+        .filter(|chapter| range.contains(&positions[&chapter.id]))
+        .collect();
+    chapters.sort_by_key(|chapter| positions[&chapter.id]);
+    Ok(chapters)
+}
+
+// 正文中标签与字符实体之外的文本片段（字节范围）
+fn text_ranges(content: &str) -> Vec<(usize, usize)> {
+    let bytes = content.as_bytes();
+    let mut ranges = Vec::new();
+    let mut start = 0;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let skip_to = match bytes[pos] {
+            b'<' => Some(
+                content[pos..]
+                    .find('>')
+                    .map_or(bytes.len(), |end| pos + end + 1),
+            ),
+            b'&' => {
+                let name_len = bytes[pos + 1..]
+                    .iter()
+                    .take_while(|b| b.is_ascii_alphanumeric() || **b == b'#')
+                    .count();
+                (name_len > 0 && bytes.get(pos + 1 + name_len) == Some(&b';'))
+                    .then_some(pos + name_len + 2)
+            }
+            _ => None,
+        };
+        match skip_to {
+            Some(end) => {
+                if start < pos {
+                    ranges.push((start, pos));
+                }
+                start = end;
+                pos = end;
+            }
+            None => pos += 1,
+        }
+    }
+    if start < bytes.len() {
+        ranges.push((start, bytes.len()));
+    }
+    ranges
+}
+
+// 查找正文中的所有匹配。只在文本片段中查找，标签、属性与字符实体不会被改写；
+// 整词匹配时起止位置都必须是分词边界，不是边界时从下一个字符重新查找
+fn find_captures<'h>(content: &'h str, regex: &Regex, whole_word: bool) -> Vec<Captures<'h>> {
+    let mut found = Vec::new();
+    for (start, end) in text_ranges(content) {
+        let bounds: Option<HashSet<usize>> = whole_word.then(|| {
+            content[start..end]
+                .split_word_bound_indices()
+                .map(|(index, _)| start + index)
+                .chain([end])
+                .collect()
+        });
+        // 截断到片段末尾，匹配不会跨过标签；保留前文以便 ^ 等断言按原位置判断
+        let haystack = &content[..end];
+        let mut pos = start;
+        while let Some(caps) = regex.captures_at(haystack, pos) {
+            let matched = caps.get(0).expect("group 0 always participates");
+            let on_bounds = match &bounds {
+                Some(bounds) => {
+                    bounds.contains(&matched.start()) && bounds.contains(&matched.end())
+                }
+                None => true,
+            };
+            let accepted = matched.start() < matched.end() && on_bounds;
+            if accepted {
+                pos = matched.end();
+                found.push(caps);
+            } else {
+                let next = content[matched.start()..end].chars().next();
+                match next {
+                    Some(c) => pos = matched.start() + c.len_utf8(),
+                    None => break,
+                }
+            }
+        }
+    }
+    found
+}
+
+// 展开替换文本，普通文本替换时 $1 等按字面写入
+fn expand_replacement(caps: &Captures, replacement: &str, literal: bool) -> String {
+    if literal {
+        return replacement.to_string();
+    }
+    let mut expanded = String::new();
+    caps.expand(replacement, &mut expanded);
+    expanded
+}
+
+fn replace_content(
+    content: &str,
+    regex: &Regex,
+    replacement: &str,
+    options: &FindReplaceOptions,
+) -> String {
+    let mut replaced = String::with_capacity(content.len());
+    let mut last = 0;
+    for caps in find_captures(content, regex, options.whole_word) {
+        let found = caps.get(0).expect("group 0 always participates");
+        replaced.push_str(&content[last..found.start()]);
+        replaced.push_str(&expand_replacement(&caps, replacement, !options.regex));
+        last = found.end();
+    }
+    replaced.push_str(&content[last..]);
+    replaced
+}
+
+// 查找所有章节，预览时收集匹配及上下文，否则生成需要写入的章节
+fn find_matches(
+    chapters: Vec<Chapter>,
+    regex: &Regex,
+    replacement: &str,
+    options: &FindReplaceOptions,
+) -> (FindReplaceResult, Vec<ChapterUpdate>) {
+    let max_matches = options.max_matches.unwrap_or(MAX_PREVIEW_MATCHES);
+    let mut result = FindReplaceResult {
+        dry_run: options.dry_run,
+        chapters: chapters.len(),
+        ..Default::default()
+    };
+    let mut updates = Vec::new();
+
+    for chapter in chapters {
+        let mut count = 0;
+        for caps in find_captures(&chapter.content, regex, options.whole_word) {
+            count += 1;
+            if !options.dry_run {
+                continue;
+            }
+            if result.preview.len() >= max_matches {
+                result.truncated = true;
+                continue;
+            }
+            let found = caps.get(0).expect("group 0 always participates");
+            let before = &chapter.content[..found.start()];
+            let skip = before.chars().count().saturating_sub(CONTEXT_CHARS);
+            result.preview.push(ReplaceMatch {
+                chapter_id: chapter.id,
+                label: chapter.label.clone(),
+                matched: found.as_str().to_string(),
+                replacement: expand_replacement(&caps, replacement, !options.regex),
+                before: before.chars().skip(skip).collect(),
+                after: chapter.content[found.end()..]
+                    .chars()
+                    .take(CONTEXT_CHARS)
+                    .collect(),
+            });
+        }
+        if count == 0 {
+            continue;
+        }
+        result.matched_chapters += 1;
+        result.matches += count;
+
+        if !options.dry_run {
+            let content = replace_content(&chapter.content, regex, replacement, options);
+            if content != chapter.content {
+                updates.push(ChapterUpdate {
+                    id: chapter.id,
+                    label: chapter.label,
+                    content: Some(content),
+                });
+            }
+        }
+    }
+
+    (result, updates)
+}
+
+// 在整本书中查找并替换正文，dry_run 时只返回匹配预览
+// 写入时所有章节在同一事务中通过批量更新写入，并保存历史版本，可通过 undo_operation 撤销
+#[command]
+pub async fn find_replace(
+    book_id: i64,
+    pattern: String,
+    replacement: String,
+    options: Option<FindReplaceOptions>,
+    app_handle: AppHandle,
+) -> DbResponse<FindReplaceResult> {
+    run_blocking(app_handle, move |state| {
+        let options = options.unwrap_or_default();
+        let regex = build_regex(&pattern, &options)?;
+
+        if options.dry_run {
+            let db = get_read_connection(state)?;
+            let chapters = load_chapters(&db, book_id, &options)?;
+            return Ok(find_matches(chapters, &regex, &replacement, &options).0);
+        }
+
+        let mut db = get_db_connection(state)?;
+        let tx = db.transaction()?;
+        let chapters = load_chapters(&tx, book_id, &options)?;
+        let (mut result, updates) = find_matches(chapters, &regex, &replacement, &options);
+
+        let ctx = RevisionContext::new("replace", options.operation_id.clone());
+        let results = update_chapters_tx(&tx, &updates, &ctx)?;
+        tx.commit()?;
+
+        result.changed = results.iter().filter(|r| r.changed).count();
+        result.operation_id = Some(ctx.operation_id);
+        Ok(result)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::replace_toc_from_json;
+    use crate::testutil;
+
+    fn options(regex: bool, whole_word: bool) -> FindReplaceOptions {
+        FindReplaceOptions {
+            regex,
+            whole_word,
+            ..Default::default()
+        }
+    }
+
+    fn replace(
+        content: &str,
+        pattern: &str,
+        replacement: &str,
+        options: &FindReplaceOptions,
+    ) -> String {
+        let regex = build_regex(pattern, options).unwrap();
+        replace_content(content, &regex, replacement, options)
+    }
+
+    // 三个章节，目录中的阅读顺序为 3、1、2
+    fn open_book() -> Connection {
+        let mut db =
+            testutil::open_book(&[("一", "猫 cat"), ("二", "catalog"), ("三", "Cat 猫猫")]);
+        let tx = db.transaction().unwrap();
+        replace_toc_from_json(
+            &tx,
+            1,
+            r#"[{"label":"三","href":3},{"label":"一","href":1},{"label":"二","href":2}]"#,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        db
+    }
+
+    fn chapter_ids(chapters: &[Chapter]) -> Vec<i64> {
+        chapters.iter().map(|chapter| chapter.id).collect()
+    }
+
+    #[test]
+    fn literal_pattern_and_replacement() {
+        let literal = options(false, false);
+        // 普通文本中的正则元字符按字面匹配，替换文本中的 $1 也不展开
+        assert_eq!(replace("a.b axb", "a.b", "$1", &literal), "$1 axb");
+        assert_eq!(replace("1+1=2", "1+1", "二", &literal), "二=2");
+    }
+
+    #[test]
+    fn regex_pattern_expands_groups() {
+        let regex = options(true, false);
+        assert_eq!(
+            replace("第12章 第3章", r"第(\d+)章", "Chapter $1", &regex),
+            "Chapter 12 Chapter 3"
+        );
+        assert!(build_regex("(", &regex).is_err());
+    }
+
+    #[test]
+    fn whole_word_and_case_insensitive() {
+        let whole_word = options(false, true);
+        assert_eq!(
+            replace("cat catalog cat", "cat", "dog", &whole_word),
+            "dog catalog dog"
+        );
+        // 正则中的选择分支整体受单词边界约束
+        let regex = options(true, true);
+        assert_eq!(replace("a ab b", "a|b", "x", &regex), "x ab x");
+
+        let insensitive = FindReplaceOptions {
+            case_insensitive: true,
+            ..options(false, true)
+        };
+        assert_eq!(
+            replace("Cat CAT cats", "cat", "dog", &insensitive),
+            "dog dog cats"
+        );
+    }
+
+    #[test]
+    fn whole_word_uses_unicode_word_bounds() {
+        let whole_word = options(false, true);
+        // 中文每个字单独成词，\b 在中文之间没有边界
+        assert_eq!(replace("黑猫跑了", "猫", "狗", &whole_word), "黑狗跑了");
+        // 以标点开头或结尾的查找内容
+        assert_eq!(replace("c++ 和 c++", "c++", "C", &whole_word), "C 和 C");
+        assert_eq!(replace("abc++", "c++", "C", &whole_word), "abc++");
+    }
+
+    #[test]
+    fn markup_is_never_replaced() {
+        let literal = options(false, false);
+        assert_eq!(
+            replace(r#"<p class="p">p&amp;</p>"#, "p", "q", &literal),
+            r#"<p class="p">q&amp;</p>"#
+        );
+        assert_eq!(
+            replace(r#"<p class="x">class</p>"#, "class", "c", &literal),
+            r#"<p class="x">c</p>"#
+        );
+        let regex = options(true, false);
+        assert_eq!(replace("<p>甲</p>", r"<p>(.)", "[$1]", &regex), "<p>甲</p>");
+    }
+
+    #[test]
+    fn whole_word_only_matches_text() {
+        let whole_word = options(false, true);
+        assert_eq!(
+            replace(r#"<p class="cat">cat</p>"#, "cat", "dog", &whole_word),
+            r#"<p class="cat">dog</p>"#
+        );
+        assert_eq!(replace("&amp; amp", "amp", "x", &whole_word), "&amp; x");
+        // 匹配不跨过标签
+        let regex = options(true, true);
+        assert_eq!(replace("<b>a</b> b", "a.*b", "x", &regex), "<b>a</b> b");
+    }
+
+    #[test]
+    fn rejects_empty_matches() {
+        assert!(build_regex("", &options(false, false)).is_err());
+        assert!(build_regex("a*", &options(true, false)).is_err());
+        assert!(build_regex("^", &options(true, false)).is_err());
+    }
+
+    #[test]
+    fn load_chapters_in_reading_order() {
+        let db = open_book();
+        let all = load_chapters(&db, 1, &FindReplaceOptions::default()).unwrap();
+        assert_eq!(chapter_ids(&all), vec![3, 1, 2]);
+
+        // 起止章节按阅读顺序确定范围，顺序颠倒时自动交换
+        let range = FindReplaceOptions {
+            from_chapter: Some(1),
+            to_chapter: Some(3),
+            ..Default::default()
+        };
+        assert_eq!(
+            chapter_ids(&load_chapters(&db, 1, &range).unwrap()),
+            vec![3, 1]
+        );
+
+        let from = FindReplaceOptions {
+            from_chapter: Some(1),
+            ..Default::default()
+        };
+        assert_eq!(
+            chapter_ids(&load_chapters(&db, 1, &from).unwrap()),
+            vec![1, 2]
+        );
+
+        let missing = FindReplaceOptions {
+            to_chapter: Some(99),
+            ..Default::default()
+        };
+        assert!(matches!(
+            load_chapters(&db, 1, &missing),
+            Err(AppError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn find_matches_preview_and_updates() {
+        let db = open_book();
+        let whole_word = FindReplaceOptions {
+            case_insensitive: true,
+            dry_run: true,
+            max_matches: Some(1),
+            ..options(false, true)
+        };
+        let regex = build_regex("cat", &whole_word).unwrap();
+        let chapters = load_chapters(&db, 1, &whole_word).unwrap();
+        let (result, updates) = find_matches(chapters, &regex, "dog", &whole_word);
+        assert_eq!(result.chapters, 3);
+        assert_eq!(result.matched_chapters, 2);
+        assert_eq!(result.matches, 2);
+        assert!(result.truncated);
+        assert_eq!(result.preview[0].chapter_id, 3);
+        assert_eq!(result.preview[0].matched, "Cat");
+        assert_eq!(result.preview[0].after, " 猫猫");
+        assert!(updates.is_empty());
+
+        let write = FindReplaceOptions {
+            dry_run: false,
+            ..whole_word
+        };
+        let chapters = load_chapters(&db, 1, &write).unwrap();
+        let (result, updates) = find_matches(chapters, &regex, "dog", &write);
+        assert!(result.preview.is_empty());
+        let updates: Vec<(i64, Option<String>)> = updates
+            .into_iter()
+            .map(|update| (update.id, update.content))
+            .collect();
+        assert_eq!(
+            updates,
+            vec![
+                (3, Some("dog 猫猫".to_string())),
+                (1, Some("猫 dog".to_string()))
+            ]
+        );
+    }
+}
@@ -0,0 +1,587 @@
+use crate::database::{
+    get_current_time_string, get_db_connection, insert_chapter, load_toc_tree, query_chapters_with,
+    sync_toc_column, toc_chapter_order, toc_sibling_ids, update_chapters_tx, write_toc_order,
+    Chapter, ChapterQuery, ChapterUpdate, DbResponse,
+};
+use crate::error::{AppError, AppResult};
+use crate::pool::run_blocking;
+use crate::revision::{
+    record_created_chapter, snapshot_deleted_chapter, snapshot_toc, RevisionContext,
+};
+use regex::{Regex, RegexBuilder};
+use rusqlite::{params, OptionalExtension, Transaction};
+use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
+use tauri::{command, AppHandle};
+
+// 拆分位置
+#[derive(Debug, Clone, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum SplitAt {
+    // 按字符位置拆分，位置落在 HTML 标签或字符实体中间时移到其开头
+    Offsets { offsets: Vec<usize> },
+    // 每个匹配正则的行作为新章节的开头（如 第.+章），该行去掉标签后的文本作为章名
+    Pattern { pattern: String },
+    // 每个章节不超过 max_chars 个字符，只在行边界拆分，单行过长时不截断
+    MaxLength { max_chars: usize },
+}
+
+// 拆分后的一段，start 为在原正文中的字节位置
+struct Piece {
+    start: usize,
+    label: Option<String>,
+}
+
+// 每行开头的字节位置
+fn line_starts(content: &str) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(content.match_indices('\n').map(|(index, _)| index + 1))
+        .filter(|start| *start < content.len())
+        .collect()
+}
+
+fn html_tag_pattern() -> AppResult<Regex> {
+    Ok(Regex::new(r"<[^>]*>")?)
+}
+
+// 拆分位置落在 HTML 标签（<...>）或字符实体（&...;）中间时，移到标签或实体的开头
+fn cut_position(content: &str, byte: usize) -> usize {
+    let before = &content[..byte];
+    if let Some(open) = before.rfind('<') {
+        if !before[open..].contains('>') {
+            return open;
+        }
+    }
+    if let Some(amp) = before.rfind('&') {
+        let name = &before[amp + 1..];
+        let rest = content[byte..].trim_start_matches(|c: char| c.is_ascii_alphanumeric());
+        if name.chars().all(|c| c.is_ascii_alphanumeric() || c == '#') && rest.starts_with(';') {
+            return amp;
+        }
+    }
+    byte
+}
+
+// 计算拆分位置，不包含正文开头
+fn plan_split(content: &str, at: &SplitAt) -> AppResult<Vec<Piece>> {
+    let starts = line_starts(content);
+    let mut pieces = Vec::new();
+    match at {
+        SplitAt::Offsets { offsets } => {
+            let mut cuts = offsets
+                .iter()
+                .filter_map(|offset| content.char_indices().nth(*offset))
+                .map(|(byte, _)| cut_position(content, byte))
+                .filter(|start| *start > 0)
+                .collect::<Vec<_>>();
+            cuts.sort_unstable();
+            cuts.dedup();
+            pieces.extend(cuts.into_iter().map(|start| Piece { start, label: None }));
+        }
+        SplitAt::Pattern { pattern } => {
+            let regex = RegexBuilder::new(pattern)
+                .build()
+                .map_err(|err| AppError::from(err).context("正则表达式错误"))?;
+            let tag_pattern = html_tag_pattern()?;
+            for (index, start) in starts.iter().enumerate().skip(1) {
+                let end = starts.get(index + 1).copied().unwrap_or(content.len());
+                let line = content[*start..end].trim_end_matches(['\r', '\n']);
+                if regex.is_match(line) {
+                    let label = tag_pattern.replace_all(line, "").trim().to_string();
+                    pieces.push(Piece {
+                        start: *start,
+                        label: Some(label).filter(|label| !label.is_empty()),
+                    });
+                }
+            }
+        }
+        SplitAt::MaxLength { max_chars } => {
+            if *max_chars == 0 {
+                return Err(AppError::InvalidInput("最大字数必须大于 0".to_string()));
+            }
+            let mut count = 0;
+            for (index, start) in starts.iter().enumerate() {
+                let end = starts.get(index + 1).copied().unwrap_or(content.len());
+                let chars = content[*start..end].chars().count();
+                if count > 0 && count + chars > *max_chars {
+                    pieces.push(Piece {
+                        start: *start,
+                        label: None,
+                    });
+                    count = 0;
+                }
+                count += chars;
+            }
+        }
+    }
+    if pieces.is_empty() {
+        return Err(AppError::InvalidInput("没有找到拆分位置".to_string()));
+    }
+    Ok(pieces)
+}
+
+fn load_chapters(tx: &Transaction, ids: &[i64]) -> AppResult<Vec<Chapter>> {
+    let query = ChapterQuery {
+        ids: Some(ids.to_vec()),
+        ..Default::default()
+    };
+    let chapters = query_chapters_with(tx, &query)?;
+    if let Some(id) = ids
+        .iter()
+        .find(|id| !chapters.iter().any(|chapter| chapter.id == **id))
+    {
+        return Err(AppError::NotFound(format!("章节 {} 不存在", id)));
+    }
+    Ok(chapters)
+}
+
+// 依次生成与前端新建章节相同格式的 href，从当前毫秒时间戳开始递增，同一批次内不会重复
+pub fn chapter_hrefs() -> impl Iterator<Item = String> {
+    let millis = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|dur| dur.as_millis())
+        .unwrap_or(0);
+    (millis..).map(|value| format!("OPS/chapter-{}", value))
+}
+
+// 目录中指向整个章节（不带锚点）的第一个节点
+fn chapter_toc_node(tx: &Transaction, chapter_id: i64) -> AppResult<Option<(i64, Option<i64>)>> {
+    Ok(tx
+        .query_row(
+            "SELECT id, parentId FROM ee_toc_node WHERE chapterId = ? \
+             ORDER BY anchor IS NOT NULL, sortIndex, id LIMIT 1",
+            params![chapter_id],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )
+        .optional()?)
+}
+
+fn touch_book(tx: &Transaction, book_id: i64) -> AppResult<()> {
+    sync_toc_column(tx, book_id)?;
+    tx.execute(
+        "UPDATE ee_book SET updateTime = ? WHERE id = ?",
+        params![get_current_time_string(), book_id],
+    )?;
+    Ok(())
+}
+
+fn split_chapter_tx(
+    tx: &Transaction,
+    id: i64,
+    at: &SplitAt,
+    ctx: &RevisionContext,
+) -> AppResult<Vec<i64>> {
+    let chapter = load_chapters(tx, &[id])?.remove(0);
+    let pieces = plan_split(&chapter.content, at)?;
+    snapshot_toc(tx, chapter.book_id, ctx)?;
+
+    let ends = pieces
+        .iter()
+        .map(|piece| piece.start)
+        .chain(std::iter::once(chapter.content.len()));
+    let mut texts = vec![&chapter.content[..pieces[0].start]];
+    texts.extend(
+        pieces
+            .iter()
+            .zip(ends.skip(1))
+            .map(|(piece, end)| &chapter.content[piece.start..end]),
+    );
+    let texts = texts
+        .into_iter()
+        .map(|text| text.trim_end_matches(['\r', '\n']).to_string())
+        .collect::<Vec<_>>();
+
+    update_chapters_tx(
+        tx,
+        &[ChapterUpdate {
+            id,
+            label: chapter.label.clone(),
+            content: Some(texts[0].clone()),
+        }],
+        ctx,
+    )?;
+
+    let current_time = get_current_time_string();
+    let mut new_chapters = Vec::with_capacity(pieces.len());
+    for ((index, piece), href) in pieces.iter().enumerate().zip(chapter_hrefs()) {
+        let label = piece
+            .label
+            .clone()
+            .unwrap_or_else(|| format!("{}（{}）", chapter.label, index + 2));
+        let new_id = insert_chapter(
+            tx,
+            chapter.book_id,
+            &label,
+            &href,
+            &texts[index + 1],
+            Some(&current_time),
+        )?;
+        record_created_chapter(tx, new_id, ctx)?;
+        new_chapters.push((new_id, label));
+    }
+
+    // 新章节的目录项紧跟在原章节之后，原章节不在目录中时追加到目录末尾
+    let (node_id, parent_id) = match chapter_toc_node(tx, id)? {
+        Some((node_id, parent_id)) => (Some(node_id), parent_id),
+        None => (None, None),
+    };
+    let mut siblings = toc_sibling_ids(tx, chapter.book_id, parent_id)?;
+    let index = node_id
+        .and_then(|node_id| siblings.iter().position(|s| *s == node_id))
+        .map(|position| position + 1)
+        .unwrap_or(siblings.len());
+    for (offset, (chapter_id, label)) in new_chapters.iter().enumerate() {
+        tx.execute(
+            "INSERT INTO ee_toc_node (bookId, parentId, sortIndex, chapterId, label) \
+             VALUES (?, ?, 0, ?, ?)",
+            params![chapter.book_id, parent_id, chapter_id, label],
+        )?;
+        siblings.insert(index + offset, tx.last_insert_rowid());
+    }
+    write_toc_order(tx, parent_id, &siblings)?;
+
+    // 带锚点的目录项指向锚点所在的新章节
+    let anchors: Vec<(i64, String)> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, anchor FROM ee_toc_node WHERE chapterId = ? AND anchor IS NOT NULL",
+        )?;
+        let rows = stmt.query_map(params![id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    for (node_id, anchor) in anchors {
+        let found = texts.iter().skip(1).position(|text| {
+            text.contains(&format!("id=\"{}\"", anchor))
+                || text.contains(&format!("id='{}'", anchor))
+        });
+        if let Some(position) = found {
+            tx.execute(
+                "UPDATE ee_toc_node SET chapterId = ? WHERE id = ?",
+                params![new_chapters[position].0, node_id],
+            )?;
+        }
+    }
+
+    touch_book(tx, chapter.book_id)?;
+    Ok(std::iter::once(id)
+        .chain(new_chapters.iter().map(|(chapter_id, _)| *chapter_id))
+        .collect())
+}
+
+fn merge_chapters_tx(tx: &Transaction, ids: &[i64], ctx: &RevisionContext) -> AppResult<i64> {
+    if ids.len() < 2 {
+        return Err(AppError::InvalidInput("至少需要两个章节".to_string()));
+    }
+    if ids.iter().collect::<HashSet<_>>().len() != ids.len() {
+        return Err(AppError::InvalidInput("合并的章节重复".to_string()));
+    }
+    let mut chapters = load_chapters(tx, ids)?;
+    let book_id = chapters[0].book_id;
+    if chapters.iter().any(|chapter| chapter.book_id != book_id) {
+        return Err(AppError::InvalidInput(
+            "只能合并同一本书中的章节".to_string(),
+        ));
+    }
+
+    // 按目录顺序合并，不在目录中的章节按传入顺序排在最后
+    let mut order = Vec::new();
+    toc_chapter_order(
+        &load_toc_tree(tx, book_id)?,
+        &mut order,
+        &mut HashSet::new(),
+    );
+    let positions: HashMap<i64, usize> = order
+        .iter()
+        .enumerate()
+        .map(|(position, id)| (*id, position))
+        .collect();
+    let given: HashMap<i64, usize> = ids
+        .iter()
+        .enumerate()
+        .map(|(index, id)| (*id, index))
+        .collect();
+    chapters.sort_by_key(|chapter| {
+        (
+            positions.get(&chapter.id).copied().unwrap_or(usize::MAX),
+            given[&chapter.id],
+        )
+    });
+    let in_toc = chapters
+        .iter()
+        .filter_map(|chapter| positions.get(&chapter.id))
+        .collect::<Vec<_>>();
+    if in_toc.windows(2).any(|pair| *pair[1] != *pair[0] + 1) {
+        return Err(AppError::InvalidInput("只能合并相邻的章节".to_string()));
+    }
+
+    snapshot_toc(tx, book_id, ctx)?;
+    let first = &chapters[0];
+    let content = chapters
+        .iter()
+        .map(|chapter| chapter.content.trim_end_matches(['\r', '\n']))
+        .filter(|content| !content.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    update_chapters_tx(
+        tx,
+        &[ChapterUpdate {
+            id: first.id,
+            label: first.label.clone(),
+            content: Some(content),
+        }],
+        ctx,
+    )?;
+
+    for chapter in &chapters[1..] {
+        // 带锚点的目录项改为指向合并后的章节，锚点仍在正文中
+        tx.execute(
+            "UPDATE ee_toc_node SET chapterId = ? WHERE chapterId = ? AND anchor IS NOT NULL",
+            params![first.id, chapter.id],
+        )?;
+        // 指向整个章节的目录项删除，子目录上移到其位置
+        while let Some((node_id, parent_id)) = chapter_toc_node(tx, chapter.id)? {
+            let children = toc_sibling_ids(tx, book_id, Some(node_id))?;
+            let siblings = toc_sibling_ids(tx, book_id, parent_id)?
+                .into_iter()
+                .flat_map(|id| {
+                    if id == node_id {
+                        children.clone()
+                    } else {
+                        vec![id]
+                    }
+                })
+                .collect::<Vec<_>>();
+            tx.execute("DELETE FROM ee_toc_node WHERE id = ?", params![node_id])?;
+            write_toc_order(tx, parent_id, &siblings)?;
+        }
+        snapshot_deleted_chapter(tx, chapter.id, ctx)?;
+        tx.execute("DELETE FROM ee_chapter WHERE id = ?", params![chapter.id])?;
+    }
+
+    touch_book(tx, book_id)?;
+    Ok(first.id)
+}
+
+// 拆分章节：第一段保留在原章节中，其余各段作为新章节插入到原章节之后，并在目录中添加对应的目录项
+// 返回拆分后按顺序排列的章节 id（第一个为原章节）。可通过 undo_operation 撤销
+#[command]
+pub async fn split_chapter(
+    id: i64,
+    at: SplitAt,
+    operation_id: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<i64>> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+        let tx = db.transaction()?;
+        let ctx = RevisionContext::new("split", operation_id);
+        let ids = split_chapter_tx(&tx, id, &at, &ctx)?;
+        tx.commit()?;
+        Ok(ids)
+    })
+    .await
+    .into()
+}
+
+// 合并目录中相邻的章节：正文按目录顺序拼接到第一个章节，其余章节及其目录项删除
+// 返回合并后的章节 id。可通过 undo_operation 撤销
+#[command]
+pub async fn merge_chapters(
+    ids: Vec<i64>,
+    operation_id: Option<String>,
+    app_handle: AppHandle,
+) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+        let tx = db.transaction()?;
+        let ctx = RevisionContext::new("merge", operation_id);
+        let id = merge_chapters_tx(&tx, &ids, &ctx)?;
+        tx.commit()?;
+        Ok(id)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{replace_toc_from_json, toc_to_json};
+    use crate::revision::undo_operation_tx;
+    use crate::testutil;
+    use rusqlite::Connection;
+
+    const BODY: &str = "<h3>第一章 起</h3>\n<p>甲</p>\n<h3>第二章 承</h3>\n<p id=\"a2\">乙</p>\n\
+                        <h3>第三章 转</h3>\n<p>丙</p>";
+
+    // 一本书：卷一 > 第一章（含锚点 a2）、尾章
+    fn open_book() -> Connection {
+        let mut db = testutil::open_book(&[("第一章", BODY), ("尾", "<p>尾</p>")]);
+        let tx = db.transaction().unwrap();
+        replace_toc_from_json(
+            &tx,
+            1,
+            r#"[{"label":"卷一","subitems":[
+                {"label":"第一章","href":1,"subitems":[{"label":"锚","href":"1#a2"}]},
+                {"label":"尾","href":2}]}]"#,
+        )
+        .unwrap();
+        tx.commit().unwrap();
+        db
+    }
+
+    // 按阅读顺序列出目录项的 label 与 href
+    fn toc_entries(db: &Connection) -> Vec<(String, String)> {
+        fn walk(items: &[serde_json::Value], out: &mut Vec<(String, String)>) {
+            for item in items {
+                out.push((
+                    item["label"].as_str().unwrap_or_default().to_string(),
+                    item["href"].to_string(),
+                ));
+                if let Some(subitems) = item["subitems"].as_array() {
+                    walk(subitems, out);
+                }
+            }
+        }
+        let toc: serde_json::Value = serde_json::from_str(&toc_to_json(db, 1).unwrap()).unwrap();
+        let mut out = Vec::new();
+        walk(toc.as_array().unwrap(), &mut out);
+        out
+    }
+
+    fn chapter_contents(db: &Connection) -> Vec<(i64, String)> {
+        let query = ChapterQuery {
+            book_id: Some(1),
+            ..Default::default()
+        };
+        query_chapters_with(db, &query)
+            .unwrap()
+            .into_iter()
+            .map(|chapter| (chapter.id, chapter.content))
+            .collect()
+    }
+
+    fn split(db: &mut Connection, id: i64, at: SplitAt, operation_id: &str) -> AppResult<Vec<i64>> {
+        let tx = db.transaction()?;
+        let ctx = RevisionContext::new("split", Some(operation_id.to_string()));
+        let ids = split_chapter_tx(&tx, id, &at, &ctx)?;
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    fn merge(db: &mut Connection, ids: &[i64], operation_id: &str) -> AppResult<i64> {
+        let tx = db.transaction()?;
+        let ctx = RevisionContext::new("merge", Some(operation_id.to_string()));
+        let id = merge_chapters_tx(&tx, ids, &ctx)?;
+        tx.commit()?;
+        Ok(id)
+    }
+
+    fn undo(db: &mut Connection, operation_id: &str) {
+        let tx = db.transaction().unwrap();
+        undo_operation_tx(&tx, operation_id).unwrap();
+        tx.commit().unwrap();
+    }
+
+    fn entry(label: &str, href: &str) -> (String, String) {
+        (label.to_string(), href.to_string())
+    }
+
+    #[test]
+    fn split_inserts_toc_entries_after_the_chapter() {
+        let mut db = open_book();
+        let at = SplitAt::Pattern {
+            pattern: "^<h3>第.+章".to_string(),
+        };
+        assert_eq!(split(&mut db, 1, at, "op").unwrap(), vec![1, 3, 4]);
+        assert_eq!(
+            toc_entries(&db),
+            vec![
+                entry("卷一", "null"),
+                entry("第一章", "1"),
+                entry("锚", "\"3#a2\""),
+                entry("第二章 承", "3"),
+                entry("第三章 转", "4"),
+                entry("尾", "2"),
+            ]
+        );
+    }
+
+    #[test]
+    fn split_by_offset_cuts_inside_a_single_line() {
+        let mut db = open_book();
+        insert_chapter(&db, 1, "单行", "c3", "<p>甲乙&amp;丙丁</p>", None).unwrap();
+        // 位置 1 落在 <p> 中间，移到正文开头后被忽略；位置 7 落在 &amp; 中间，移到 & 之前
+        let at = SplitAt::Offsets {
+            offsets: vec![1, 7, 11],
+        };
+        assert_eq!(split(&mut db, 3, at, "op").unwrap(), vec![3, 4, 5]);
+        let contents = chapter_contents(&db);
+        assert_eq!(
+            contents[2..],
+            [
+                (3, "<p>甲乙".to_string()),
+                (4, "&amp;丙".to_string()),
+                (5, "丁</p>".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_moves_anchors_and_removes_toc_entries() {
+        let mut db = open_book();
+        let at = SplitAt::Pattern {
+            pattern: "^<h3>第.+章".to_string(),
+        };
+        split(&mut db, 1, at, "split").unwrap();
+        // 不相邻的章节不能合并
+        assert!(merge(&mut db, &[1, 4], "merge").is_err());
+        assert_eq!(merge(&mut db, &[4, 3, 1], "merge").unwrap(), 1);
+        assert_eq!(
+            toc_entries(&db),
+            vec![
+                entry("卷一", "null"),
+                entry("第一章", "1"),
+                entry("锚", "\"1#a2\""),
+                entry("尾", "2"),
+            ]
+        );
+        assert_eq!(
+            chapter_contents(&db),
+            vec![(1, BODY.to_string()), (2, "<p>尾</p>".to_string())]
+        );
+    }
+
+    #[test]
+    fn undo_restores_chapters_and_toc() {
+        let mut db = open_book();
+        let original_toc = toc_entries(&db);
+        let original = chapter_contents(&db);
+
+        let at = SplitAt::MaxLength { max_chars: 30 };
+        let ids = split(&mut db, 1, at, "split").unwrap();
+        assert!(ids.len() > 1);
+        undo(&mut db, "split");
+        assert_eq!(chapter_contents(&db), original);
+        assert_eq!(toc_entries(&db), original_toc);
+
+        let at = SplitAt::Pattern {
+            pattern: "^<h3>第.+章".to_string(),
+        };
+        let ids = split(&mut db, 1, at, "split-2").unwrap();
+        let split_toc = toc_entries(&db);
+        let split_chapters = chapter_contents(&db);
+        merge(&mut db, &ids, "merge").unwrap();
+        undo(&mut db, "merge");
+        assert_eq!(chapter_contents(&db), split_chapters);
+        assert_eq!(toc_entries(&db), split_toc);
+
+        // 撤销本身也可以撤销
+        undo(&mut db, "undo-merge");
+        assert_eq!(chapter_contents(&db).len(), 2);
+    }
+}
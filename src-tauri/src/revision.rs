@@ -0,0 +1,707 @@
+use crate::compress::read_content;
+use crate::database::{
+    get_current_time_string, get_db_connection, get_read_connection, get_setting, set_setting,
+    sync_toc_column, write_chapter, DbResponse,
+};
+use crate::error::AppError;
+use crate::pool::run_blocking;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use similar::{ChangeTag, TextDiff};
+use std::time::SystemTime;
+use tauri::{command, AppHandle};
+
+// 编辑器逐字保存时使用的操作名，同一章节在合并窗口内只保留一个历史版本
+pub const OP_EDIT: &str = "edit";
+const EDIT_COALESCE_SECS: i64 = 300;
+
+// 历史版本的类型：修改前的版本、操作中新建的章节（撤销时删除）、操作中删除的章节（撤销时重新插入）
+const CHANGE_UPDATE: &str = "update";
+const CHANGE_CREATE: &str = "create";
+const CHANGE_DELETE: &str = "delete";
+
+const SETTING_MAX_PER_CHAPTER: &str = "revision.maxPerChapter";
+const SETTING_MAX_AGE_DAYS: &str = "revision.maxAgeDays";
+const DEFAULT_MAX_PER_CHAPTER: i64 = 50;
+const DEFAULT_MAX_AGE_DAYS: i64 = 30;
+// 差异中变更前后保留的上下文行数
+const DIFF_CONTEXT_LINES: usize = 3;
+
+// 产生历史版本的操作，同一次批量操作中的所有章节共用 operation_id
+pub struct RevisionContext {
+    pub operation: String,
+    pub operation_id: String,
+}
+
+impl RevisionContext {
+    pub fn new(operation: &str, operation_id: Option<String>) -> Self {
+        Self {
+            operation: operation.to_string(),
+            operation_id: operation_id.unwrap_or_else(|| generate_operation_id(operation)),
+        }
+    }
+}
+
+pub fn generate_operation_id(operation: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|dur| dur.as_nanos())
+        .unwrap_or(0);
+    format!("{}-{}", operation, nanos)
+}
+
+// 历史版本保留策略，0 表示不限制
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionRetention {
+    pub max_per_chapter: i64,
+    pub max_age_days: i64,
+}
+
+fn load_retention(db: &Connection) -> Result<RevisionRetention, rusqlite::Error> {
+    let read = |key: &str, default: i64| -> Result<i64, rusqlite::Error> {
+        Ok(get_setting(db, key)?
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default))
+    };
+    Ok(RevisionRetention {
+        max_per_chapter: read(SETTING_MAX_PER_CHAPTER, DEFAULT_MAX_PER_CHAPTER)?,
+        max_age_days: read(SETTING_MAX_AGE_DAYS, DEFAULT_MAX_AGE_DAYS)?,
+    })
+}
+
+fn insert_revision(
+    db: &Connection,
+    chapter_id: i64,
+    ctx: &RevisionContext,
+    change: &str,
+) -> Result<(), rusqlite::Error> {
+    // 新建的章节只需要记录，不保存正文
+    db.execute(
+        "INSERT INTO ee_chapter_revision \
+         (chapterId, bookId, label, content, href, changeType, operation, operationId, createTime) \
+         SELECT id, bookId, label, CASE WHEN ?1 = 'create' THEN NULL ELSE content END, href, ?1, ?2, ?3, ?4 \
+         FROM ee_chapter WHERE id = ?5",
+        params![
+            change,
+            ctx.operation,
+            ctx.operation_id,
+            get_current_time_string(),
+            chapter_id
+        ],
+    )?;
+    prune_revisions(db, chapter_id)
+}
+
+// 在章节被修改前保存当前版本
+pub fn snapshot_chapter(
+    db: &Connection,
+    chapter_id: i64,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    insert_revision(db, chapter_id, ctx, CHANGE_UPDATE)
+}
+
+// 在章节被删除前保存完整内容，撤销时重新插入
+pub fn snapshot_deleted_chapter(
+    db: &Connection,
+    chapter_id: i64,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    insert_revision(db, chapter_id, ctx, CHANGE_DELETE)
+}
+
+// 记录操作中新建的章节，撤销时删除
+pub fn record_created_chapter(
+    db: &Connection,
+    chapter_id: i64,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    insert_revision(db, chapter_id, ctx, CHANGE_CREATE)
+}
+
+// 在目录被修改前保存整本书的目录节点，同一操作只保存第一次
+pub fn snapshot_toc(
+    db: &Connection,
+    book_id: i64,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    db.execute(
+        "INSERT OR IGNORE INTO ee_toc_revision (bookId, operationId, nodes, createTime) \
+         SELECT ?1, ?2, json_group_array(json_object( \
+             'id', id, 'parentId', parentId, 'sortIndex', sortIndex, \
+             'chapterId', chapterId, 'label', label, 'anchor', anchor)), ?3 \
+         FROM ee_toc_node WHERE bookId = ?1",
+        params![book_id, ctx.operation_id, get_current_time_string()],
+    )?;
+    Ok(())
+}
+
+// 用保存的节点替换书籍当前的目录，节点 id 不会被复用，可以原样写回
+fn restore_toc(tx: &Transaction, book_id: i64, nodes: &str) -> Result<(), rusqlite::Error> {
+    tx.execute("DELETE FROM ee_toc_node WHERE bookId = ?", params![book_id])?;
+    tx.execute(
+        "INSERT INTO ee_toc_node (id, bookId, parentId, sortIndex, chapterId, label, anchor) \
+         SELECT value ->> 'id', ?, value ->> 'parentId', value ->> 'sortIndex', \
+             value ->> 'chapterId', value ->> 'label', value ->> 'anchor' \
+         FROM json_each(?)",
+        params![book_id, nodes],
+    )?;
+    sync_toc_column(tx, book_id)
+}
+
+// 编辑器保存：合并窗口内已有编辑版本时不再重复保存。
+// 每个合并窗口是一次新的编辑会话，使用新的操作 id，撤销时只回到本次会话之前
+pub fn snapshot_for_edit(db: &Connection, chapter_id: i64) -> Result<(), rusqlite::Error> {
+    let last_edit: Option<i64> = db
+        .query_row(
+            "SELECT CAST(createTime AS INTEGER) FROM ee_chapter_revision \
+             WHERE chapterId = ? AND operation = ? ORDER BY id DESC LIMIT 1",
+            params![chapter_id, OP_EDIT],
+            |row| row.get(0),
+        )
+        .optional()?;
+    let now: i64 = get_current_time_string().parse().unwrap_or(0);
+    match last_edit {
+        Some(time) if now - time < EDIT_COALESCE_SECS => Ok(()),
+        _ => snapshot_chapter(db, chapter_id, &RevisionContext::new(OP_EDIT, None)),
+    }
+}
+
+// 按整个操作清理，避免撤销时只剩下操作中的部分章节。
+// 新建章节的记录不计入每章数量，随所属操作一起清理
+fn prune_revisions(db: &Connection, chapter_id: i64) -> Result<(), rusqlite::Error> {
+    let retention = load_retention(db)?;
+    let mut pruned: Vec<String> = Vec::new();
+    if retention.max_per_chapter > 0 {
+        let mut stmt = db.prepare(
+            "SELECT operationId FROM ee_chapter_revision \
+             WHERE chapterId = ?1 AND changeType != 'create' \
+             GROUP BY operationId ORDER BY MAX(id) DESC LIMIT -1 OFFSET ?2",
+        )?;
+        let rows = stmt.query_map(params![chapter_id, retention.max_per_chapter], |row| {
+            row.get(0)
+        })?;
+        pruned.extend(rows.collect::<Result<Vec<_>, _>>()?);
+    }
+    if retention.max_age_days > 0 {
+        let now: i64 = get_current_time_string().parse().unwrap_or(0);
+        let mut stmt = db.prepare(
+            "SELECT operationId FROM ee_chapter_revision WHERE operationId IN \
+                 (SELECT operationId FROM ee_chapter_revision WHERE chapterId = ?1) \
+             GROUP BY operationId HAVING MAX(CAST(createTime AS INTEGER)) < ?2",
+        )?;
+        let rows = stmt.query_map(
+            params![chapter_id, now - retention.max_age_days * 86400],
+            |row| row.get(0),
+        )?;
+        pruned.extend(rows.collect::<Result<Vec<_>, _>>()?);
+    }
+    // 只删除被清理的操作的版本与目录快照，不扫描整张表
+    for operation_id in &pruned {
+        db.execute(
+            "DELETE FROM ee_chapter_revision WHERE operationId = ?",
+            params![operation_id],
+        )?;
+        db.execute(
+            "DELETE FROM ee_toc_revision WHERE operationId = ?",
+            params![operation_id],
+        )?;
+    }
+    Ok(())
+}
+
+// 历史版本列表项（不含内容）
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevisionInfo {
+    pub id: i64,
+    pub chapter_id: i64,
+    pub label: String,
+    pub operation: String,
+    pub operation_id: String,
+    pub create_time: String,
+    pub length: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Revision {
+    pub id: i64,
+    pub chapter_id: i64,
+    pub label: String,
+    pub content: String,
+    pub change_type: String,
+    pub operation: String,
+    pub operation_id: String,
+    pub create_time: String,
+    // 重新插入已删除的章节时使用
+    #[serde(skip)]
+    book_id: Option<i64>,
+    #[serde(skip)]
+    href: Option<String>,
+}
+
+fn load_revision(db: &Connection, id: i64) -> Result<Revision, rusqlite::Error> {
+    db.query_row(
+        "SELECT id, chapterId, label, content, changeType, operation, operationId, createTime, \
+         bookId, href FROM ee_chapter_revision WHERE id = ?",
+        params![id],
+        |row| {
+            Ok(Revision {
+                id: row.get(0)?,
+                chapter_id: row.get(1)?,
+                label: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                content: read_content(row, 3)?,
+                change_type: row.get(4)?,
+                operation: row.get(5)?,
+                operation_id: row.get(6)?,
+                create_time: row.get(7)?,
+                book_id: row.get(8)?,
+                href: row.get(9)?,
+            })
+        },
+    )
+}
+
+// 获取章节的历史版本，按时间倒序。新建章节的记录没有内容，不在列表中
+#[command]
+pub async fn list_revisions(
+    chapter_id: i64,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<RevisionInfo>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        let mut stmt = db.prepare(
+            "SELECT id, chapterId, label, operation, operationId, createTime, content \
+             FROM ee_chapter_revision WHERE chapterId = ? AND changeType != 'create' \
+             ORDER BY id DESC",
+        )?;
+        let rows = stmt.query_map(params![chapter_id], |row| {
+            Ok(RevisionInfo {
+                id: row.get(0)?,
+                chapter_id: row.get(1)?,
+                label: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+                operation: row.get(3)?,
+                operation_id: row.get(4)?,
+                create_time: row.get(5)?,
+                // 历史版本没有长度列，压缩的正文需要解压后计算
+                length: read_content(row, 6)?.chars().count() as i64,
+            })
+        })?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    })
+    .await
+    .into()
+}
+
+// 获取单个历史版本的完整内容
+#[command]
+pub async fn get_revision(id: i64, app_handle: AppHandle) -> DbResponse<Revision> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        Ok(load_revision(&db, id)?)
+    })
+    .await
+    .into()
+}
+
+// 差异中的一行，tag 为 equal / insert / delete
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiffLine {
+    pub tag: String,
+    pub old_line: Option<usize>,
+    pub new_line: Option<usize>,
+    pub text: String,
+}
+
+fn diff_text(old: &str, new: &str) -> Vec<Vec<DiffLine>> {
+    let diff = TextDiff::from_lines(old, new);
+    diff.grouped_ops(DIFF_CONTEXT_LINES)
+        .iter()
+        .map(|group| {
+            group
+                .iter()
+                .flat_map(|op| diff.iter_changes(op))
+                .map(|change| DiffLine {
+                    tag: match change.tag() {
+                        ChangeTag::Equal => "equal",
+                        ChangeTag::Insert => "insert",
+                        ChangeTag::Delete => "delete",
+                    }
+                    .to_string(),
+                    old_line: change.old_index().map(|i| i + 1),
+                    new_line: change.new_index().map(|i| i + 1),
+                    text: change.value().trim_end_matches('\n').to_string(),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+// 比较两个历史版本，to_id 为空时与章节当前内容比较，返回按变更分组的差异
+#[command]
+pub async fn diff_revisions(
+    from_id: i64,
+    to_id: Option<i64>,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<Vec<DiffLine>>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        let from = load_revision(&db, from_id)?;
+        let to_content = match to_id {
+            Some(to_id) => load_revision(&db, to_id)?.content,
+            None => db.query_row(
+                "SELECT content FROM ee_chapter WHERE id = ?",
+                params![from.chapter_id],
+                |row| read_content(row, 0),
+            )?,
+        };
+        Ok(diff_text(&from.content, &to_content))
+    })
+    .await
+    .into()
+}
+
+// 将章节恢复为某个历史版本，恢复前先保存当前版本；章节已被删除时按原 id 重新插入
+fn restore_revision_tx(
+    tx: &Transaction,
+    revision: &Revision,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    let current_time = get_current_time_string();
+    let exists: bool = tx.query_row(
+        "SELECT EXISTS (SELECT 1 FROM ee_chapter WHERE id = ?)",
+        params![revision.chapter_id],
+        |row| row.get(0),
+    )?;
+    if exists {
+        snapshot_chapter(tx, revision.chapter_id, ctx)?;
+    } else {
+        if revision.book_id.is_none() {
+            return Err(rusqlite::Error::QueryReturnedNoRows);
+        }
+        // 章节 id 自增不复用，按原 id 插入后再写入正文
+        tx.execute(
+            "INSERT INTO ee_chapter (id, bookId, label, href, createTime, updateTime) \
+             VALUES (?, ?, ?, ?, ?, ?)",
+            params![
+                revision.chapter_id,
+                revision.book_id,
+                revision.label,
+                revision.href,
+                current_time,
+                current_time
+            ],
+        )?;
+        record_created_chapter(tx, revision.chapter_id, ctx)?;
+    }
+    write_chapter(
+        tx,
+        revision.chapter_id,
+        &revision.label,
+        Some(&revision.content),
+        &current_time,
+    )?;
+    Ok(())
+}
+
+// 撤销章节的新建：删除前保存完整内容，再次撤销时可以恢复
+fn remove_created_chapter(
+    tx: &Transaction,
+    chapter_id: i64,
+    ctx: &RevisionContext,
+) -> Result<(), rusqlite::Error> {
+    snapshot_deleted_chapter(tx, chapter_id, ctx)?;
+    tx.execute("DELETE FROM ee_chapter WHERE id = ?", params![chapter_id])?;
+    Ok(())
+}
+
+// 恢复单个历史版本
+#[command]
+pub async fn restore_revision(id: i64, app_handle: AppHandle) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        let tx = db.transaction()?;
+        let revision = load_revision(&tx, id)?;
+        if revision.change_type == CHANGE_CREATE {
+            return Err(AppError::InvalidInput(
+                "新建章节的记录没有可恢复的内容".to_string(),
+            ));
+        }
+        restore_revision_tx(&tx, &revision, &RevisionContext::new("restore", None))?;
+        tx.commit()?;
+        Ok(())
+    })
+    .await
+    .into()
+}
+
+// 撤销一次批量操作：把该操作涉及的每个章节恢复到操作之前的版本，删除操作中新建的章节，
+// 重新插入操作中删除的章节，并恢复操作前的目录。返回恢复的章节数
+pub fn undo_operation_tx(tx: &Transaction, operation_id: &str) -> Result<usize, rusqlite::Error> {
+    // 每个章节在该操作中最早的版本即操作前的内容
+    let revision_ids: Vec<i64> = {
+        let mut stmt = tx.prepare(
+            "SELECT MIN(id) FROM ee_chapter_revision WHERE operationId = ? GROUP BY chapterId",
+        )?;
+        let rows = stmt.query_map(params![operation_id], |row| row.get(0))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    let tocs: Vec<(i64, String)> = {
+        let mut stmt =
+            tx.prepare("SELECT bookId, nodes FROM ee_toc_revision WHERE operationId = ?")?;
+        let rows = stmt.query_map(params![operation_id], |row| Ok((row.get(0)?, row.get(1)?)))?;
+        rows.collect::<Result<_, _>>()?
+    };
+    let ctx = RevisionContext::new("undo", Some(format!("undo-{}", operation_id)));
+    for (book_id, _) in &tocs {
+        snapshot_toc(tx, *book_id, &ctx)?;
+    }
+    for id in &revision_ids {
+        let revision = load_revision(tx, *id)?;
+        if revision.change_type == CHANGE_CREATE {
+            remove_created_chapter(tx, revision.chapter_id, &ctx)?;
+        } else {
+            restore_revision_tx(tx, &revision, &ctx)?;
+        }
+    }
+    for (book_id, nodes) in &tocs {
+        restore_toc(tx, *book_id, nodes)?;
+    }
+    Ok(revision_ids.len())
+}
+
+#[command]
+pub async fn undo_operation(operation_id: String, app_handle: AppHandle) -> DbResponse<usize> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        let tx = db.transaction()?;
+        let count = undo_operation_tx(&tx, &operation_id)?;
+        tx.commit()?;
+        Ok(count)
+    })
+    .await
+    .into()
+}
+
+#[command]
+pub async fn get_revision_retention(app_handle: AppHandle) -> DbResponse<RevisionRetention> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        Ok(load_retention(&db)?)
+    })
+    .await
+    .into()
+}
+
+// 设置历史版本保留策略，新策略在下次保存版本时生效
+#[command]
+pub async fn set_revision_retention(
+    retention: RevisionRetention,
+    app_handle: AppHandle,
+) -> DbResponse<()> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+
+        set_setting(
+            &db,
+            SETTING_MAX_PER_CHAPTER,
+            &retention.max_per_chapter.to_string(),
+        )?;
+        set_setting(
+            &db,
+            SETTING_MAX_AGE_DAYS,
+            &retention.max_age_days.to_string(),
+        )?;
+        Ok(())
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::database::{update_chapters_tx, ChapterUpdate};
+    use crate::testutil::{self, content};
+
+    fn open_book() -> Connection {
+        testutil::open_book(&[("第一章", "甲\n乙\n丙"), ("第二章", "丁")])
+    }
+
+    fn update(db: &mut Connection, operation_id: &str, updates: &[(i64, &str)]) {
+        let updates: Vec<ChapterUpdate> = updates
+            .iter()
+            .map(|(id, content)| ChapterUpdate {
+                id: *id,
+                label: if *id == 1 { "第一章" } else { "第二章" }.to_string(),
+                content: Some(content.to_string()),
+            })
+            .collect();
+        let tx = db.transaction().unwrap();
+        let ctx = RevisionContext::new("batch", Some(operation_id.to_string()));
+        update_chapters_tx(&tx, &updates, &ctx).unwrap();
+        tx.commit().unwrap();
+    }
+
+    fn undo(db: &mut Connection, operation_id: &str) -> usize {
+        let tx = db.transaction().unwrap();
+        let count = undo_operation_tx(&tx, operation_id).unwrap();
+        tx.commit().unwrap();
+        count
+    }
+
+    #[test]
+    fn diff_marks_changed_lines() {
+        let groups = diff_text("甲\n乙\n丙\n", "甲\n乙乙\n丙\n");
+        assert_eq!(groups.len(), 1);
+        let lines: Vec<(&str, Option<usize>, Option<usize>, &str)> = groups[0]
+            .iter()
+            .map(|line| {
+                (
+                    line.tag.as_str(),
+                    line.old_line,
+                    line.new_line,
+                    line.text.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                ("equal", Some(1), Some(1), "甲"),
+                ("delete", Some(2), None, "乙"),
+                ("insert", None, Some(2), "乙乙"),
+                ("equal", Some(3), Some(3), "丙"),
+            ]
+        );
+        assert!(diff_text("甲", "甲").is_empty());
+    }
+
+    #[test]
+    fn undo_restores_content_before_the_operation() {
+        let mut db = open_book();
+        update(&mut db, "op-1", &[(1, "一改"), (2, "二改")]);
+        // 同一操作中再次修改，撤销时仍恢复到操作之前
+        update(&mut db, "op-1", &[(1, "一再改")]);
+        update(&mut db, "op-2", &[(2, "二再改")]);
+
+        assert_eq!(undo(&mut db, "op-1"), 2);
+        assert_eq!(content(&db, 1), "甲\n乙\n丙");
+        assert_eq!(content(&db, 2), "丁");
+
+        // 撤销本身也是一次操作，可以再撤销
+        assert_eq!(undo(&mut db, "undo-op-1"), 2);
+        assert_eq!(content(&db, 1), "一再改");
+        assert_eq!(content(&db, 2), "二再改");
+    }
+
+    #[test]
+    fn retention_keeps_latest_revisions() {
+        let mut db = open_book();
+        set_setting(&db, SETTING_MAX_PER_CHAPTER, "2").unwrap();
+        for (index, text) in ["一", "二", "三", "四"].iter().enumerate() {
+            update(&mut db, &format!("op-{}", index), &[(1, text)]);
+        }
+        let kept: Vec<String> = db
+            .prepare("SELECT operationId FROM ee_chapter_revision WHERE chapterId = 1 ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(kept, vec!["op-2", "op-3"]);
+    }
+
+    #[test]
+    fn retention_prunes_whole_operations() {
+        let mut db = open_book();
+        set_setting(&db, SETTING_MAX_PER_CHAPTER, "2").unwrap();
+        update(&mut db, "op-both", &[(1, "一"), (2, "一")]);
+        // 新建章节的记录不计入数量
+        let ctx = RevisionContext::new("batch", Some("op-create".to_string()));
+        record_created_chapter(&db, 1, &ctx).unwrap();
+        update(&mut db, "op-1", &[(1, "二")]);
+        update(&mut db, "op-2", &[(1, "三")]);
+
+        let operations: Vec<(i64, String)> = db
+            .prepare("SELECT chapterId, operationId FROM ee_chapter_revision ORDER BY id")
+            .unwrap()
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        // 第二章的版本随 op-both 一起清理，撤销不会只恢复一半
+        assert_eq!(
+            operations,
+            vec![
+                (1, "op-create".to_string()),
+                (1, "op-1".to_string()),
+                (1, "op-2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn pruning_only_removes_toc_snapshots_of_pruned_operations() {
+        let mut db = open_book();
+        set_setting(&db, SETTING_MAX_PER_CHAPTER, "1").unwrap();
+        snapshot_toc(
+            &db,
+            1,
+            &RevisionContext::new("batch", Some("op-1".to_string())),
+        )
+        .unwrap();
+        // 其他操作的目录快照，没有对应的章节版本也不受影响
+        snapshot_toc(
+            &db,
+            1,
+            &RevisionContext::new("batch", Some("op-toc".to_string())),
+        )
+        .unwrap();
+        update(&mut db, "op-1", &[(1, "一")]);
+        update(&mut db, "op-2", &[(1, "二")]);
+
+        let kept: Vec<String> = db
+            .prepare("SELECT operationId FROM ee_toc_revision ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(kept, vec!["op-toc"]);
+    }
+
+    #[test]
+    fn each_edit_session_gets_its_own_operation() {
+        let db = open_book();
+        snapshot_for_edit(&db, 1).unwrap();
+        // 合并窗口内再次保存不产生新版本
+        snapshot_for_edit(&db, 1).unwrap();
+        // 模拟合并窗口已过
+        db.execute(
+            "UPDATE ee_chapter_revision SET createTime = CAST(createTime AS INTEGER) - ? \
+             WHERE chapterId = 1",
+            params![EDIT_COALESCE_SECS],
+        )
+        .unwrap();
+        snapshot_for_edit(&db, 1).unwrap();
+
+        let operations: Vec<String> = db
+            .prepare("SELECT operationId FROM ee_chapter_revision WHERE chapterId = 1 ORDER BY id")
+            .unwrap()
+            .query_map([], |row| row.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(operations.len(), 2);
+        assert_ne!(operations[0], operations[1]);
+        assert!(operations.iter().all(|id| id.starts_with("edit-")));
+    }
+}
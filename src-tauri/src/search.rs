@@ -0,0 +1,508 @@
+use crate::compress::read_content;
+use crate::database::{get_read_connection, get_setting, set_setting, DbResponse};
+use crate::error::AppResult;
+use crate::pool::run_blocking;
+use rusqlite::types::Value;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle};
+
+// trigram 分词器要求每个词至少 3 个字符，更短的关键词使用短词索引
+const TRIGRAM_MIN_CHARS: usize = 3;
+// 摘要中关键词前后保留的字符数
+const SNIPPET_CONTEXT_CHARS: usize = 30;
+const DEFAULT_LIMIT: i64 = 100;
+// 全文索引的写入规则版本，规则修改后递增，启动时重建索引
+const SEARCH_INDEX_VERSION: &str = "1";
+const SETTING_SEARCH_INDEX_VERSION: &str = "search.indexVersion";
+
+// 全文检索结果
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchHit {
+    pub book_id: i64,
+    pub chapter_id: i64,
+    pub label: String,
+    pub snippet: String,
+    pub rank: f64,
+}
+
+// 将关键词转换为 FTS5 查询：每个词作为短语并以 AND 连接。
+// 短词索引中单个字符的词按前缀匹配以该字符开头的分词
+fn to_match_expression(keywords: &[&str]) -> String {
+    keywords
+        .iter()
+        .map(|word| {
+            let phrase = format!("\"{}\"", word.replace('"', "\"\""));
+            if word.chars().count() == 1 {
+                format!("{} *", phrase)
+            } else {
+                phrase
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" AND ")
+}
+
+// 短词索引的分词：每段连续的字母与数字中相邻两个字符为一个词，最后一个字符单独为一个词，
+// 这样一个字符的关键词按前缀、两个字符的关键词按整词都能在索引中找到
+fn pair_terms(text: &str) -> String {
+    let mut terms = String::new();
+    let mut previous: Option<char> = None;
+    for c in text.chars() {
+        let current = Some(c).filter(|c| c.is_alphanumeric());
+        if let Some(prev) = previous {
+            if !terms.is_empty() {
+                terms.push(' ');
+            }
+            terms.push(prev);
+            if let Some(current) = current {
+                terms.push(current);
+            }
+        }
+        previous = current;
+    }
+    if let Some(prev) = previous {
+        if !terms.is_empty() {
+            terms.push(' ');
+        }
+        terms.push(prev);
+    }
+    terms
+}
+
+// 正文去掉 HTML 标签后的短词分词
+pub fn short_terms(content: &str) -> String {
+    pair_terms(&plain_text(content))
+}
+
+// 写入章节的全文索引。两个索引都只保存去掉标签后的正文，标签与属性中的文字不会被检索到
+pub fn index_chapter(
+    db: &Connection,
+    chapter_id: i64,
+    label: &str,
+    content: &str,
+) -> Result<(), rusqlite::Error> {
+    let text = plain_text(content);
+    db.prepare_cached(
+        "INSERT OR REPLACE INTO ee_chapter_fts (rowid, label, content) VALUES (?, ?, ?)",
+    )?
+    .execute(params![chapter_id, label, text])?;
+    db.prepare_cached(
+        "INSERT OR REPLACE INTO ee_chapter_bigram (rowid, label, content) VALUES (?, ?, ?)",
+    )?
+    .execute(params![chapter_id, pair_terms(label), pair_terms(&text)])?;
+    Ok(())
+}
+
+// 按数据库中保存的标题与正文重建章节的全文索引（只修改标题或复制章节后调用）
+pub fn reindex_chapter(db: &Connection, chapter_id: i64) -> Result<(), rusqlite::Error> {
+    let stored: Option<(Option<String>, String)> = db
+        .query_row(
+            "SELECT label, content FROM ee_chapter WHERE id = ?",
+            params![chapter_id],
+            |row| Ok((row.get(0)?, read_content(row, 1)?)),
+        )
+        .optional()?;
+    match stored {
+        Some((label, content)) => {
+            index_chapter(db, chapter_id, &label.unwrap_or_default(), &content)
+        }
+        None => Ok(()),
+    }
+}
+
+// 全文索引由应用写入，索引规则版本与已建索引不一致时（包括首次升级）重建全部章节的索引
+pub fn ensure_search_index(db: &mut Connection) -> AppResult<()> {
+    if get_setting(db, SETTING_SEARCH_INDEX_VERSION)?.as_deref() == Some(SEARCH_INDEX_VERSION) {
+        return Ok(());
+    }
+    let tx = db.transaction()?;
+    for table in ["ee_chapter_fts", "ee_chapter_bigram"] {
+        tx.execute(
+            &format!("INSERT INTO {0} ({0}) VALUES ('delete-all')", table),
+            [],
+        )?;
+    }
+    {
+        let mut stmt = tx.prepare("SELECT id, label, content FROM ee_chapter")?;
+        let mut rows = stmt.query([])?;
+        while let Some(row) = rows.next()? {
+            let label = row.get::<_, Option<String>>(1)?.unwrap_or_default();
+            index_chapter(&tx, row.get(0)?, &label, &read_content(row, 2)?)?;
+        }
+    }
+    set_setting(&tx, SETTING_SEARCH_INDEX_VERSION, SEARCH_INDEX_VERSION)?;
+    tx.commit()?;
+    Ok(())
+}
+
+// 短词索引只包含字母与数字，含有其他字符的短词不能在索引中查找
+fn is_short_term(word: &str) -> bool {
+    word.chars().all(char::is_alphanumeric)
+}
+
+pub fn search_chapters_with(
+    db: &Connection,
+    query: &str,
+    book_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SearchHit>, rusqlite::Error> {
+    let keywords: Vec<&str> = query.split_whitespace().collect();
+    if keywords.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let (long, short): (Vec<&str>, Vec<&str>) = keywords
+        .iter()
+        .partition(|word| word.chars().count() >= TRIGRAM_MIN_CHARS);
+    if short.iter().all(|word| is_short_term(word)) {
+        search_by_index(db, &keywords, &long, &short, book_id, limit, offset)
+    } else {
+        search_by_scan(db, &keywords, book_id, limit, offset)
+    }
+}
+
+// 较长的词在 trigram 索引中查找，较短的词在短词索引中查找，两者都有时取交集，按第一个索引的相关度排序
+fn search_by_index(
+    db: &Connection,
+    keywords: &[&str],
+    long: &[&str],
+    short: &[&str],
+    book_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SearchHit>, rusqlite::Error> {
+    let indexes: Vec<(&str, String)> = [("ee_chapter_fts", long), ("ee_chapter_bigram", short)]
+        .into_iter()
+        .filter(|(_, words)| !words.is_empty())
+        .map(|(table, words)| (table, to_match_expression(words)))
+        .collect();
+    let table = indexes[0].0;
+    let mut sql = format!(
+        "SELECT c.bookId, c.id, c.label, c.content, bm25({0}) AS rank \
+         FROM {0} \
+         JOIN ee_chapter c ON c.id = {0}.rowid \
+         JOIN ee_book b ON b.id = c.bookId AND b.isDel = 0 \
+         WHERE {0} MATCH ?",
+        table
+    );
+    for (other, _) in &indexes[1..] {
+        sql.push_str(&format!(
+            " AND c.id IN (SELECT rowid FROM {0} WHERE {0} MATCH ?)",
+            other
+        ));
+    }
+    let mut values: Vec<Value> = indexes
+        .into_iter()
+        .map(|(_, expression)| Value::Text(expression))
+        .collect();
+    if let Some(book_id) = book_id {
+        sql.push_str(" AND c.bookId = ?");
+        values.push(Value::Integer(book_id));
+    }
+    sql.push_str(" ORDER BY rank LIMIT ? OFFSET ?");
+    values.push(Value::Integer(limit));
+    values.push(Value::Integer(offset));
+
+    let mut stmt = db.prepare(&sql)?;
+    let rows = stmt.query_map(params_from_iter(values), |row| {
+        Ok(SearchHit {
+            book_id: row.get(0)?,
+            chapter_id: row.get(1)?,
+            label: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            snippet: keyword_snippet(&read_content(row, 3)?, keywords),
+            rank: row.get(4)?,
+        })
+    })?;
+    rows.collect()
+}
+
+// 关键词含有不能在短词索引中查找的字符时，逐章解压正文并去掉标签后查找，与索引一样不区分大小写
+fn search_by_scan(
+    db: &Connection,
+    keywords: &[&str],
+    book_id: Option<i64>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<SearchHit>, rusqlite::Error> {
+    let mut sql = String::from(
+        "SELECT c.bookId, c.id, c.label, c.content FROM ee_chapter c \
+         JOIN ee_book b ON b.id = c.bookId AND b.isDel = 0",
+    );
+    let mut values = Vec::new();
+    if let Some(book_id) = book_id {
+        sql.push_str(" WHERE c.bookId = ?");
+        values.push(Value::Integer(book_id));
+    }
+    sql.push_str(" ORDER BY c.bookId, c.id");
+
+    let mut stmt = db.prepare(&sql)?;
+    let mut rows = stmt.query(params_from_iter(values))?;
+    let mut hits = Vec::new();
+    let mut skipped = 0;
+    while let Some(row) = rows.next()? {
+        if hits.len() as i64 >= limit {
+            break;
+        }
+        let label = row.get::<_, Option<String>>(2)?.unwrap_or_default();
+        let content = plain_text(&read_content(row, 3)?);
+        let matched = keywords.iter().all(|word| {
+            find_ignore_case(&label, word)
+                .or(find_ignore_case(&content, word))
+                .is_some()
+        });
+        if !matched {
+            continue;
+        }
+        if skipped < offset {
+            skipped += 1;
+            continue;
+        }
+        hits.push(SearchHit {
+            book_id: row.get(0)?,
+            chapter_id: row.get(1)?,
+            label,
+            snippet: make_snippets(&content, keywords),
+            rank: 0.0,
+        });
+    }
+    Ok(hits)
+}
+
+// 不区分大小写查找关键词，返回匹配部分的字节范围
+fn find_ignore_case(text: &str, keyword: &str) -> Option<(usize, usize)> {
+    let same = |a: char, b: char| a == b || a.to_lowercase().eq(b.to_lowercase());
+    text.char_indices().find_map(|(start, _)| {
+        let mut chars = text[start..].char_indices();
+        for k in keyword.chars() {
+            match chars.next() {
+                Some((_, c)) if same(c, k) => {}
+                _ => return None,
+            }
+        }
+        let end = chars
+            .next()
+            .map_or(text.len(), |(offset, _)| start + offset);
+        Some((start, end))
+    })
+}
+
+// 去掉 HTML 标签并还原常见的字符实体，摘要只展示正文文字
+fn plain_text(content: &str) -> String {
+    let mut text = String::with_capacity(content.len());
+    let mut in_tag = false;
+    for c in content.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    [
+        ("&lt;", "<"),
+        ("&gt;", ">"),
+        ("&quot;", "\""),
+        ("&#39;", "'"),
+        ("&nbsp;", " "),
+        ("&amp;", "&"),
+    ]
+    .iter()
+    .fold(text, |text, (entity, c)| text.replace(entity, c))
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// 全文索引不保存正文，摘要取正文中第一个出现的关键词
+fn keyword_snippet(content: &str, keywords: &[&str]) -> String {
+    make_snippets(&plain_text(content), keywords)
+}
+
+fn make_snippets(text: &str, keywords: &[&str]) -> String {
+    keywords
+        .iter()
+        .map(|word| make_snippet(text, word))
+        .find(|snippet| !snippet.is_empty())
+        .unwrap_or_default()
+}
+
+// 生成与 FTS5 snippet() 相同格式的摘要。text 为纯文本，除 <mark> 外的内容都经过转义，可直接作为 HTML 显示
+fn make_snippet(text: &str, keyword: &str) -> String {
+    let (start, end) = match find_ignore_case(text, keyword) {
+        Some(range) => range,
+        None => return String::new(),
+    };
+
+    let before: Vec<char> = text[..start]
+        .chars()
+        .rev()
+        .take(SNIPPET_CONTEXT_CHARS)
+        .collect();
+    let before: String = before.into_iter().rev().collect();
+    let after: String = text[end..].chars().take(SNIPPET_CONTEXT_CHARS).collect();
+
+    let mut snippet = String::new();
+    if text[..start]
+        .chars()
+        .rev()
+        .nth(SNIPPET_CONTEXT_CHARS)
+        .is_some()
+    {
+        snippet.push('…');
+    }
+    snippet.push_str(&escape_html(&before));
+    snippet.push_str("<mark>");
+    snippet.push_str(&escape_html(&text[start..end]));
+    snippet.push_str("</mark>");
+    snippet.push_str(&escape_html(&after));
+    if text[end..].chars().nth(SNIPPET_CONTEXT_CHARS).is_some() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+// 全文检索章节，book_id 为空时检索所有未删除的书籍
+#[command]
+pub async fn search_chapters(
+    query: String,
+    book_id: Option<i64>,
+    limit: Option<i64>,
+    offset: Option<i64>,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<SearchHit>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        Ok(search_chapters_with(
+            &db,
+            &query,
+            book_id,
+            limit.unwrap_or(DEFAULT_LIMIT),
+            offset.unwrap_or(0).max(0),
+        )?)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::open_book;
+
+    fn open_library() -> Connection {
+        open_book(&[
+            ("第一章", "<p>我们读书</p>"),
+            ("第二章", "<p>Rust 是一门语言，书，</p>"),
+        ])
+    }
+
+    fn search(db: &Connection, query: &str) -> Vec<(i64, String)> {
+        search_chapters_with(db, query, None, DEFAULT_LIMIT, 0)
+            .unwrap()
+            .into_iter()
+            .map(|hit| (hit.chapter_id, hit.snippet))
+            .collect()
+    }
+
+    #[test]
+    fn short_terms_pair_adjacent_characters() {
+        assert_eq!(short_terms("<p class=\"x\">读书</p>"), "读书 书");
+        assert_eq!(short_terms("a 读书，Go"), "a 读书 书 Go o");
+    }
+
+    #[test]
+    fn short_keywords_use_the_bigram_index() {
+        let db = open_library();
+        assert_eq!(
+            search(&db, "读书"),
+            vec![(1, "我们<mark>读书</mark>".to_string())]
+        );
+        let ids: Vec<i64> = search(&db, "书").into_iter().map(|(id, _)| id).collect();
+        assert_eq!(ids.len(), 2);
+        // 长短关键词同时出现时取两个索引的交集
+        assert_eq!(search(&db, "rust 语言").len(), 1);
+        assert!(search(&db, "rust 读书").is_empty());
+        // 标签中的文字不进入短词索引
+        assert!(search(&db, "p").is_empty());
+    }
+
+    #[test]
+    fn short_index_is_rebuilt_for_existing_chapters() {
+        let mut db = open_library();
+        let clear = |db: &Connection| {
+            db.execute(
+                "INSERT INTO ee_chapter_bigram (ee_chapter_bigram) VALUES ('delete-all')",
+                [],
+            )
+            .unwrap();
+        };
+        // 升级后第一次启动时补建索引
+        clear(&db);
+        assert!(search(&db, "读书").is_empty());
+        ensure_search_index(&mut db).unwrap();
+        assert_eq!(search(&db, "读书").len(), 1);
+
+        // 索引规则版本一致时不重建，版本变化后重建
+        clear(&db);
+        ensure_search_index(&mut db).unwrap();
+        assert!(search(&db, "读书").is_empty());
+        set_setting(&db, SETTING_SEARCH_INDEX_VERSION, "0").unwrap();
+        ensure_search_index(&mut db).unwrap();
+        assert_eq!(search(&db, "读书").len(), 1);
+    }
+
+    #[test]
+    fn keywords_only_in_markup_are_not_found() {
+        let db = open_book(&[("第一章", r#"<p class="note"><span>正文</span></p>"#)]);
+        assert!(search(&db, "class").is_empty());
+        assert!(search(&db, "span").is_empty());
+        assert!(search(&db, "note").is_empty());
+        // 不能使用索引的关键词逐章查找时同样忽略标签
+        assert!(search(&db, "<span>").is_empty());
+        assert_eq!(search(&db, "正文").len(), 1);
+    }
+
+    #[test]
+    fn keywords_with_punctuation_fall_back_to_scanning() {
+        let db = open_library();
+        assert_eq!(
+            search(&db, "书，"),
+            vec![(2, "Rust 是一门语言，<mark>书，</mark>".to_string())]
+        );
+    }
+
+    #[test]
+    fn snippet_matches_case_insensitively_and_escapes_html() {
+        assert_eq!(
+            keyword_snippet("<p>a &lt;b&gt; Rust</p>", &["rust"]),
+            "a &lt;b&gt; <mark>Rust</mark>"
+        );
+        assert_eq!(
+            make_snippet("<script>", "SCRIPT"),
+            "&lt;<mark>script</mark>&gt;"
+        );
+        let long = format!("{}书{}", "甲".repeat(40), "乙".repeat(40));
+        let snippet = make_snippet(&long, "书");
+        assert!(snippet.starts_with('…') && snippet.ends_with('…'));
+        assert_eq!(
+            snippet.chars().count(),
+            2 + 30 + "<mark>书</mark>".chars().count() + 30
+        );
+    }
+}
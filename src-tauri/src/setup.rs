@@ -1,19 +1,45 @@
-use crate::database::init_db;
+use crate::database::{init_db, purge_expired_books, DB_FILENAME};
+use crate::encryption::is_encrypted;
+use crate::migration::MigrationError;
+use crate::pool::DbPool;
+use crate::search::ensure_search_index;
+use std::collections::HashMap;
 use std::error::Error;
-use std::sync::Mutex;
-use tauri::{App, Manager};
+use std::sync::atomic::AtomicBool;
+use std::sync::{Arc, Mutex};
+use tauri::{App, AppHandle, Emitter, Manager};
 
 // 1. 定义应用状态结构体
 pub struct AppState {
-    pub db: Mutex<rusqlite::Connection>,
+    // 数据库连接池：一个写连接与多个只读连接
+    pub db: DbPool,
+    // 正在执行的批量处理任务及其取消标记
+    pub transform_jobs: Mutex<HashMap<String, Arc<AtomicBool>>>,
 }
 
+// 启动时数据库迁移失败的信息，前端通过 get_migration_status 查询。
+// 迁移失败时不注册 AppState，因此单独注册
+#[derive(Default)]
+pub struct MigrationStatus(pub Mutex<Option<MigrationError>>);
+
 pub fn setup_app(app: &mut App) -> Result<(), Box<dyn Error>> {
-    // 调用 数据库初始化
-    let db = init_db(app.handle())?;
+    app.manage(MigrationStatus::default());
 
-    // 将数据库连接存储在应用状态中
-    app.manage(AppState { db: Mutex::new(db) });
+    let db_path = app.path().app_data_dir()?.join(DB_FILENAME);
+    if is_encrypted(&db_path) {
+        // 加密库需要前端通过 unlock_database 输入密码后才能打开，在此之前不注册 AppState
+        println!("[DB] 数据库已加密，等待解锁");
+    } else {
+        match init_state(app.handle(), None) {
+            // 将数据库连接存储在应用状态中
+            Ok(state) => {
+                app.manage(state);
+            }
+            // 迁移失败时整体回滚、原库保持不变，也不注册 AppState，避免在空库上继续操作
+            Err(err) if err.is::<MigrationError>() => {}
+            Err(err) => return Err(err),
+        }
+    }
 
     // 调试环境下打开开发者工具
     #[cfg(debug_assertions)]
@@ -22,6 +48,43 @@ pub fn setup_app(app: &mut App) -> Result<(), Box<dyn Error>> {
     Ok(())
 }
 
+// 打开数据库并创建应用状态，加密库的 key 为用户输入的密码
+// 迁移失败时记录到 MigrationStatus 并通知前端（包含升级前的备份路径），返回 MigrationError
+pub fn init_state(app: &AppHandle, key: Option<&str>) -> Result<AppState, Box<dyn Error>> {
+    // 调用 数据库初始化
+    let db = match init_db(app, key) {
+        Ok(db) => db,
+        Err(err) => {
+            if let Some(migration_error) = err.downcast_ref::<MigrationError>() {
+                eprintln!("{}", migration_error);
+                app.emit("db-migration-error", migration_error.clone())?;
+                if let Some(status) = app.try_state::<MigrationStatus>() {
+                    if let Ok(mut status) = status.0.lock() {
+                        *status = Some(migration_error.clone());
+                    }
+                }
+            }
+            return Err(err);
+        }
+    };
+
+    // 清理回收站中超过自动清理天数的书籍
+    let app_dir = app.path().app_data_dir()?;
+    match purge_expired_books(&mut *db.writer()?, &app_dir) {
+        Ok(0) => {}
+        Ok(count) => println!("[DB] 已自动清理回收站中的 {} 本书籍", count),
+        Err(err) => eprintln!("[DB] 自动清理回收站失败: {}", err),
+    }
+    // 首次升级或索引规则变化后重建全文索引，失败时只影响检索
+    if let Err(err) = ensure_search_index(&mut *db.writer()?) {
+        eprintln!("[DB] 重建全文索引失败: {}", err);
+    }
+    Ok(AppState {
+        db,
+        transform_jobs: Mutex::new(HashMap::new()),
+    })
+}
+
 //调试环境打开开发者工具
 #[cfg(debug_assertions)]
 fn open_devtools(app: &mut App) -> Result<(), Box<dyn Error>> {
@@ -0,0 +1,374 @@
+use crate::compress::read_content;
+use crate::database::{get_db_connection, DbResponse};
+use crate::error::AppResult;
+use crate::pool::run_blocking;
+use regex::Regex;
+use rusqlite::{params, Connection};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle};
+use unicode_segmentation::UnicodeSegmentation;
+
+// 章节长度低于中位数的该比例或高于中位数的该倍数时视为异常
+const OUTLIER_LOW_RATIO: f64 = 0.2;
+const OUTLIER_HIGH_RATIO: f64 = 3.0;
+
+// 单个章节的统计结果
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TextCounts {
+    // 字符数（按字素计，不含空白）
+    pub chars: i64,
+    // 中日韩文字数（汉字、假名、谚文）
+    pub cjk_chars: i64,
+    // 拉丁等以空格分词的文字的单词数
+    pub latin_words: i64,
+    // 段落数：有 <p> <div> <h1-6> <li> 等块级元素时按块计，纯文本按非空行计（只有图片的不计入）
+    pub paragraphs: i64,
+    pub images: i64,
+}
+
+impl TextCounts {
+    // 字数：中日韩文字按字计，其他文字按单词计
+    pub fn words(&self) -> i64 {
+        self.cjk_chars + self.latin_words
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c as u32,
+        0x3040..=0x30FF      // 平假名、片假名
+        | 0x3400..=0x4DBF    // 扩展 A
+        | 0x4E00..=0x9FFF    // 基本汉字
+        | 0xAC00..=0xD7AF    // 谚文音节
+        | 0xF900..=0xFAFF    // 兼容汉字
+        | 0x20000..=0x3134F) // 扩展 B 至 G
+}
+
+// 统计用到的正则，批量统计时只编译一次
+pub struct CountPatterns {
+    tag: Regex,
+    block: Regex,
+}
+
+impl CountPatterns {
+    pub fn new() -> AppResult<Self> {
+        Ok(Self {
+            tag: Regex::new(r"<[^>]*>")?,
+            block: Regex::new(r"(?i)</?(?:p|div|h[1-6]|li)\b[^>]*>")?,
+        })
+    }
+}
+
+// 统计一段正文，HTML 标签不计入文字，<img> 计入图片数
+pub fn count_text(content: &str, patterns: &CountPatterns) -> TextCounts {
+    let images = content.matches("<img").count() as i64;
+    let text = patterns.tag.replace_all(content, "");
+
+    let mut counts = TextCounts {
+        images,
+        ..Default::default()
+    };
+    for grapheme in text.graphemes(true) {
+        if grapheme.chars().all(char::is_whitespace) {
+            continue;
+        }
+        counts.chars += 1;
+        if grapheme.chars().next().is_some_and(is_cjk) {
+            counts.cjk_chars += 1;
+        }
+    }
+    counts.latin_words = text
+        .unicode_words()
+        .filter(|word| !word.chars().any(is_cjk))
+        .count() as i64;
+    // 单行 HTML 中的多个段落也要分开计数，只有纯文本才按行计
+    counts.paragraphs = if patterns.block.is_match(content) {
+        patterns
+            .block
+            .split(content)
+            .filter(|block| !patterns.tag.replace_all(block, "").trim().is_empty())
+            .count() as i64
+    } else {
+        text.lines().filter(|line| !line.trim().is_empty()).count() as i64
+    };
+    counts
+}
+
+// 章节统计，label 便于前端直接展示
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChapterStats {
+    pub id: i64,
+    pub label: String,
+    #[serde(flatten)]
+    pub counts: TextCounts,
+    pub words: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BookStats {
+    pub book_id: i64,
+    pub chapter_count: i64,
+    #[serde(flatten)]
+    pub totals: TextCounts,
+    pub words: i64,
+    // 章节长度（字符数）
+    pub average_chapter_chars: f64,
+    pub min_chapter_chars: i64,
+    pub max_chapter_chars: i64,
+    pub empty_chapters: Vec<ChapterStats>,
+    // 长度明显偏离中位数的章节，通常是拆分错误
+    pub outlier_chapters: Vec<ChapterStats>,
+    pub chapters: Vec<ChapterStats>,
+}
+
+// 为缺少缓存的章节计算统计并写入缓存
+fn refresh_stats_cache(db: &Connection, book_id: i64) -> AppResult<()> {
+    let pending: Vec<(i64, String)> = {
+        let mut stmt = db.prepare(
+            "SELECT c.id, c.content FROM ee_chapter c \
+                 LEFT JOIN ee_chapter_stats s ON s.chapterId = c.id \
+                 WHERE c.bookId = ? AND s.chapterId IS NULL",
+        )?;
+        let rows = stmt.query_map(params![book_id], |row| {
+            Ok((row.get(0)?, read_content(row, 1)?))
+        })?;
+        rows.collect::<Result<_, _>>()?
+    };
+    if pending.is_empty() {
+        return Ok(());
+    }
+
+    let patterns = CountPatterns::new()?;
+    let mut stmt = db.prepare(
+        "INSERT OR REPLACE INTO ee_chapter_stats \
+             (chapterId, bookId, chars, cjkChars, latinWords, paragraphs, images) \
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+    )?;
+    for (id, content) in pending {
+        let counts = count_text(&content, &patterns);
+        stmt.execute(params![
+            id,
+            book_id,
+            counts.chars,
+            counts.cjk_chars,
+            counts.latin_words,
+            counts.paragraphs,
+            counts.images
+        ])?;
+    }
+    Ok(())
+}
+
+// 书籍字数：已有缓存的章节直接累加，缺少缓存的章节即时统计。
+// 只读取不写缓存，可以在只读连接上调用
+pub fn book_word_count(db: &Connection, book_id: i64) -> AppResult<i64> {
+    let cached: i64 = db.query_row(
+        "SELECT COALESCE(SUM(s.cjkChars + s.latinWords), 0) \
+             FROM ee_chapter c JOIN ee_chapter_stats s ON s.chapterId = c.id \
+             WHERE c.bookId = ?",
+        params![book_id],
+        |row| row.get(0),
+    )?;
+
+    let mut stmt = db.prepare(
+        "SELECT c.content FROM ee_chapter c \
+             LEFT JOIN ee_chapter_stats s ON s.chapterId = c.id \
+             WHERE c.bookId = ? AND s.chapterId IS NULL",
+    )?;
+    let mut rows = stmt.query(params![book_id])?;
+    let patterns = CountPatterns::new()?;
+    let mut pending = 0;
+    while let Some(row) = rows.next()? {
+        pending += count_text(&read_content(row, 0)?, &patterns).words();
+    }
+    Ok(cached + pending)
+}
+
+fn load_chapter_stats(db: &Connection, book_id: i64) -> AppResult<Vec<ChapterStats>> {
+    let mut stmt = db.prepare(
+        "SELECT c.id, c.label, s.chars, s.cjkChars, s.latinWords, s.paragraphs, s.images \
+             FROM ee_chapter c JOIN ee_chapter_stats s ON s.chapterId = c.id \
+             WHERE c.bookId = ? ORDER BY c.id",
+    )?;
+    let rows = stmt.query_map(params![book_id], |row| {
+        let counts = TextCounts {
+            chars: row.get(2)?,
+            cjk_chars: row.get(3)?,
+            latin_words: row.get(4)?,
+            paragraphs: row.get(5)?,
+            images: row.get(6)?,
+        };
+        Ok(ChapterStats {
+            id: row.get(0)?,
+            label: row.get::<_, Option<String>>(1)?.unwrap_or_default(),
+            words: counts.words(),
+            counts,
+        })
+    })?;
+    Ok(rows.collect::<Result<_, _>>()?)
+}
+
+fn median(values: &mut [i64]) -> f64 {
+    if values.is_empty() {
+        return 0.0;
+    }
+    values.sort_unstable();
+    let mid = values.len() / 2;
+    if values.len() % 2 == 1 {
+        values[mid] as f64
+    } else {
+        (values[mid - 1] + values[mid]) as f64 / 2.0
+    }
+}
+
+pub fn book_stats_with(db: &Connection, book_id: i64) -> AppResult<BookStats> {
+    refresh_stats_cache(db, book_id)?;
+    let chapters = load_chapter_stats(db, book_id)?;
+
+    let mut totals = TextCounts::default();
+    for chapter in &chapters {
+        totals.chars += chapter.counts.chars;
+        totals.cjk_chars += chapter.counts.cjk_chars;
+        totals.latin_words += chapter.counts.latin_words;
+        totals.paragraphs += chapter.counts.paragraphs;
+        totals.images += chapter.counts.images;
+    }
+
+    let mut lengths: Vec<i64> = chapters
+        .iter()
+        .map(|chapter| chapter.counts.chars)
+        .filter(|chars| *chars > 0)
+        .collect();
+    let median = median(&mut lengths);
+    let empty_chapters: Vec<ChapterStats> = chapters
+        .iter()
+        .filter(|chapter| chapter.counts.chars == 0)
+        .cloned()
+        .collect();
+    let outlier_chapters: Vec<ChapterStats> = chapters
+        .iter()
+        .filter(|chapter| {
+            let chars = chapter.counts.chars as f64;
+            chars > 0.0
+                && (chars < median * OUTLIER_LOW_RATIO || chars > median * OUTLIER_HIGH_RATIO)
+        })
+        .cloned()
+        .collect();
+
+    Ok(BookStats {
+        book_id,
+        chapter_count: chapters.len() as i64,
+        words: totals.words(),
+        average_chapter_chars: if chapters.is_empty() {
+            0.0
+        } else {
+            totals.chars as f64 / chapters.len() as f64
+        },
+        min_chapter_chars: chapters.iter().map(|c| c.counts.chars).min().unwrap_or(0),
+        max_chapter_chars: chapters.iter().map(|c| c.counts.chars).max().unwrap_or(0),
+        totals,
+        empty_chapters,
+        outlier_chapters,
+        chapters,
+    })
+}
+
+// 统计整本书的字数、段落、图片等信息，结果按章节缓存，章节内容修改后自动重新计算
+#[command]
+pub async fn book_stats(book_id: i64, app_handle: AppHandle) -> DbResponse<BookStats> {
+    // 统计会写入缓存，使用写连接
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+        book_stats_with(&db, book_id)
+    })
+    .await
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::open_book;
+
+    fn counts(content: &str) -> TextCounts {
+        count_text(content, &CountPatterns::new().unwrap())
+    }
+
+    #[test]
+    fn paragraphs_count_blocks_before_lines() {
+        assert_eq!(counts("<p>a</p><p>b</p>").paragraphs, 2);
+        assert_eq!(
+            counts("<h1>题</h1><div>甲</div><ul><li>乙</li><li>丙</li></ul>").paragraphs,
+            4
+        );
+        // 空段落与只有图片的段落不计入
+        assert_eq!(
+            counts("<p>a</p><p> </p><p><img src=\"a.jpg\"></p>").paragraphs,
+            1
+        );
+        assert_eq!(counts("甲\n\n乙").paragraphs, 2);
+    }
+
+    #[test]
+    fn count_text_mixes_cjk_and_latin() {
+        let counts = counts("<p>你好 world</p>\n<img src=\"a.jpg\">\n\n<p>Hello, 世界!</p>");
+        assert_eq!(counts.cjk_chars, 4);
+        assert_eq!(counts.latin_words, 2);
+        assert_eq!(counts.words(), 6);
+        assert_eq!(counts.images, 1);
+        // 只有图片的行去掉标签后为空，不计段落
+        assert_eq!(counts.paragraphs, 2);
+        // 你好world + Hello,世界!，空白不计
+        assert_eq!(counts.chars, 7 + 9);
+    }
+
+    #[test]
+    fn count_text_counts_graphemes() {
+        // 组合字符与 emoji 序列各算一个字符
+        let counts = counts("e\u{301} 👨‍👩‍👧 かな 한글");
+        assert_eq!(counts.chars, 6);
+        assert_eq!(counts.cjk_chars, 4);
+        assert_eq!(counts.latin_words, 1);
+        assert_eq!(counts.paragraphs, 1);
+    }
+
+    #[test]
+    fn count_text_empty_content() {
+        assert_eq!(counts(""), TextCounts::default());
+        assert_eq!(counts("<p> </p>\n\n").chars, 0);
+    }
+
+    #[test]
+    fn book_stats_refresh_after_edit() {
+        let db = open_book(&[("第一章", "一二三"), ("第二章", "")]);
+
+        let stats = book_stats_with(&db, 1).unwrap();
+        assert_eq!(stats.chapter_count, 2);
+        assert_eq!(stats.words, 3);
+        assert_eq!(stats.empty_chapters.len(), 1);
+
+        // 修改内容后缓存失效，重新统计
+        db.execute(
+            "UPDATE ee_chapter SET content = '一二三四五' WHERE id = 2",
+            [],
+        )
+        .unwrap();
+        let stats = book_stats_with(&db, 1).unwrap();
+        assert_eq!(stats.words, 8);
+        assert!(stats.empty_chapters.is_empty());
+        assert_eq!(stats.max_chapter_chars, 5);
+    }
+
+    #[test]
+    fn book_word_count_ignores_markup_with_or_without_cache() {
+        let db = open_book(&[
+            ("第一章", "<p class=\"body\">一二三</p>"),
+            ("第二章", "<p>hello world</p>"),
+        ]);
+        assert_eq!(book_word_count(&db, 1).unwrap(), 5);
+        book_stats_with(&db, 1).unwrap();
+        assert_eq!(book_word_count(&db, 1).unwrap(), 5);
+    }
+}
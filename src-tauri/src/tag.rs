@@ -0,0 +1,431 @@
+use crate::database::{
+    get_current_time_string, get_db_connection, get_read_connection, load_books_where, Book,
+    BookSort, DbResponse,
+};
+use crate::error::{AppError, AppResult};
+use crate::pool::run_blocking;
+use rusqlite::types::Value;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use serde::{Deserialize, Serialize};
+use tauri::{command, AppHandle};
+
+// 标签类型：普通标签可以给一本书打多个；书单（collection）用于把书籍归到用户自建的分组中
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TagKind {
+    #[default]
+    Tag,
+    Collection,
+}
+
+impl TagKind {
+    fn as_str(&self) -> &'static str {
+        match self {
+            TagKind::Tag => "tag",
+            TagKind::Collection => "collection",
+        }
+    }
+
+    fn from_str(value: &str) -> Self {
+        match value {
+            "collection" => TagKind::Collection,
+            _ => TagKind::Tag,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Tag {
+    pub id: i64,
+    pub name: String,
+    pub kind: TagKind,
+    // 使用该标签的未删除书籍数量
+    pub book_count: i64,
+}
+
+fn load_tags(db: &Connection, condition: &str, value: Value) -> Result<Vec<Tag>, rusqlite::Error> {
+    let sql = format!(
+        "SELECT t.id, t.name, t.kind, \
+                (SELECT COUNT(*) FROM ee_book_tag bt JOIN ee_book b ON b.id = bt.bookId \
+                 WHERE bt.tagId = t.id AND b.isDel = 0) \
+         FROM ee_tag t WHERE {} ORDER BY t.kind, t.sortIndex, t.name",
+        condition
+    );
+    let mut stmt = db.prepare(&sql)?;
+    let rows = stmt.query_map([value], |row| {
+        Ok(Tag {
+            id: row.get(0)?,
+            name: row.get(1)?,
+            kind: TagKind::from_str(&row.get::<_, String>(2)?),
+            book_count: row.get(3)?,
+        })
+    })?;
+    rows.collect()
+}
+
+fn load_tag(db: &Connection, id: i64) -> AppResult<Tag> {
+    load_tags(db, "t.id = ?", Value::Integer(id))?
+        .pop()
+        .ok_or_else(|| AppError::NotFound(format!("标签 {} 不存在", id)))
+}
+
+fn check_tag_name(db: &Connection, name: &str, except_id: Option<i64>) -> AppResult<String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(AppError::InvalidInput("标签名称不能为空".to_string()));
+    }
+    let existing: Option<i64> = db
+        .query_row(
+            "SELECT id FROM ee_tag WHERE name = ? AND id IS NOT ?",
+            params![name, except_id],
+            |row| row.get(0),
+        )
+        .optional()?;
+    if existing.is_some() {
+        return Err(AppError::Conflict(format!("标签“{}”已存在", name)));
+    }
+    Ok(name.to_string())
+}
+
+// 复制书籍时一并复制标签
+pub fn copy_book_tags(tx: &Transaction, from_id: i64, to_id: i64) -> Result<(), rusqlite::Error> {
+    tx.execute(
+        "INSERT OR IGNORE INTO ee_book_tag (bookId, tagId) \
+         SELECT ?, tagId FROM ee_book_tag WHERE bookId = ?",
+        params![to_id, from_id],
+    )?;
+    Ok(())
+}
+
+pub fn delete_book_tags(tx: &Transaction, book_id: i64) -> Result<(), rusqlite::Error> {
+    tx.execute("DELETE FROM ee_book_tag WHERE bookId = ?", params![book_id])?;
+    Ok(())
+}
+
+// 获取所有标签与书单，kind 为空时返回全部
+#[command]
+pub async fn list_tags(kind: Option<TagKind>, app_handle: AppHandle) -> DbResponse<Vec<Tag>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        Ok(list_tags_with(&db, kind)?)
+    })
+    .await
+    .into()
+}
+
+fn list_tags_with(db: &Connection, kind: Option<TagKind>) -> Result<Vec<Tag>, rusqlite::Error> {
+    match kind {
+        Some(kind) => load_tags(db, "t.kind = ?", Value::Text(kind.as_str().to_string())),
+        None => load_tags(db, "1 = ?", Value::Integer(1)),
+    }
+}
+
+#[command]
+pub async fn create_tag(
+    name: String,
+    kind: Option<TagKind>,
+    app_handle: AppHandle,
+) -> DbResponse<Tag> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+
+        create_tag_with(&db, &name, kind.unwrap_or_default())
+    })
+    .await
+    .into()
+}
+
+fn create_tag_with(db: &Connection, name: &str, kind: TagKind) -> AppResult<Tag> {
+    let name = check_tag_name(db, name, None)?;
+    db.execute(
+        "INSERT INTO ee_tag (name, kind, sortIndex, createTime) \
+         VALUES (?, ?, (SELECT COALESCE(MAX(sortIndex) + 1, 0) FROM ee_tag), ?)",
+        params![name, kind.as_str(), get_current_time_string()],
+    )?;
+    load_tag(db, db.last_insert_rowid())
+}
+
+#[command]
+pub async fn rename_tag(id: i64, name: String, app_handle: AppHandle) -> DbResponse<Tag> {
+    run_blocking(app_handle, move |state| {
+        let db = get_db_connection(state)?;
+
+        let name = check_tag_name(&db, &name, Some(id))?;
+        db.execute("UPDATE ee_tag SET name = ? WHERE id = ?", params![name, id])?;
+        load_tag(&db, id)
+    })
+    .await
+    .into()
+}
+
+// 删除标签，书籍本身不受影响
+#[command]
+pub async fn delete_tag(id: i64, app_handle: AppHandle) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        let tx = db.transaction()?;
+        tx.execute("DELETE FROM ee_book_tag WHERE tagId = ?", params![id])?;
+        tx.execute("DELETE FROM ee_tag WHERE id = ?", params![id])?;
+        tx.commit()?;
+        Ok(id)
+    })
+    .await
+    .into()
+}
+
+// 获取一本书的标签与书单
+#[command]
+pub async fn get_book_tags(book_id: i64, app_handle: AppHandle) -> DbResponse<Vec<Tag>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+
+        Ok(load_tags(
+            &db,
+            "t.id IN (SELECT tagId FROM ee_book_tag WHERE bookId = ?)",
+            Value::Integer(book_id),
+        )?)
+    })
+    .await
+    .into()
+}
+
+// 设置一本书的全部标签（覆盖原有标签）
+#[command]
+pub async fn set_book_tags(
+    book_id: i64,
+    tag_ids: Vec<i64>,
+    app_handle: AppHandle,
+) -> DbResponse<i64> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        let tx = db.transaction()?;
+        set_book_tags_tx(&tx, book_id, &tag_ids)?;
+        tx.commit()?;
+        Ok(book_id)
+    })
+    .await
+    .into()
+}
+
+// 不存在的标签 id 直接忽略
+fn set_book_tags_tx(
+    tx: &Transaction,
+    book_id: i64,
+    tag_ids: &[i64],
+) -> Result<(), rusqlite::Error> {
+    delete_book_tags(tx, book_id)?;
+    for tag_id in tag_ids {
+        tx.execute(
+            "INSERT OR IGNORE INTO ee_book_tag (bookId, tagId) \
+             SELECT ?, id FROM ee_tag WHERE id = ?",
+            params![book_id, tag_id],
+        )?;
+    }
+    Ok(())
+}
+
+// 给多本书添加或移除同一个标签
+#[command]
+pub async fn assign_tag(
+    tag_id: i64,
+    book_ids: Vec<i64>,
+    assigned: bool,
+    app_handle: AppHandle,
+) -> DbResponse<usize> {
+    run_blocking(app_handle, move |state| {
+        let mut db = get_db_connection(state)?;
+
+        let tx = db.transaction()?;
+        let count = assign_tag_tx(&tx, tag_id, &book_ids, assigned)?;
+        tx.commit()?;
+        Ok(count)
+    })
+    .await
+    .into()
+}
+
+// 返回实际添加或移除的书籍数，已有或没有该标签的书籍不计入
+fn assign_tag_tx(
+    tx: &Transaction,
+    tag_id: i64,
+    book_ids: &[i64],
+    assigned: bool,
+) -> Result<usize, rusqlite::Error> {
+    let mut count = 0;
+    for book_id in book_ids {
+        count += if assigned {
+            tx.execute(
+                "INSERT OR IGNORE INTO ee_book_tag (bookId, tagId) \
+                 SELECT ?, id FROM ee_tag WHERE id = ?",
+                params![book_id, tag_id],
+            )?
+        } else {
+            tx.execute(
+                "DELETE FROM ee_book_tag WHERE bookId = ? AND tagId = ?",
+                params![book_id, tag_id],
+            )?
+        };
+    }
+    Ok(count)
+}
+
+// 按标签查询书籍，match_all 为 true 时要求同时拥有所有标签，否则拥有任一标签即可
+#[command]
+pub async fn get_books_by_tags(
+    tag_ids: Vec<i64>,
+    match_all: Option<bool>,
+    sort: Option<BookSort>,
+    app_handle: AppHandle,
+) -> DbResponse<Vec<Book>> {
+    run_blocking(app_handle, move |state| {
+        let db = get_read_connection(state)?;
+        Ok(get_books_by_tags_with(
+            &db,
+            &tag_ids,
+            match_all.unwrap_or(false),
+            sort.unwrap_or_default(),
+        )?)
+    })
+    .await
+    .into()
+}
+
+fn get_books_by_tags_with(
+    db: &Connection,
+    tag_ids: &[i64],
+    match_all: bool,
+    sort: BookSort,
+) -> Result<Vec<Book>, rusqlite::Error> {
+    if tag_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    let placeholders = vec!["?"; tag_ids.len()].join(", ");
+    let mut condition = format!(
+        "id IN (SELECT bookId FROM ee_book_tag WHERE tagId IN ({}) GROUP BY bookId",
+        placeholders
+    );
+    let mut values: Vec<Value> = tag_ids.iter().map(|id| Value::Integer(*id)).collect();
+    if match_all {
+        condition.push_str(" HAVING COUNT(DISTINCT tagId) = ?");
+        values.push(Value::Integer(tag_ids.len() as i64));
+    }
+    condition.push(')');
+    load_books_where(db, &condition, values, sort)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::open_db;
+
+    // 三本书（id 1、2、3），标签“科幻”“长篇”与书单“在读”
+    fn open_library() -> (Connection, i64, i64, i64) {
+        let db = open_db();
+        db.execute_batch(
+            "INSERT INTO ee_book (title, isDel) VALUES ('甲', 0);
+             INSERT INTO ee_book (title, isDel) VALUES ('乙', 0);
+             INSERT INTO ee_book (title, isDel) VALUES ('丙', 0);",
+        )
+        .unwrap();
+        let scifi = create_tag_with(&db, "科幻", TagKind::Tag).unwrap().id;
+        let novel = create_tag_with(&db, "长篇", TagKind::Tag).unwrap().id;
+        let reading = create_tag_with(&db, "在读", TagKind::Collection)
+            .unwrap()
+            .id;
+        (db, scifi, novel, reading)
+    }
+
+    fn book_ids(db: &Connection, tag_ids: &[i64], match_all: bool) -> Vec<i64> {
+        let mut ids: Vec<i64> = get_books_by_tags_with(db, tag_ids, match_all, BookSort::default())
+            .unwrap()
+            .into_iter()
+            .map(|book| book.id)
+            .collect();
+        ids.sort();
+        ids
+    }
+
+    #[test]
+    fn set_book_tags_replaces_existing_tags() {
+        let (mut db, scifi, novel, reading) = open_library();
+        let tx = db.transaction().unwrap();
+        set_book_tags_tx(&tx, 1, &[scifi, reading]).unwrap();
+        // 覆盖原有标签，不存在的标签忽略
+        set_book_tags_tx(&tx, 1, &[novel, reading, 999]).unwrap();
+        tx.commit().unwrap();
+
+        let names: Vec<String> = load_tags(
+            &db,
+            "t.id IN (SELECT tagId FROM ee_book_tag WHERE bookId = ?)",
+            Value::Integer(1),
+        )
+        .unwrap()
+        .into_iter()
+        .map(|tag| tag.name)
+        .collect();
+        // 书单排在标签之前
+        assert_eq!(names, vec!["在读", "长篇"]);
+    }
+
+    #[test]
+    fn assign_tag_counts_only_changed_books() {
+        let (mut db, scifi, _, _) = open_library();
+        let tx = db.transaction().unwrap();
+        assert_eq!(assign_tag_tx(&tx, scifi, &[1, 2], true).unwrap(), 2);
+        assert_eq!(assign_tag_tx(&tx, scifi, &[2, 3], true).unwrap(), 1);
+        assert_eq!(assign_tag_tx(&tx, scifi, &[1, 2], false).unwrap(), 2);
+        tx.commit().unwrap();
+        assert_eq!(book_ids(&db, &[scifi], false), vec![3]);
+    }
+
+    #[test]
+    fn books_by_tags_match_any_or_all() {
+        let (mut db, scifi, novel, _) = open_library();
+        let tx = db.transaction().unwrap();
+        set_book_tags_tx(&tx, 1, &[scifi, novel]).unwrap();
+        set_book_tags_tx(&tx, 2, &[scifi]).unwrap();
+        set_book_tags_tx(&tx, 3, &[novel]).unwrap();
+        tx.commit().unwrap();
+
+        assert_eq!(book_ids(&db, &[scifi, novel], false), vec![1, 2, 3]);
+        assert_eq!(book_ids(&db, &[scifi, novel], true), vec![1]);
+        assert!(book_ids(&db, &[], true).is_empty());
+
+        // 回收站中的书籍不返回
+        db.execute("UPDATE ee_book SET isDel = 1 WHERE id = 1", [])
+            .unwrap();
+        assert!(book_ids(&db, &[scifi, novel], true).is_empty());
+    }
+
+    #[test]
+    fn tags_and_collections_are_listed_separately() {
+        let (mut db, scifi, _, reading) = open_library();
+        let tx = db.transaction().unwrap();
+        set_book_tags_tx(&tx, 1, &[scifi, reading]).unwrap();
+        tx.commit().unwrap();
+
+        let names = |kind| -> Vec<String> {
+            list_tags_with(&db, kind)
+                .unwrap()
+                .into_iter()
+                .map(|tag| tag.name)
+                .collect()
+        };
+        assert_eq!(names(Some(TagKind::Tag)), vec!["科幻", "长篇"]);
+        assert_eq!(names(Some(TagKind::Collection)), vec!["在读"]);
+        assert_eq!(names(None), vec!["在读", "科幻", "长篇"]);
+
+        let collection = load_tag(&db, reading).unwrap();
+        assert_eq!(collection.kind, TagKind::Collection);
+        assert_eq!(collection.book_count, 1);
+        // 标签与书单共用名称空间
+        assert!(matches!(
+            create_tag_with(&db, "在读", TagKind::Tag),
+            Err(AppError::Conflict(_))
+        ));
+    }
+}
@@ -0,0 +1,33 @@
+use crate::compress::read_content;
+use crate::database::insert_chapter;
+use crate::migration::run_migrations;
+use rusqlite::{params, Connection};
+
+// 已执行全部迁移的内存数据库
+pub fn open_db() -> Connection {
+    let mut db = Connection::open_in_memory().unwrap();
+    run_migrations(&mut db, &std::env::temp_dir()).unwrap();
+    db
+}
+
+// 一本 id 为 1 的书，按顺序插入 (标题, 正文) 章节，章节 id 从 1 开始，href 依次为 c1、c2…
+pub fn open_book(chapters: &[(&str, &str)]) -> Connection {
+    let db = open_db();
+    db.execute("INSERT INTO ee_book (title, isDel) VALUES ('书', 0)", [])
+        .unwrap();
+    for (index, (label, content)) in chapters.iter().enumerate() {
+        let href = format!("c{}", index + 1);
+        insert_chapter(&db, 1, label, &href, content, None).unwrap();
+    }
+    db
+}
+
+// 读取章节正文（自动解压）
+pub fn content(db: &Connection, id: i64) -> String {
+    db.query_row(
+        "SELECT content FROM ee_chapter WHERE id = ?",
+        params![id],
+        |row| read_content(row, 0),
+    )
+    .unwrap()
+}
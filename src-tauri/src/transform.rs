@@ -0,0 +1,357 @@
+use crate::database::{
+    get_db_connection, query_chapters_with, update_chapters_tx, ChapterQuery, ChapterUpdate,
+    DbResponse,
+};
+use crate::error::{AppError, AppResult};
+use crate::pool::run_blocking;
+use crate::revision::RevisionContext;
+use crate::search::escape_html;
+use crate::setup::AppState;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::ipc::Channel;
+use tauri::{command, AppHandle, State};
+
+// 批量处理操作，按顺序依次作用于每个章节
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(
+    tag = "type",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum TransformOp {
+    // 去掉每行首尾空白
+    Trim,
+    // 去掉行首空白后添加缩进
+    Indent {
+        prefix: String,
+    },
+    // 删除空行
+    DropBlankLines,
+    // 第一行包含章名时删除第一行
+    StripTitle,
+    // 第一行不包含章名时插入 <h3>章名</h3>
+    InsertTitle,
+    // 正则替换，replacement 中可使用 $1 等分组引用
+    RegexReplace {
+        pattern: String,
+        replacement: String,
+        #[serde(default)]
+        case_insensitive: bool,
+    },
+}
+
+// 编译后的操作（正则只编译一次）
+enum CompiledOp {
+    Trim,
+    Indent(String),
+    DropBlankLines,
+    StripTitle,
+    InsertTitle,
+    RegexReplace(Regex, String),
+}
+
+fn compile_ops(ops: &[TransformOp]) -> AppResult<Vec<CompiledOp>> {
+    ops.iter()
+        .map(|op| {
+            Ok(match op {
+                TransformOp::Trim => CompiledOp::Trim,
+                TransformOp::Indent { prefix } => CompiledOp::Indent(prefix.clone()),
+                TransformOp::DropBlankLines => CompiledOp::DropBlankLines,
+                TransformOp::StripTitle => CompiledOp::StripTitle,
+                TransformOp::InsertTitle => CompiledOp::InsertTitle,
+                TransformOp::RegexReplace {
+                    pattern,
+                    replacement,
+                    case_insensitive,
+                } => {
+                    let regex = RegexBuilder::new(pattern)
+                        .case_insensitive(*case_insensitive)
+                        .multi_line(true)
+                        .build()
+                        .map_err(|err| AppError::from(err).context("正则表达式错误"))?;
+                    CompiledOp::RegexReplace(regex, replacement.clone())
+                }
+            })
+        })
+        .collect()
+}
+
+fn map_lines(content: &str, f: impl Fn(&str) -> String) -> String {
+    content.split('\n').map(f).collect::<Vec<_>>().join("\n")
+}
+
+// 对单个章节内容依次执行所有操作，规则与前端单章处理保持一致
+fn apply_ops(content: &str, label: &str, ops: &[CompiledOp]) -> String {
+    let mut content = content.to_string();
+    for op in ops {
+        content = match op {
+            CompiledOp::Trim => map_lines(&content, |line| line.trim().to_string()),
+            CompiledOp::Indent(prefix) => {
+                map_lines(&content, |line| format!("{}{}", prefix, line.trim_start()))
+            }
+            CompiledOp::DropBlankLines => content
+                .split('\n')
+                .filter(|line| !line.trim().is_empty())
+                .collect::<Vec<_>>()
+                .join("\n"),
+            // 章名为空时任何一行都包含它，不做处理
+            CompiledOp::StripTitle if label.is_empty() => content,
+            // 与插入章名一致，首行中的章名可能是原文也可能是转义后的文本
+            CompiledOp::StripTitle => {
+                let (first, rest) = content.split_once('\n').unwrap_or((&content, ""));
+                let first = first.trim();
+                if !first.is_empty()
+                    && (first.contains(label) || first.contains(&escape_html(label)))
+                {
+                    rest.to_string()
+                } else {
+                    content
+                }
+            }
+            // 章名按文本插入，其中的 < & 等需要转义
+            CompiledOp::InsertTitle => {
+                let first = content.split('\n').next().unwrap_or_default().trim();
+                let title = escape_html(label);
+                if first.contains(label) || first.contains(&title) {
+                    content
+                } else {
+                    format!("<h3>{}</h3>\n{}", title, content)
+                }
+            }
+            CompiledOp::RegexReplace(regex, replacement) => regex
+                .replace_all(&content, replacement.as_str())
+                .into_owned(),
+        };
+    }
+    content
+}
+
+// 处理进度，通过 Channel 发送给前端
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformProgress {
+    pub current: usize,
+    pub total: usize,
+    pub chapter_id: i64,
+    pub label: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransformResult {
+    pub total: usize,
+    pub changed: usize,
+    pub operation_id: String,
+}
+
+// 注册一个可取消的任务，返回取消标记。同一 id 的任务正在执行时拒绝，
+// 否则后一个任务会覆盖前一个的取消标记
+fn register_job(state: &AppState, job_id: &str) -> AppResult<Arc<AtomicBool>> {
+    let mut jobs = state.transform_jobs.lock()?;
+    if jobs.contains_key(job_id) {
+        return Err(AppError::Conflict(format!("任务“{}”正在执行", job_id)));
+    }
+    let flag = Arc::new(AtomicBool::new(false));
+    jobs.insert(job_id.to_string(), flag.clone());
+    Ok(flag)
+}
+
+fn unregister_job(state: &AppState, job_id: &str) {
+    if let Ok(mut jobs) = state.transform_jobs.lock() {
+        jobs.remove(job_id);
+    }
+}
+
+fn run_transforms(
+    state: &AppState,
+    book_id: i64,
+    job_id: &str,
+    ops: &[TransformOp],
+    cancelled: &AtomicBool,
+    on_progress: &Channel<TransformProgress>,
+) -> AppResult<TransformResult> {
+    let ops = compile_ops(ops)?;
+
+    let mut db = get_db_connection(state)?;
+    let tx = db.transaction()?;
+
+    let query = ChapterQuery {
+        book_id: Some(book_id),
+        ..Default::default()
+    };
+    let chapters = query_chapters_with(&tx, &query)?;
+    let total = chapters.len();
+
+    let mut updates = Vec::new();
+    for (index, chapter) in chapters.into_iter().enumerate() {
+        if cancelled.load(Ordering::Relaxed) {
+            // 未提交的事务在 drop 时自动回滚
+            return Err(AppError::Cancelled);
+        }
+        let content = apply_ops(&chapter.content, &chapter.label, &ops);
+        let _ = on_progress.send(TransformProgress {
+            current: index + 1,
+            total,
+            chapter_id: chapter.id,
+            label: chapter.label.clone(),
+        });
+        if content != chapter.content {
+            updates.push(ChapterUpdate {
+                id: chapter.id,
+                label: chapter.label,
+                content: Some(content),
+            });
+        }
+    }
+
+    // 以任务 id 作为操作 id，可通过 undo_operation 撤销整个批量处理
+    let ctx = RevisionContext::new("transform", Some(job_id.to_string()));
+    let results = update_chapters_tx(&tx, &updates, &ctx)?;
+    if cancelled.load(Ordering::Relaxed) {
+        return Err(AppError::Cancelled);
+    }
+    tx.commit()?;
+
+    Ok(TransformResult {
+        total,
+        changed: results.iter().filter(|r| r.changed).count(),
+        operation_id: ctx.operation_id,
+    })
+}
+
+// 对整本书执行批量处理，所有章节在同一事务中写入，可通过 cancel_transform 取消
+#[command]
+pub async fn apply_transforms(
+    book_id: i64,
+    job_id: String,
+    ops: Vec<TransformOp>,
+    on_progress: Channel<TransformProgress>,
+    app_handle: AppHandle,
+) -> DbResponse<TransformResult> {
+    run_blocking(app_handle, move |state| {
+        let cancelled = register_job(state, &job_id)?;
+        let result = run_transforms(state, book_id, &job_id, &ops, &cancelled, &on_progress);
+        unregister_job(state, &job_id);
+        result
+    })
+    .await
+    .into()
+}
+
+// 取消正在执行的批量处理
+#[command]
+pub fn cancel_transform(job_id: String, state: State<'_, AppState>) -> DbResponse<bool> {
+    (|| -> AppResult<bool> {
+        let jobs = state.transform_jobs.lock()?;
+        Ok(match jobs.get(&job_id) {
+            Some(flag) => {
+                flag.store(true, Ordering::Relaxed);
+                true
+            }
+            None => false,
+        })
+    })()
+    .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(content: &str, ops: &[TransformOp]) -> String {
+        apply_ops(content, "第一章", &compile_ops(ops).unwrap())
+    }
+
+    #[test]
+    fn trims_and_indents_lines() {
+        assert_eq!(apply("  甲  \n\t乙", &[TransformOp::Trim]), "甲\n乙");
+        let indent = TransformOp::Indent {
+            prefix: "　　".to_string(),
+        };
+        assert_eq!(apply("  甲\n乙", &[indent]), "　　甲\n　　乙");
+    }
+
+    #[test]
+    fn drops_blank_lines() {
+        assert_eq!(
+            apply("甲\n\n  \n乙", &[TransformOp::DropBlankLines]),
+            "甲\n乙"
+        );
+    }
+
+    #[test]
+    fn strips_and_inserts_title() {
+        assert_eq!(apply("第一章 起\n正文", &[TransformOp::StripTitle]), "正文");
+        assert_eq!(apply("正文", &[TransformOp::StripTitle]), "正文");
+        assert_eq!(
+            apply("正文", &[TransformOp::InsertTitle]),
+            "<h3>第一章</h3>\n正文"
+        );
+        assert_eq!(
+            apply("<h3>第一章</h3>\n正文", &[TransformOp::InsertTitle]),
+            "<h3>第一章</h3>\n正文"
+        );
+    }
+
+    #[test]
+    fn inserted_title_is_escaped() {
+        let ops = compile_ops(&[TransformOp::InsertTitle]).unwrap();
+        let inserted = apply_ops("正文", "<甲&乙>", &ops);
+        assert_eq!(inserted, "<h3>&lt;甲&amp;乙&gt;</h3>\n正文");
+        // 再次执行时识别出已插入的章名
+        assert_eq!(apply_ops(&inserted, "<甲&乙>", &ops), inserted);
+    }
+
+    #[test]
+    fn escaped_title_round_trips() {
+        let insert = compile_ops(&[TransformOp::InsertTitle]).unwrap();
+        let strip = compile_ops(&[TransformOp::StripTitle]).unwrap();
+        let inserted = apply_ops("正文", "A & B", &insert);
+        assert_eq!(inserted, "<h3>A &amp; B</h3>\n正文");
+        assert_eq!(apply_ops(&inserted, "A & B", &strip), "正文");
+    }
+
+    #[test]
+    fn empty_title_is_not_stripped() {
+        let ops = compile_ops(&[TransformOp::StripTitle]).unwrap();
+        assert_eq!(apply_ops("第一行\n正文", "", &ops), "第一行\n正文");
+    }
+
+    #[test]
+    fn applies_ops_in_order() {
+        let ops = [
+            TransformOp::DropBlankLines,
+            TransformOp::Trim,
+            TransformOp::RegexReplace {
+                pattern: r"^(.)".to_string(),
+                replacement: "<p>$1".to_string(),
+                case_insensitive: false,
+            },
+        ];
+        assert_eq!(apply(" 甲\n\n 乙", &ops), "<p>甲\n<p>乙");
+    }
+
+    #[test]
+    fn regex_replace_honours_case_insensitive() {
+        let op = |case_insensitive| TransformOp::RegexReplace {
+            pattern: "abc".to_string(),
+            replacement: "x".to_string(),
+            case_insensitive,
+        };
+        assert_eq!(apply("ABC abc", &[op(false)]), "ABC x");
+        assert_eq!(apply("ABC abc", &[op(true)]), "x x");
+    }
+
+    #[test]
+    fn rejects_invalid_regex() {
+        let ops = [TransformOp::RegexReplace {
+            pattern: "(".to_string(),
+            replacement: String::new(),
+            case_insensitive: false,
+        }];
+        assert!(compile_ops(&ops).is_err());
+    }
+}
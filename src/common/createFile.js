@@ -51,12 +51,75 @@ const formatText = (text) => {
   return paragraphs.join("\n");
 };
 
+const escapeXml = (text) =>
+  String(text)
+    .replace(/&/g, "&amp;")
+    .replace(/</g, "&lt;")
+    .replace(/>/g, "&gt;")
+    .replace(/"/g, "&quot;");
+
+// 书籍的包标识：UUID 格式的标识写成 urn:uuid:xxx，其他导入的标识原样使用
+const bookIdentifier = (uuid) => {
+  if (!uuid) return `urn:uuid:${crypto.randomUUID()}`;
+  return /^[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$/i.test(
+    uuid
+  )
+    ? `urn:uuid:${uuid}`
+    : uuid;
+};
+
+// 根据书籍的扩展元数据生成 OPF 中的 dc 元素
+const generateOpfMetadata = (author, extra = {}) => {
+  const items = [];
+  const authors = extra.authors?.length
+    ? extra.authors
+    : [{ name: author, role: "aut" }];
+  authors.forEach((x) => {
+    items.push(
+      `<dc:creator opf:role="${escapeXml(x.role)}">${escapeXml(x.name)}</dc:creator>`
+    );
+  });
+  items.push(`<dc:language>${escapeXml(extra.language || "zh")}</dc:language>`);
+  if (extra.publisher) {
+    items.push(`<dc:publisher>${escapeXml(extra.publisher)}</dc:publisher>`);
+  }
+  if (extra.pubDate) {
+    items.push(
+      `<dc:date opf:event="publication">${escapeXml(extra.pubDate)}</dc:date>`
+    );
+  }
+  (extra.identifiers ?? []).forEach((x) => {
+    items.push(
+      `<dc:identifier opf:scheme="${escapeXml(x.scheme)}">${escapeXml(x.value)}</dc:identifier>`
+    );
+  });
+  (extra.subjects ?? []).forEach((x) => {
+    items.push(`<dc:subject>${escapeXml(x)}</dc:subject>`);
+  });
+  if (extra.rights) {
+    items.push(`<dc:rights>${escapeXml(extra.rights)}</dc:rights>`);
+  }
+  // Calibre、Kobo 等通过以下 meta 识别系列
+  if (extra.series) {
+    items.push(
+      `<meta name="calibre:series" content="${escapeXml(extra.series)}"/>`
+    );
+    if (extra.seriesIndex != null) {
+      items.push(
+        `<meta name="calibre:series_index" content="${extra.seriesIndex}"/>`
+      );
+    }
+  }
+  return items.join("\n                ");
+};
+
 export const createEpub = async (metadata, chapters) => {
   return new Promise(async (resolve, reject) => {
     try {
       // 4. 现在才开始生成 EPUB 文件内容
       const imagesList = [];
       const { author, title, bookId } = metadata;
+      const identifier = bookIdentifier(metadata.uuid);
       console.log("createEpub ", author, title, bookId);
       //内容页的图片保存目录
       const imagesDir = await join(
@@ -112,7 +175,7 @@ export const createEpub = async (metadata, chapters) => {
         ` <?xml version="1.0" encoding="UTF-8"?>
             <ncx xmlns="http://www.daisy.org/z3986/2005/ncx/" version="2005-1">
                 <head>
-                <meta name="dtb:uid" content="book-id" />
+                <meta name="dtb:uid" content="${escapeXml(identifier)}" />
                 <meta name="dtb:depth" content="1" />
                 <meta name="dtb:totalPageCount" content="0" />
                 <meta name="dtb:maxPageNumber" content="0" />
@@ -231,11 +294,10 @@ export const createEpub = async (metadata, chapters) => {
             "content.opf",
             `<?xml version="1.0" encoding="UTF-8"?>
             <package xmlns="http://www.idpf.org/2007/opf" unique-identifier="book-id" version="2.0">
-              <metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+              <metadata xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:opf="http://www.idpf.org/2007/opf">
                 <dc:title>${title}</dc:title>
-                <dc:language>zh</dc:language>
-                <dc:creator>${author}</dc:creator>
-                <dc:identifier id="book-id">${new Date().getTime()}</dc:identifier>
+                <dc:identifier id="book-id">${escapeXml(identifier)}</dc:identifier>
+                ${generateOpfMetadata(author, metadata.metadata)}
                 ${
                   isCoverExists
                     ? '<meta name="cover" content="cover-image"/>'
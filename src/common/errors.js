@@ -0,0 +1,34 @@
+// 后端命令返回的错误为 { code, messageKey, detail }
+// code 用于判断错误类型，messageKey 对应下面的文案，detail 为具体原因
+const messages = {
+  "error.notFound": "记录不存在",
+  "error.invalidInput": "参数不正确",
+  "error.conflict": "与已有数据冲突",
+  "error.busy": "数据库正忙，请稍后重试",
+  "error.diskFull": "磁盘空间不足",
+  "error.permissionDenied": "没有读写权限",
+  "error.fileNotFound": "文件不存在",
+  "error.corrupt": "数据已损坏",
+  "error.encrypted": "数据库已加密，请输入正确的密码",
+  "error.network": "网络连接失败",
+  "error.cancelled": "操作已取消",
+  "error.database": "数据库错误",
+  "error.io": "文件读写错误",
+  "error.internal": "程序内部错误",
+};
+
+/**
+ * 将后端错误转换为可展示的文案
+ * @param {*} err 命令返回的错误对象，也兼容字符串和 Error
+ * @returns 错误文案
+ */
+export const errorMessage = (err) => {
+  if (!err) return "";
+  if (typeof err === "string") return err;
+  if (err instanceof Error) return err.message;
+  const message = messages[err.messageKey] || err.code || "未知错误";
+  return err.detail ? `${message}: ${err.detail}` : message;
+};
+
+// 判断错误是否为指定类型，如 isErrorCode(res.error, "BUSY")
+export const isErrorCode = (err, code) => !!err && err.code === code;
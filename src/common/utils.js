@@ -30,10 +30,14 @@ export const readTxtFile = async (file) => {
 export const loadImage = async (path) => {
   console.log("加载图片:", path);
   try {
-    const imageData = await invoke("read_image", {
+    const res = await invoke("read_image", {
       path: path,
     });
-    return `data:image/jpeg;base64,${imageData}`;
+    if (!res.success) {
+      console.error("加载图片失败:", res.error);
+      return;
+    }
+    return `data:image/jpeg;base64,${res.data}`;
   } catch (error) {
     console.error("加载图片失败:", error);
   }
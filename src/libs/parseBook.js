@@ -23,6 +23,63 @@ const generateCustomShortId = () => {
   return timestamp + random;
 };
 
+// foliate 解析出的人名等可能是多语言对象，取其中一个字符串
+const textOf = (value) => {
+  if (!value) return "";
+  if (typeof value === "string") return value;
+  if (value.name) return textOf(value.name);
+  return Object.values(value).find((x) => typeof x === "string") || "";
+};
+
+// foliate 元数据中的角色字段对应的 MARC relator 代码
+const ROLE_CODES = {
+  author: "aut",
+  translator: "trl",
+  editor: "edt",
+  illustrator: "ill",
+  artist: "art",
+  colorist: "clr",
+  narrator: "nrt",
+  contributor: "ctb",
+};
+
+// 解析 urn:isbn:xxx 形式的标识，无前缀时按 ISBN 格式判断
+const parseIdentifier = (value) => {
+  if (!value) return null;
+  if (typeof value === "object") {
+    return { scheme: value.scheme || "other", value: value.value };
+  }
+  const match = /^urn:([^:]+):(.+)$/i.exec(value);
+  if (match) return { scheme: match[1], value: match[2] };
+  const digits = value.replace(/[-\s]/g, "");
+  const scheme = /^(97[89])?\d{9}[\dX]$/i.test(digits) ? "isbn" : "other";
+  return { scheme, value };
+};
+
+// 把 EPUB 的元数据转换为 add_book 使用的扩展元数据
+const extractMetadata = (metadata) => {
+  const authors = Object.entries(ROLE_CODES).flatMap(([key, role]) =>
+    [].concat(metadata[key] ?? []).map((x) => ({ name: textOf(x), role }))
+  );
+  const identifiers = [metadata.identifier]
+    .concat(metadata.altIdentifier ?? [])
+    .map(parseIdentifier)
+    .filter((x) => x && x.value);
+  const series = [].concat(metadata.belongsTo?.series ?? [])[0];
+  const seriesIndex = parseFloat(series?.position);
+  return {
+    authors: authors.filter((x) => x.name),
+    language: [].concat(metadata.language ?? [])[0] || null,
+    publisher: textOf(metadata.publisher) || null,
+    pubDate: metadata.published || null,
+    identifiers,
+    series: textOf(series?.name) || null,
+    seriesIndex: Number.isNaN(seriesIndex) ? null : seriesIndex,
+    subjects: [].concat(metadata.subject ?? []).map(textOf).filter(Boolean),
+    rights: metadata.rights || null,
+  };
+};
+
 // 调用libs/vendor/zip.js 解压epub文件
 const unzipEpub = async (file, extractPath) => {
   console.log("开始解压epub文件:", file, extractPath);
@@ -103,14 +160,17 @@ export const openFile = async (file) => {
         //插入书籍
         let _metaData = {
           title: book.metadata.title || "未命名",
-          author: book.metadata.author?.[0]?.name || "佚名",
+          author: textOf(book.metadata.author?.[0]) || "佚名",
           description: book.metadata.description || "暂缺",
           toc: "",
+          metadata: extractMetadata(book.metadata),
+          // 沿用原书的标识，重复或缺失时由后端生成新的 UUID
+          uuid: book.metadata.identifier || null,
         };
         const res = await invoke("add_book", _metaData);
         if (res.success) {
           const bookId = res.data.id;
-          setMetaData({ ..._metaData, bookId: bookId });
+          setMetaData({ ..._metaData, bookId: bookId, uuid: res.data.uuid });
           if (book.metadata.cover) {
             await saveCoverImage(book.metadata.cover, bookId);
           }
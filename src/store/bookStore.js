@@ -1,7 +1,9 @@
 import EventBus from "../common/EventBus";
+import { errorMessage } from "../common/errors";
 import { defineStore } from "pinia";
 import { invoke } from "@tauri-apps/api/core";
-import { ref } from "vue";
+import { ref, toRaw } from "vue";
+import { ElMessage } from "element-plus";
 
 export const useBookStore = defineStore("bookStore", {
   state: () => ({
@@ -31,6 +33,36 @@ export const useBookStore = defineStore("bookStore", {
     clearToc() {
       this.toc = null;
     },
+    // 重新读取数据库中的目录，目录项的 id 为目录节点 id
+    async reloadToc() {
+      const res = await invoke("get_book", { id: this.metaData.bookId });
+      if (res.success) {
+        this.toc = JSON.parse(res.data.toc || "[]");
+      } else {
+        ElMessage.error(errorMessage(res.error));
+      }
+    },
+    // 整体保存本地修改后的目录（新增、删除、改名），保存后重新读取以获得新的节点 id
+    async saveToc() {
+      const res = await invoke("update_toc", {
+        id: this.metaData.bookId,
+        toc: JSON.stringify(toRaw(this.toc) || []),
+      });
+      if (!res.success) {
+        ElMessage.error(errorMessage(res.error));
+      }
+      await this.reloadToc();
+    },
+    // 调用目录节点命令（移动、缩进）后重新读取目录并刷新视图
+    async runTocCommand(command, args, href) {
+      const res = await invoke(command, args);
+      if (!res.success) {
+        ElMessage.error(errorMessage(res.error));
+        return;
+      }
+      await this.reloadToc();
+      EventBus.emit("updateToc", href);
+    },
     setIsAllEdit() {
       this.isAllEdit = !this.isAllEdit;
     },
@@ -63,8 +95,10 @@ export const useBookStore = defineStore("bookStore", {
             // 从数组中删除元素
             items.splice(i, 1);
 
-            // 触发更新目录事件，传入参考项的href或null
-            EventBus.emit("updateToc", refItem ? refItem.href : null);
+            // 保存后触发更新目录事件，传入参考项的href或null
+            this.saveToc().then(() => {
+              EventBus.emit("updateToc", refItem ? refItem.href : null);
+            });
             return true;
           }
 
@@ -85,7 +119,7 @@ export const useBookStore = defineStore("bookStore", {
     },
     // 插入数据库中 并更新目录以及当前章节
     async addTocByHref(href, tocItem) {
-      await invoke("add_chapter", tocItem).then((res) => {
+      await invoke("add_chapter", tocItem).then(async (res) => {
         if (res.success) {
           const item = {
             label: tocItem.label,
@@ -105,6 +139,7 @@ export const useBookStore = defineStore("bookStore", {
             }
             this.toc.push(item);
           }
+          await this.saveToc();
           // 发送插入成功事件
           EventBus.emit("addChapterRes", res);
           // 发送更新目录事件
@@ -113,7 +148,7 @@ export const useBookStore = defineStore("bookStore", {
       });
     },
     //把fromHref移动到toHref的后面
-    moveToc(fromHref, toHref) {
+    async moveToc(fromHref, toHref) {
       // 递归查找目标项及其父级数组
       const findItemAndParent = (href, items, parent = null) => {
         for (let i = 0; i < items.length; i++) {
@@ -131,37 +166,26 @@ export const useBookStore = defineStore("bookStore", {
         return null;
       };
 
-      // 查找要移动的项及其父级
       const fromResult = findItemAndParent(fromHref, this.toc);
-      console.log("fromResult", fromResult);
-      if (!fromResult) {
-        console.log("未找到要移动的目录项:", fromHref);
-        return;
-      }
-      const {
-        item: movingItem,
-        parent: fromParent,
-        index: fromIndex,
-        items: fromItems,
-      } = fromResult;
-
-      // 从原位置移除要移动的项
-      fromItems.splice(fromIndex, 1);
-
-      // 查找目标项及其父级
       const toResult = findItemAndParent(toHref, this.toc);
-      if (!toResult) {
-        console.log("未找到目标目录项:", toHref);
-        // 若未找到目标项，将移动项放回原位置
-        fromItems.splice(fromIndex, 0, movingItem);
+      if (!fromResult || !toResult) {
+        console.log("未找到目录项:", fromHref, toHref);
         return;
       }
-      const { parent: toParent, index: toIndex, items: toItems } = toResult;
-      console.log("toResult", toResult);
-      toItems.splice(toIndex + 1, 0, movingItem);
-
-      // 触发更新目录事件
-      EventBus.emit("updateToc", movingItem.href);
+      if (fromResult.item === toResult.item) return;
+      // 目标位置按移除被移动项后的兄弟列表计算
+      const siblings = toResult.items.filter(
+        (item) => item !== fromResult.item
+      );
+      await this.runTocCommand(
+        "move_toc_node",
+        {
+          id: fromResult.item.id,
+          parentId: toResult.parent ? toResult.parent.id : null,
+          index: siblings.indexOf(toResult.item) + 1,
+        },
+        fromResult.item.href
+      );
     },
     updateTocByHref(newItem) {
       const tocItem = this.findTocByHref(newItem.id);
@@ -169,7 +193,9 @@ export const useBookStore = defineStore("bookStore", {
       if (tocItem) {
         tocItem.label = newItem.label;
         console.log("更新后toc", this.toc);
-        EventBus.emit("updateToc", tocItem.href);
+        this.saveToc().then(() => {
+          EventBus.emit("updateToc", tocItem.href);
+        });
       }
     },
     findTocByHref(href) {
@@ -190,23 +216,9 @@ export const useBookStore = defineStore("bookStore", {
       return findItem(href, this.toc);
     }, // 新增方法：让某个子对象升级
     upperToc(href) {
-      const parent = this.findParentByHref(href);
-      if (parent) {
-        const items = parent.subitems;
-        const index = items.findIndex((item) => item.href == href);
-        if (index > -1) {
-          const item = items.splice(index, 1)[0];
-          // 找到父对象所在的层级
-          const grandParent = this.findParentByHref(parent.href);
-          if (grandParent) {
-            const insertIndex = grandParent.subitems.indexOf(parent) + 1;
-            grandParent.subitems.splice(insertIndex, 0, item);
-          } else {
-            // 如果父对象在顶级，直接添加到顶级数组
-            this.toc.splice(this.toc.indexOf(parent) + 1, 0, item);
-          }
-          EventBus.emit("updateToc", item.href);
-        }
+      const item = this.findTocByHref(href);
+      if (item && this.findParentByHref(href)) {
+        this.runTocCommand("outdent_toc_node", { id: item.id }, href);
       }
     },
     lowerToc(href) {
@@ -214,16 +226,9 @@ export const useBookStore = defineStore("bookStore", {
       const items = parent ? parent.subitems : this.toc;
       const index = items.findIndex((item) => item.href == href);
 
+      // 确保前面有兄弟对象
       if (index > 0) {
-        // 确保前面有兄弟对象
-        const prevItem = items[index - 1];
-        const item = items.splice(index, 1)[0];
-
-        if (!prevItem.subitems) {
-          prevItem.subitems = [];
-        }
-        prevItem.subitems.push(item);
-        EventBus.emit("updateToc", item.href);
+        this.runTocCommand("indent_toc_node", { id: items[index].id }, href);
       }
     },
     // 新增方法：让某个子对象位置向上移动一个位置
@@ -232,11 +237,17 @@ export const useBookStore = defineStore("bookStore", {
       const items = parent ? parent.subitems : this.toc;
       const index = items.findIndex((item) => item.href == href);
 
+      // 确保不是第一个元素
       if (index > 0) {
-        // 确保不是第一个元素
-        // 交换当前元素和前一个元素的位置
-        [items[index - 1], items[index]] = [items[index], items[index - 1]];
-        EventBus.emit("updateToc", href);
+        this.runTocCommand(
+          "move_toc_node",
+          {
+            id: items[index].id,
+            parentId: parent ? parent.id : null,
+            index: index - 1,
+          },
+          href
+        );
       }
     },
     // 新增方法：让某个子对象位置向下移动一个位置
@@ -246,10 +257,16 @@ export const useBookStore = defineStore("bookStore", {
       const index = items.findIndex((item) => item.href == href);
 
       // 确保不是最后一个元素
-      if (index < items.length - 1) {
-        // 交换当前元素和后一个元素的位置
-        [items[index + 1], items[index]] = [items[index], items[index + 1]];
-        EventBus.emit("updateToc", href);
+      if (index > -1 && index < items.length - 1) {
+        this.runTocCommand(
+          "move_toc_node",
+          {
+            id: items[index].id,
+            parentId: parent ? parent.id : null,
+            index: index + 1,
+          },
+          href
+        );
       }
     }, // 新增辅助方法：查找父对象
     findParentByHref(href) {